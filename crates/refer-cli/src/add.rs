@@ -1,10 +1,7 @@
 use crate::{default_refer_location, ReferEditor, ReferResult};
 use inquire::{Editor, Text};
 use refer::{Reader, Writer};
-use std::{
-    ffi::OsString,
-    fs::{File, OpenOptions},
-};
+use std::{ffi::OsString, fs::File};
 
 pub fn add_rc(
     journal: bool,
@@ -14,11 +11,7 @@ pub fn add_rc(
     editor_exec: ReferEditor,
 ) -> ReferResult<()> {
     let default_location = default_refer_location()?;
-    let file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open(default_location)?;
-    let writer = Writer::new(file);
+    let writer = Writer::from_path_append(default_location)?;
 
     if let Some(cli_string) = string {
         write_from_cli_or_editor_string(cli_string, writer)?;
@@ -43,9 +36,7 @@ fn write_from_cli_or_editor_string(string: String, mut writer: Writer<File>) ->
     let mut reader = Reader::new(string.as_bytes());
 
     for result in reader.records() {
-        let record = result?;
-        let checked_record_string = record.to_string();
-        writer.write_record(vec![checked_record_string])?;
+        writer.write(&result?)?;
     }
 
     writer.flush()?;
@@ -127,4 +118,4 @@ fn write_journal_record(mut writer: Writer<File>) -> ReferResult<()> {
 #[allow(unused_variables, unused_mut, dead_code)]
 fn write_book_record(mut writer: Writer<File>) {
     todo!()
-}
\ No newline at end of file
+}
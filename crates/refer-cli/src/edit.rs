@@ -24,12 +24,10 @@ pub fn edit_rc(keywords: &[String], all: &bool, editor_exec: ReferEditor) -> Ref
         let r_selection = Select::new("Title: ", checked_titles).prompt();
         let selection = r_selection?;
 
-        // find the line number of the match
-        let mut line_no = 0;
-
         let mut reader = Reader::from_path(default_location.clone())?;
-        for result in reader.records() {
-            let record = result?;
+        for result in reader.records_with_position() {
+            let (position, record) = result?;
+            let line_no = position.line();
             let tmp_title = record.clone().title.unwrap_or_else(|| "".into());
             // if we hit the title
             if tmp_title == selection {
@@ -59,18 +57,14 @@ pub fn edit_rc(keywords: &[String], all: &bool, editor_exec: ReferEditor) -> Ref
                 // quit the loop here.
                 break;
             }
-            // +2 because there is a line break between records.
-            line_no += &record.to_string().lines().count() + 2;
         }
     } else {
         let CheckedRecord { title, styled: _ } = matches_from_keywords(reader, keywords)?;
 
-        // find the line number of the match
-        let mut line_no = 0;
-
         let mut reader = Reader::from_path(default_location.clone())?;
-        for result in reader.records() {
-            let record = result?;
+        for result in reader.records_with_position() {
+            let (position, record) = result?;
+            let line_no = position.line();
             let tmp_title = record.clone().title.unwrap_or_else(|| "".into());
             // if we hit the title
             if tmp_title == title {
@@ -95,8 +89,6 @@ pub fn edit_rc(keywords: &[String], all: &bool, editor_exec: ReferEditor) -> Ref
                 // quit the loop here.
                 break;
             }
-            // +2 because there is a line break between records.
-            line_no += &record.to_string().lines().count() + 2;
         }
     }
     Ok(())
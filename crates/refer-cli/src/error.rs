@@ -54,7 +54,7 @@ impl From<io::Error> for ReferError {
 
 impl From<ReferError> for io::Error {
     fn from(err: ReferError) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, err)
+        io::Error::other(err)
     }
 }
 
@@ -1,6 +1,6 @@
 use add::add_rc;
 use edit::edit_rc;
-use error::{ReferError, ReferErrorKind, ReferResult};
+pub use error::{ReferError, ReferErrorKind, ReferResult};
 use inquire::{formatter::OptionFormatter, Select};
 use refer::{Error as InnerReferError, Reader, StyleBuilder};
 use setup::setup_rc;
@@ -0,0 +1,907 @@
+/*!
+Conversion between refer [`Record`]s and BibTeX entries.
+
+Only the `@article`, `@book`, `@incollection` and `@techreport` entry
+types are mapped specially, other entry types are read using the
+general field mapping. `@string` macros are expanded, `@comment` and
+`@preamble` are skipped.
+
+| refer | BibTeX                                   |
+|-------|------------------------------------------|
+| %A    | author                                   |
+| %B    | booktitle                                |
+| %C    | address                                  |
+| %D    | year (and month)                         |
+| %E    | editor                                   |
+| %I    | publisher (institution for techreport)   |
+| %J    | journal                                  |
+| %K    | keywords                                 |
+| %L    | the citation key                         |
+| %N    | number (for techreport this is %R)       |
+| %O    | note, or doi/url                         |
+| %P    | pages                                    |
+| %S    | series                                   |
+| %T    | title                                    |
+| %V    | volume                                   |
+| %X    | annote, or abstract                      |
+
+# Example
+
+```
+use refer::bibtex;
+
+let bib = r#"@article{brown2023,
+    author = {Brown, Max and M{\"u}ller, J.},
+    title = {Parasitic plants},
+    journal = "PNAS",
+    year = 2023, month = mar,
+    pages = {1--10}
+}"#;
+
+let records = bibtex::from_str(bib).unwrap();
+assert_eq!(records[0].date.as_deref(), Some("March 2023"));
+assert_eq!(records[0].author[1].last, "Müller");
+
+let entry = bibtex::to_string(&records[0]);
+assert!(entry.starts_with("@article{brown2023,"));
+```
+*/
+
+use nom::{bytes::complete::take_while1, character::complete::char, IResult};
+use std::collections::HashMap;
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    pages::PageFormat,
+    record::{Author, Record, RecordType},
+};
+
+/// Month names used for the `month` field, and the predefined month macros.
+const MONTHS: [(&str, &str); 12] = [
+    ("jan", "January"),
+    ("feb", "February"),
+    ("mar", "March"),
+    ("apr", "April"),
+    ("may", "May"),
+    ("jun", "June"),
+    ("jul", "July"),
+    ("aug", "August"),
+    ("sep", "September"),
+    ("oct", "October"),
+    ("nov", "November"),
+    ("dec", "December"),
+];
+
+/// LaTeX accent commands and the unicode combining character they add.
+const ACCENTS: [(char, char); 12] = [
+    ('"', '\u{308}'),
+    ('\'', '\u{301}'),
+    ('`', '\u{300}'),
+    ('^', '\u{302}'),
+    ('~', '\u{303}'),
+    ('=', '\u{304}'),
+    ('.', '\u{307}'),
+    ('u', '\u{306}'),
+    ('v', '\u{30C}'),
+    ('H', '\u{30B}'),
+    ('c', '\u{327}'),
+    ('k', '\u{328}'),
+];
+
+/// LaTeX commands which stand for a whole letter.
+const LETTERS: [(&str, &str); 14] = [
+    ("ss", "ß"),
+    ("o", "ø"),
+    ("O", "Ø"),
+    ("aa", "å"),
+    ("AA", "Å"),
+    ("ae", "æ"),
+    ("AE", "Æ"),
+    ("oe", "œ"),
+    ("OE", "Œ"),
+    ("l", "ł"),
+    ("L", "Ł"),
+    ("i", "ı"),
+    ("j", "ȷ"),
+    ("&", "&"),
+];
+
+/// Parse all the entries in a BibTeX string into records.
+pub fn from_str(input: &str) -> Result<Vec<Record>> {
+    let mut macros: HashMap<String, String> = MONTHS
+        .iter()
+        .map(|(abbr, month)| (abbr.to_string(), month.to_string()))
+        .collect();
+    let mut records = Vec::new();
+
+    let mut rest = input;
+    // anything outside of an entry is a comment, including an `@` which
+    // isn't followed by an entry type and `{` or `(`, e.g. in an email
+    // address
+    while let Some(at) = rest.find('@') {
+        rest = &rest[at + 1..];
+        let Ok((i, typ)) = identifier(rest.trim_start()) else {
+            continue;
+        };
+        let typ = typ.to_lowercase();
+        let i = i.trim_start();
+        let (close, i) = match i.chars().next() {
+            Some('{') => ('}', &i[1..]),
+            Some('(') => (')', &i[1..]),
+            _ => continue,
+        };
+
+        match typ.as_str() {
+            "comment" | "preamble" => {
+                // skip the (balanced) body
+                let (i, _) = delimited_body(i, close)?;
+                rest = i;
+            }
+            "string" => {
+                let (i, fields) = fields(i, close, &macros)?;
+                macros.extend(fields);
+                rest = i;
+            }
+            _ => {
+                let (i, key) = match take_while1::<_, &str, ()>(|c: char| {
+                    c != ',' && c != close && !c.is_whitespace()
+                })(i.trim_start())
+                {
+                    Ok(k) => k,
+                    Err(_) => return Err(bibtex_error("expected a citation key", i)),
+                };
+                let i = i.trim_start();
+                let i = i.strip_prefix(',').unwrap_or(i);
+                let (i, fields) = fields(i, close, &macros)?;
+                records.push(to_record(&typ, key, fields));
+                rest = i;
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+/// Format a record as a single BibTeX entry. The citation key is the
+/// label (%L) if there is one, otherwise it is made from the first
+/// author and the year.
+pub fn to_string(record: &Record) -> String {
+    let typ = entry_type(record);
+    let mut fields: Vec<(&str, String)> = Vec::new();
+
+    if !record.author.is_empty() {
+        let authors: Vec<_> = record.author.iter().map(escaped_author_name).collect();
+        fields.push(("author", authors.join(" and ")));
+    } else if let Some(q) = &record.author_np {
+        // braces stop BibTeX splitting a corporate name
+        fields.push(("author", format!("{{{}}}", escape(q))));
+    }
+    if !record.editor.is_empty() {
+        fields.push(("editor", names(record.editor.iter().cloned())));
+    }
+    match typ {
+        "book" => {
+            if let Some(t) = record.title.as_ref().or(record.book.as_ref()) {
+                fields.push(("title", escape(t)));
+            }
+        }
+        _ => {
+            if let Some(t) = &record.title {
+                fields.push(("title", escape(t)));
+            }
+            if let Some(b) = &record.book {
+                fields.push(("booktitle", escape(b)));
+            }
+        }
+    }
+    if let Some(j) = &record.journal {
+        fields.push(("journal", escape(j)));
+    }
+    if let Some(v) = &record.volume {
+        fields.push(("volume", escape(v)));
+    }
+    match typ {
+        "techreport" => {
+            if let Some(r) = record.report.as_ref().or(record.government.as_ref()) {
+                fields.push(("number", escape(r)));
+            }
+        }
+        // e.g. `PhD thesis`
+        "phdthesis" | "mastersthesis" => {
+            if let Some(r) = &record.report {
+                fields.push(("type", escape(r)));
+            }
+        }
+        _ => {
+            if let Some(n) = &record.issue_number {
+                fields.push(("number", escape(n)));
+            }
+        }
+    }
+    if let Some(p) = &record.page_number {
+        let pages = match record.parsed_pages() {
+            Ok(Some(range)) => range.format(PageFormat::Expanded, "--"),
+            _ => p.replace('-', "--").replace("----", "--"),
+        };
+        fields.push(("pages", escape(&pages)));
+    }
+    let (year, month) = split_date(record.date.as_deref());
+    if let Some(y) = year {
+        fields.push(("year", escape(&y)));
+    }
+    if let Some(i) = &record.issuer {
+        let name = match typ {
+            "techreport" => "institution",
+            "phdthesis" | "mastersthesis" => "school",
+            _ => "publisher",
+        };
+        fields.push((name, escape(i)));
+    }
+    if let Some(c) = &record.place {
+        fields.push(("address", escape(c)));
+    }
+    if let Some(s) = &record.series {
+        fields.push(("series", escape(s)));
+    }
+    if let Some(k) = &record.keywords {
+        fields.push(("keywords", escape(&k.join(", "))));
+    }
+    if let Some(o) = &record.other {
+        fields.push(("note", escape(o)));
+    }
+    if let Some(x) = &record.annotation {
+        fields.push(("annote", escape(x)));
+    }
+
+    let mut entry = format!("@{}{{{},\n", typ, citation_key(record));
+    for (name, value) in fields {
+        entry += &format!("  {} = {{{}}},\n", name, value);
+    }
+    if let Some(m) = month {
+        // month macros are written bare
+        entry += &format!("  month = {},\n", m);
+    }
+    entry += "}\n";
+    entry
+}
+
+/// The BibTeX entry type for a record.
+fn entry_type(record: &Record) -> &'static str {
+    match record.record_type() {
+        RecordType::Journal => "article",
+        RecordType::Book => "book",
+        RecordType::ArticleInBook => "incollection",
+        RecordType::Report | RecordType::Government => "techreport",
+        RecordType::Thesis
+            if record
+                .report
+                .as_deref()
+                .is_some_and(|r| r.to_lowercase().contains("master")) =>
+        {
+            "mastersthesis"
+        }
+        RecordType::Thesis => "phdthesis",
+        RecordType::Conference => "inproceedings",
+        RecordType::Misc | RecordType::None => "misc",
+    }
+}
+
+/// Turn the fields of a parsed entry into a record.
+fn to_record(typ: &str, key: &str, fields: Vec<(String, String)>) -> Record {
+    let mut record = Record {
+        label: Some(key.to_string()),
+        rec_type: match typ {
+            "article" => RecordType::Journal,
+            "book" | "booklet" => RecordType::Book,
+            "incollection" | "inbook" => RecordType::ArticleInBook,
+            "techreport" => RecordType::Report,
+            "phdthesis" | "mastersthesis" => RecordType::Thesis,
+            "inproceedings" | "conference" => RecordType::Conference,
+            "misc" | "unpublished" => RecordType::Misc,
+            _ => RecordType::None,
+        },
+        ..Default::default()
+    };
+    let mut year = None;
+    let mut month = None;
+
+    for (name, raw) in fields {
+        let value = unescape(&raw);
+        let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+        if value.is_empty() {
+            continue;
+        }
+        match name.as_str() {
+            // names are split before unescaping, as braces protect names
+            "author" => record.author = split_names(&raw).iter().map(|n| to_author(n)).collect(),
+            "editor" => {
+                record.editor = split_names(&raw)
+                    .iter()
+                    .map(|n| author_name(&to_author(n)))
+                    .collect()
+            }
+            "title" => record.title = Some(value),
+            "booktitle" => record.book = Some(value),
+            "journal" => record.journal = Some(value),
+            "volume" => record.volume = Some(value),
+            "number" if typ == "techreport" => record.report = Some(value),
+            "number" => record.issue_number = Some(value),
+            "type" if typ.ends_with("thesis") => record.report = Some(value),
+            "pages" => record.page_number = Some(value.replace("--", "-")),
+            "year" => year = Some(value),
+            "month" => month = Some(value),
+            "publisher" | "institution" | "school" | "organization" => {
+                record.issuer.get_or_insert(value);
+            }
+            "address" => record.place = Some(value),
+            "series" => record.series = Some(value),
+            "keywords" => {
+                record.keywords = Some(
+                    value
+                        .split([',', ';', ' '])
+                        .filter(|k| !k.is_empty())
+                        .map(String::from)
+                        .collect(),
+                )
+            }
+            "note" => record.other = Some(value),
+            "doi" => {
+                record.other.get_or_insert(format!("doi:{}", value));
+            }
+            "url" => {
+                record.other.get_or_insert(value);
+            }
+            "annote" => record.annotation = Some(value),
+            "abstract" => {
+                record.annotation.get_or_insert(value);
+            }
+            _ => (),
+        }
+    }
+
+    record.date = match (month, year) {
+        (Some(m), Some(y)) => Some(format!("{} {}", m, y)),
+        (None, Some(y)) => Some(y),
+        (Some(m), None) => Some(m),
+        (None, None) => None,
+    };
+    record
+}
+
+/// A BibTeX identifier, for entry types, field names and macros.
+fn identifier(i: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_alphanumeric() || "_-:.+/".contains(c))(i)
+}
+
+/// Parse the `name = value` fields up to the closing delimiter of an entry.
+fn fields<'a>(
+    mut i: &'a str,
+    close: char,
+    macros: &HashMap<String, String>,
+) -> Result<(&'a str, Vec<(String, String)>)> {
+    let mut fields = Vec::new();
+    loop {
+        i = i.trim_start();
+        if let Some(rest) = i.strip_prefix(close) {
+            return Ok((rest, fields));
+        }
+        if i.is_empty() {
+            return Err(bibtex_error("unexpected end of input in entry", i));
+        }
+        let (rest, name) = identifier(i).map_err(|_| bibtex_error("expected a field name", i))?;
+        let rest = rest.trim_start();
+        let rest = match char::<&str, ()>('=')(rest) {
+            Ok((rest, _)) => rest,
+            Err(_) => return Err(bibtex_error("expected `=` after the field name", rest)),
+        };
+        let (rest, value) = field_value(rest, macros)?;
+        fields.push((name.to_lowercase(), value));
+
+        i = rest.trim_start();
+        i = i.strip_prefix(',').unwrap_or(i);
+    }
+}
+
+/// A field value, which can be a number, a macro, a braced or quoted
+/// string, or any of these joined with `#`.
+fn field_value<'a>(mut i: &'a str, macros: &HashMap<String, String>) -> Result<(&'a str, String)> {
+    let mut value = String::new();
+    loop {
+        i = i.trim_start();
+        let (rest, part) = match i.chars().next() {
+            Some('{') => delimited_body(&i[1..], '}')?,
+            Some('"') => quoted_body(&i[1..])?,
+            Some(c) if c.is_ascii_digit() => {
+                let (rest, n) = take_while1::<_, &str, ()>(|c: char| c.is_ascii_digit())(i)
+                    .map_err(|_| bibtex_error("expected a number", i))?;
+                (rest, n)
+            }
+            _ => {
+                let (rest, name) =
+                    identifier(i).map_err(|_| bibtex_error("expected a field value", i))?;
+                match macros.get(&name.to_lowercase()) {
+                    Some(m) => {
+                        value.push_str(m);
+                        (rest, "")
+                    }
+                    None => return Err(bibtex_error("undefined @string macro", i)),
+                }
+            }
+        };
+        value.push_str(part);
+
+        i = rest.trim_start();
+        match i.strip_prefix('#') {
+            Some(rest) => i = rest,
+            None => return Ok((i, value)),
+        }
+    }
+}
+
+/// Take everything up to the balanced closing delimiter, returning the
+/// input after it and the body.
+fn delimited_body(i: &str, close: char) -> Result<(&str, &str)> {
+    let mut depth = 0;
+    for (idx, c) in i.char_indices() {
+        match c {
+            c if c == close && depth == 0 => return Ok((&i[idx + 1..], &i[..idx])),
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    Err(bibtex_error("unbalanced braces", i))
+}
+
+/// A `"` delimited string, which can contain braced quotes.
+fn quoted_body(i: &str) -> Result<(&str, &str)> {
+    let mut depth = 0;
+    for (idx, c) in i.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '"' if depth == 0 => return Ok((&i[idx + 1..], &i[..idx])),
+            _ => (),
+        }
+    }
+    Err(bibtex_error("unterminated quoted string", i))
+}
+
+/// Split a BibTeX name list on ` and ` at the top brace level.
+fn split_names(value: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for word in value.split_whitespace() {
+        if depth == 0 && word.eq_ignore_ascii_case("and") && !current.is_empty() {
+            names.push(current.trim().to_string());
+            current.clear();
+            continue;
+        }
+        depth += word.matches('{').count() as i32 - word.matches('}').count() as i32;
+        current.push_str(word);
+        current.push(' ');
+    }
+    if !current.trim().is_empty() {
+        names.push(current.trim().to_string());
+    }
+    names
+}
+
+/// Turn a BibTeX name into an author. The `Last, First`,
+/// `von Last, Jr, First` and `First von Last` forms are understood.
+fn to_author(name: &str) -> Author {
+    // a name entirely in braces is corporate, and is not split
+    if let Some(inner) = name.strip_prefix('{').and_then(|n| n.strip_suffix('}')) {
+        if delimited_body(inner, '}').is_err() {
+            return Author {
+                last: unescape(inner),
+                corporate: true,
+                ..Default::default()
+            };
+        }
+    }
+    let name = unescape(name);
+    name.parse().unwrap_or_else(|_| Author {
+        last: name.trim().to_string(),
+        ..Default::default()
+    })
+}
+
+/// A name in the `von Last, Jr, First` form.
+fn author_name(author: &Author) -> String {
+    let mut name = author.family_name();
+    if let Some(s) = &author.suffix {
+        name.push_str(", ");
+        name.push_str(s);
+    }
+    if !author.rest.is_empty() {
+        name.push_str(", ");
+        name.push_str(&author.rest);
+    }
+    name
+}
+
+/// An escaped author name, with corporate names in braces so BibTeX
+/// does not split them.
+fn escaped_author_name(author: &Author) -> String {
+    match author.corporate {
+        true => format!("{{{}}}", escape(&author.last)),
+        false => escape(&author_name(author)),
+    }
+}
+
+fn names(names: impl Iterator<Item = String>) -> String {
+    names.map(|n| escape(&n)).collect::<Vec<_>>().join(" and ")
+}
+
+/// Split a refer date such as `March 1999` into the year and a month macro.
+fn split_date(date: Option<&str>) -> (Option<String>, Option<&'static str>) {
+    let date = match date {
+        Some(d) => d,
+        None => return (None, None),
+    };
+    let mut month = None;
+    let mut rest = Vec::new();
+    for word in date.split_whitespace() {
+        let lower = word.trim_end_matches([',', '.']).to_lowercase();
+        match MONTHS
+            .iter()
+            .find(|(abbr, full)| lower == *abbr || lower == full.to_lowercase())
+        {
+            Some((abbr, _)) if month.is_none() => month = Some(*abbr),
+            _ => rest.push(word),
+        }
+    }
+    (Some(rest.join(" ")).filter(|y| !y.is_empty()), month)
+}
+
+fn citation_key(record: &Record) -> String {
+    if let Some(l) = &record.label {
+        return l.replace(char::is_whitespace, "");
+    }
+    let name = record
+        .author
+        .first()
+        .map(|a| a.last.as_str())
+        .or(record.author_np.as_deref())
+        .unwrap_or("anon");
+    let (year, _) = split_date(record.date.as_deref());
+    let key: String = decompose(name)
+        .chars()
+        .chain(year.unwrap_or_default().chars())
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    key.to_lowercase()
+}
+
+/// Replace LaTeX accents and special letters with unicode, and drop the
+/// braces used for protecting case.
+fn unescape(value: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' => (),
+            '\\' => {
+                let next = match chars.next() {
+                    Some(n) => n,
+                    None => break,
+                };
+                // letter accents (e.g. \c) must not be the start of a longer command
+                let is_command =
+                    next.is_alphabetic() && chars.peek().is_some_and(char::is_ascii_alphabetic);
+                let accent = ACCENTS.iter().find(|(a, _)| *a == next && !is_command);
+                if let Some((_, combining)) = accent {
+                    // the accent is either on the next letter, or in braces,
+                    // for letter accents skip the space
+                    if next.is_alphabetic() && chars.peek() == Some(&' ') {
+                        chars.next();
+                    }
+                    if chars.peek() == Some(&'{') {
+                        chars.next();
+                    }
+                    // a dotless i/j under an accent
+                    if chars.peek() == Some(&'\\') {
+                        chars.next();
+                    }
+                    if let Some(base) = chars.next() {
+                        if base == '}' {
+                            continue;
+                        }
+                        out.push(base);
+                        out.push(*combining);
+                    }
+                    if chars.peek() == Some(&'}') {
+                        chars.next();
+                    }
+                } else if next.is_alphabetic() {
+                    let mut command = String::from(next);
+                    while let Some(c) = chars.peek().filter(|c| c.is_ascii_alphabetic()) {
+                        command.push(*c);
+                        chars.next();
+                    }
+                    // an unknown command, e.g. \emph, is dropped but keeps its argument
+                    if let Some((_, letter)) = LETTERS.iter().find(|(l, _)| *l == command) {
+                        out.push_str(letter);
+                    }
+                    if chars.peek() == Some(&' ') {
+                        chars.next();
+                    }
+                } else {
+                    // escaped characters such as \& or \%
+                    out.push(next);
+                }
+            }
+            '~' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    compose(&out)
+}
+
+/// Escape a value for writing in a braced BibTeX field.
+fn escape(value: &str) -> String {
+    let mut out = String::new();
+    for c in decompose(value).chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '{' | '}' => (),
+            c => {
+                if let Some((accent, _)) = ACCENTS.iter().find(|(_, comb)| *comb == c) {
+                    // the accent goes before the letter we have just written
+                    if let Some(base) = out.pop() {
+                        if accent.is_alphabetic() {
+                            out.push_str(&format!("{{\\{}{{{}}}}}", accent, base));
+                        } else {
+                            out.push_str(&format!("{{\\{}{}}}", accent, base));
+                        }
+                    }
+                } else if let Some((command, _)) =
+                    LETTERS.iter().find(|(_, l)| l.starts_with(c) && c != '&')
+                {
+                    out.push_str(&format!("{{\\{}}}", command));
+                } else {
+                    out.push(c);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Precomposed letters and their (base letter, combining accent) parts.
+const PRECOMPOSED: &[(char, char, char)] = &[
+    ('á', 'a', '\u{301}'),
+    ('à', 'a', '\u{300}'),
+    ('â', 'a', '\u{302}'),
+    ('ä', 'a', '\u{308}'),
+    ('ã', 'a', '\u{303}'),
+    ('ā', 'a', '\u{304}'),
+    ('ă', 'a', '\u{306}'),
+    ('ą', 'a', '\u{328}'),
+    ('Á', 'A', '\u{301}'),
+    ('À', 'A', '\u{300}'),
+    ('Â', 'A', '\u{302}'),
+    ('Ä', 'A', '\u{308}'),
+    ('Ã', 'A', '\u{303}'),
+    ('ç', 'c', '\u{327}'),
+    ('č', 'c', '\u{30C}'),
+    ('ć', 'c', '\u{301}'),
+    ('Ç', 'C', '\u{327}'),
+    ('Č', 'C', '\u{30C}'),
+    ('é', 'e', '\u{301}'),
+    ('è', 'e', '\u{300}'),
+    ('ê', 'e', '\u{302}'),
+    ('ë', 'e', '\u{308}'),
+    ('ě', 'e', '\u{30C}'),
+    ('ę', 'e', '\u{328}'),
+    ('É', 'E', '\u{301}'),
+    ('È', 'E', '\u{300}'),
+    ('Ê', 'E', '\u{302}'),
+    ('Ë', 'E', '\u{308}'),
+    ('ğ', 'g', '\u{306}'),
+    ('í', 'i', '\u{301}'),
+    ('ì', 'i', '\u{300}'),
+    ('î', 'i', '\u{302}'),
+    ('ï', 'i', '\u{308}'),
+    ('Í', 'I', '\u{301}'),
+    ('ñ', 'n', '\u{303}'),
+    ('ń', 'n', '\u{301}'),
+    ('ň', 'n', '\u{30C}'),
+    ('Ñ', 'N', '\u{303}'),
+    ('ó', 'o', '\u{301}'),
+    ('ò', 'o', '\u{300}'),
+    ('ô', 'o', '\u{302}'),
+    ('ö', 'o', '\u{308}'),
+    ('õ', 'o', '\u{303}'),
+    ('ő', 'o', '\u{30B}'),
+    ('Ó', 'O', '\u{301}'),
+    ('Ö', 'O', '\u{308}'),
+    ('ř', 'r', '\u{30C}'),
+    ('Ř', 'R', '\u{30C}'),
+    ('ś', 's', '\u{301}'),
+    ('š', 's', '\u{30C}'),
+    ('ş', 's', '\u{327}'),
+    ('Š', 'S', '\u{30C}'),
+    ('Ş', 'S', '\u{327}'),
+    ('ú', 'u', '\u{301}'),
+    ('ù', 'u', '\u{300}'),
+    ('û', 'u', '\u{302}'),
+    ('ü', 'u', '\u{308}'),
+    ('ů', 'u', '\u{30A}'),
+    ('ű', 'u', '\u{30B}'),
+    ('Ú', 'U', '\u{301}'),
+    ('Ü', 'U', '\u{308}'),
+    ('ý', 'y', '\u{301}'),
+    ('ÿ', 'y', '\u{308}'),
+    ('Ý', 'Y', '\u{301}'),
+    ('ž', 'z', '\u{30C}'),
+    ('ź', 'z', '\u{301}'),
+    ('ż', 'z', '\u{307}'),
+    ('Ž', 'Z', '\u{30C}'),
+];
+
+/// Combine a letter followed by a combining accent into the precomposed
+/// letter where one is known.
+fn compose(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        let composed = out.chars().last().and_then(|base| {
+            PRECOMPOSED
+                .iter()
+                .find(|(_, b, comb)| *b == base && *comb == c)
+                .map(|(p, _, _)| *p)
+        });
+        match composed {
+            Some(p) => {
+                out.pop();
+                out.push(p);
+            }
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+/// The reverse of [`compose`].
+fn decompose(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match PRECOMPOSED.iter().find(|(p, _, _)| *p == c) {
+            Some((_, base, comb)) => {
+                out.push(*base);
+                out.push(*comb);
+            }
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+fn bibtex_error(message: &str, at: &str) -> Error {
+    let context: String = at.chars().take(30).collect();
+    Error::new(ErrorKind::Bibtex(format!("{}, at: {:?}", message, context)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_entries() {
+        let bib = r#"
+            @string{pnas = "Proceedings of the " # {National Academy of Sciences}}
+            @comment{ignored {entirely}}
+            @book(twyford2020,
+                author = "Alex D. Twyford and {World Health Organization}",
+                title = {The {DNA} of plants},
+                publisher = {Penguin},
+                address = {London},
+                year = {2020},
+            )
+            @article{brown2023,
+                author = {Brown, Max and M{\"u}ller, J{\'e}r{\^o}me and van der Berg, P.},
+                journal = pnas,
+                volume = 12, number = 3, pages = {45--67},
+                year = 2023, month = mar,
+                keywords = {plants, parasites},
+                annote = {A \& B}
+            }
+        "#;
+        let records = from_str(bib).unwrap();
+        assert_eq!(records.len(), 2);
+
+        let book = &records[0];
+        assert_eq!(book.label.as_deref(), Some("twyford2020"));
+        assert_eq!(book.title.as_deref(), Some("The DNA of plants"));
+        assert_eq!(book.author[0].last, "Twyford");
+        assert_eq!(book.author[0].rest, "Alex D.");
+        assert_eq!(book.author[1].last, "World Health Organization");
+        assert_eq!(book.place.as_deref(), Some("London"));
+
+        let article = &records[1];
+        assert_eq!(
+            article.journal.as_deref(),
+            Some("Proceedings of the National Academy of Sciences")
+        );
+        assert_eq!(article.author[1].last, "Müller");
+        assert_eq!(article.author[1].rest, "Jérôme");
+        assert_eq!(article.author[2].last, "Berg");
+        assert_eq!(article.author[2].particle.as_deref(), Some("van der"));
+        assert_eq!(article.page_number.as_deref(), Some("45-67"));
+        assert_eq!(article.date.as_deref(), Some("March 2023"));
+        assert_eq!(
+            article.keywords,
+            Some(vec!["plants".into(), "parasites".into()])
+        );
+        assert_eq!(article.annotation.as_deref(), Some("A & B"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let record = Record {
+            author: vec![Author {
+                last: "Müller".into(),
+                rest: "J.".into(),
+                ..Default::default()
+            }],
+            title: Some("Tom & Jerry".into()),
+            journal: Some("PNAS".into()),
+            date: Some("March 2023".into()),
+            page_number: Some("1-10".into()),
+            ..Default::default()
+        };
+        let entry = to_string(&record);
+        assert_eq!(
+            entry,
+            "@article{muller2023,\n  author = {M{\\\"u}ller, J.},\n  title = {Tom \\& Jerry},\n  \
+             journal = {PNAS},\n  pages = {1--10},\n  year = {2023},\n  month = mar,\n}\n"
+        );
+
+        let parsed = from_str(&entry).unwrap().remove(0);
+        assert_eq!(
+            parsed,
+            Record {
+                label: Some("muller2023".into()),
+                rec_type: RecordType::Journal,
+                ..record
+            }
+        );
+    }
+
+    #[test]
+    fn test_one_word_author_to_refer() {
+        let record = from_str("@misc{g, author = {{Google}}, title = {Search}}")
+            .unwrap()
+            .remove(0);
+        let refer = record.to_string();
+        assert!(refer.contains("%A Google\n"));
+        let read = crate::Reader::new(refer.as_bytes())
+            .records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.author[0].last, "Google");
+        assert_eq!(read.title, record.title);
+    }
+
+    #[test]
+    fn test_comments_between_entries() {
+        let bib = "Sent by max@example.com, see @ below.\n\
+                   @article{a, title = {One}}\n\
+                   email: a@b.org, or @ home @@\n\
+                   @book(b, title = {Two})";
+        let records = from_str(bib).unwrap();
+        let titles: Vec<_> = records.iter().map(|r| r.title.as_deref()).collect();
+        assert_eq!(titles, [Some("One"), Some("Two")]);
+    }
+
+    #[test]
+    fn test_undefined_macro() {
+        assert!(from_str("@article{key, journal = nope}").is_err());
+    }
+}
@@ -0,0 +1,2476 @@
+/*!
+A processor for styles in CSL, the Citation Style Language (version
+1.0.2), so that records can be formatted in any of the thousands of
+styles at <https://github.com/citation-style-language/styles>.
+
+A [`Style`] is parsed from a `.csl` file, and a [`Processor`] formats a
+list of records with it, as in-text citations or bibliography entries.
+Records are turned into CSL items as in the [`csl_json`]
+module.
+
+Macros, conditions, names (with `et-al-min` and `et-al-use-first`,
+initials and sort order), labels, numbers, localised and custom dates,
+page range formats, sorting and disambiguation (by adding names, given
+names, the `disambiguate` condition and year suffixes) are supported.
+Terms come from a built-in `en-US` locale, which a style can override
+with its own `<locale>`. Cites are not collapsed, and every cite is in
+the first position.
+
+# Example
+
+```
+use refer::{csl::{Processor, Style}, Author, Record};
+
+let style: Style = r#"<style xmlns="http://purl.org/net/xbiblio/csl" class="in-text" version="1.0">
+  <citation et-al-min="3" et-al-use-first="1" disambiguate-add-year-suffix="true">
+    <layout prefix="(" suffix=")" delimiter="; ">
+      <group delimiter=", ">
+        <names variable="author"><name form="short" and="symbol"/></names>
+        <date variable="issued"><date-part name="year"/></date>
+      </group>
+    </layout>
+  </citation>
+  <bibliography>
+    <sort><key variable="author"/><key variable="issued"/></sort>
+    <layout suffix=".">
+      <group delimiter=". ">
+        <names variable="author"><name name-as-sort-order="first" initialize-with=". "/></names>
+        <date variable="issued"><date-part name="year"/></date>
+        <text variable="title"/>
+      </group>
+    </layout>
+  </bibliography>
+</style>"#.parse().unwrap();
+
+let record = Record {
+    author: vec![Author { last: "Brown".into(), rest: "Max James".into(), ..Default::default() }],
+    date: Some("2023".into()),
+    title: Some("Parasitic plants".into()),
+    ..Default::default()
+};
+let records = [record];
+let processor = Processor::new(&style, &records);
+
+assert_eq!(processor.citation(&[0.into()]).unwrap(), "(Brown, 2023)");
+assert_eq!(processor.bibliography(), ["Brown, M. J. 2023. Parasitic plants."]);
+```
+*/
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use roxmltree::{Document, Node};
+use serde_json::{Map, Value};
+
+use crate::{
+    csl_json,
+    error::{Error, ErrorKind, Result},
+    label::suffix,
+    pages::{to_roman, PageFormat, PageRange},
+    record::Record,
+    text::{bold, italic, link, Markup, Text},
+};
+
+/// Macros calling macros deeper than this are taken to be recursive,
+/// and render nothing.
+const MAX_MACRO_DEPTH: usize = 32;
+
+/// Whether a style puts its citations in the text or in footnotes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    /// Citations in the text, such as `(Brown 2023)` or `[1]`.
+    #[default]
+    InText,
+    /// Citations in footnotes.
+    Note,
+}
+
+/// A CSL style, parsed from a `.csl` file.
+#[derive(Debug, Clone)]
+pub struct Style {
+    title: Option<String>,
+    class: Class,
+    options: StyleOptions,
+    terms: HashMap<(String, TermForm), Term>,
+    macros: HashMap<String, Vec<Element>>,
+    citation: Section,
+    bibliography: Option<Section>,
+}
+
+/// The options set on the `<style>` element, and the locale.
+#[derive(Debug, Clone)]
+struct StyleOptions {
+    names: NameOptions,
+    page_range_format: Option<PageFormat>,
+    initialize_with_hyphen: bool,
+    demote_particle: Demote,
+    punctuation_in_quote: bool,
+    /// Whether the style writes the year suffix itself, rather than
+    /// leaving it to follow the first year of the issued date.
+    renders_year_suffix: bool,
+}
+
+/// Where a particle such as `van` goes in an inverted name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Demote {
+    Never,
+    SortOnly,
+    DisplayAndSort,
+}
+
+/// The `<citation>` or the `<bibliography>` of a style.
+#[derive(Debug, Clone, Default)]
+struct Section {
+    names: NameOptions,
+    names_delimiter: Option<String>,
+    sort: Vec<SortKey>,
+    layout: Vec<Element>,
+    layout_formatting: Formatting,
+    layout_delimiter: String,
+    add_names: bool,
+    add_givenname: bool,
+    add_year_suffix: bool,
+    author_substitute: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct SortKey {
+    source: SortSource,
+    descending: bool,
+}
+
+#[derive(Debug, Clone)]
+enum SortSource {
+    Variable(String),
+    Macro(String),
+}
+
+/// The affixes, fonts, quotes and case of a rendering element.
+#[derive(Debug, Clone, Default)]
+struct Formatting {
+    prefix: String,
+    suffix: String,
+    italic: bool,
+    bold: bool,
+    quotes: bool,
+    strip_periods: bool,
+    text_case: Option<TextCase>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextCase {
+    Lowercase,
+    Uppercase,
+    CapitalizeFirst,
+    CapitalizeAll,
+    Sentence,
+    Title,
+}
+
+#[derive(Debug, Clone)]
+enum Element {
+    Text(TextSource, Formatting),
+    Number(String, NumberForm, Formatting),
+    Label(String, Label),
+    Names(Box<Names>),
+    Date(Box<DateElement>),
+    Group(Vec<Element>, String, Formatting),
+    Choose(Vec<Branch>),
+}
+
+#[derive(Debug, Clone)]
+enum TextSource {
+    Variable(String, TermForm),
+    Macro(String),
+    Term(String, TermForm, bool),
+    Value(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumberForm {
+    Numeric,
+    Ordinal,
+    LongOrdinal,
+    Roman,
+}
+
+#[derive(Debug, Clone)]
+struct Label {
+    form: TermForm,
+    plural: Plural,
+    formatting: Formatting,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Plural {
+    Contextual,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Names {
+    variables: Vec<String>,
+    /// Whether there is a `<name>`, rather than one inherited from the
+    /// parent of a substitute.
+    own_name: bool,
+    name: NameOptions,
+    name_formatting: Formatting,
+    family: Formatting,
+    given: Formatting,
+    et_al: Option<(String, Formatting)>,
+    label: Option<Label>,
+    label_before: bool,
+    substitute: Vec<Element>,
+    delimiter: Option<String>,
+    formatting: Formatting,
+}
+
+/// The options of a `<name>`, which are also inherited from the style,
+/// citation and bibliography.
+#[derive(Debug, Clone, Default)]
+struct NameOptions {
+    and: Option<String>,
+    delimiter: Option<String>,
+    delimiter_precedes_et_al: Option<String>,
+    delimiter_precedes_last: Option<String>,
+    et_al_min: Option<usize>,
+    et_al_use_first: Option<usize>,
+    et_al_use_last: Option<bool>,
+    form: Option<String>,
+    initialize: Option<bool>,
+    initialize_with: Option<String>,
+    name_as_sort_order: Option<String>,
+    sort_separator: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct DateElement {
+    variable: String,
+    form: Option<DateForm>,
+    /// The smallest part shown for a localised date.
+    limit: DatePartName,
+    parts: Vec<DatePart>,
+    delimiter: String,
+    formatting: Formatting,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateForm {
+    Text,
+    Numeric,
+}
+
+#[derive(Debug, Clone)]
+struct DatePart {
+    name: DatePartName,
+    form: Option<String>,
+    range_delimiter: Option<String>,
+    formatting: Formatting,
+}
+
+/// Ordered from the largest part to the smallest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum DatePartName {
+    Year,
+    Month,
+    Day,
+}
+
+#[derive(Debug, Clone)]
+struct Branch {
+    conditions: Vec<Condition>,
+    matching: Match,
+    elements: Vec<Element>,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Type(String),
+    Variable(String),
+    IsNumeric(String),
+    IsUncertainDate(String),
+    Locator(String),
+    Position(String),
+    Disambiguate(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Match {
+    All,
+    Any,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TermForm {
+    Long,
+    Short,
+    Verb,
+    VerbShort,
+    Symbol,
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    single: String,
+    multiple: String,
+}
+
+impl Style {
+    /// Read a style from a `.csl` file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Style> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// The title of the style, from its `<info>`.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Whether citations go in the text or in footnotes.
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// Whether the style has a bibliography, as well as citations.
+    pub fn has_bibliography(&self) -> bool {
+        self.bibliography.is_some()
+    }
+
+    /// A term from the locale, falling back to longer forms as CSL does.
+    fn term(&self, name: &str, form: TermForm, plural: bool) -> Option<&str> {
+        let forms: &[TermForm] = match form {
+            TermForm::Long => &[TermForm::Long],
+            TermForm::Short => &[TermForm::Short, TermForm::Long],
+            TermForm::Verb => &[TermForm::Verb, TermForm::Long],
+            TermForm::VerbShort => &[TermForm::VerbShort, TermForm::Verb, TermForm::Long],
+            TermForm::Symbol => &[TermForm::Symbol, TermForm::Short, TermForm::Long],
+        };
+        forms.iter().find_map(|f| {
+            let term = self.terms.get(&(name.to_string(), *f))?;
+            Some(match plural {
+                true => term.multiple.as_str(),
+                false => term.single.as_str(),
+            })
+        })
+    }
+
+    /// Apply the case, quotes, fonts and affixes of an element, in that
+    /// order. Nothing is added to empty text.
+    fn decorate(&self, mut text: Text, formatting: &Formatting) -> Text {
+        if text.is_empty() {
+            return text;
+        }
+        if let Some(case) = formatting.text_case {
+            let mut first = true;
+            text.map_plain(&mut |s| case.apply(s, std::mem::take(&mut first)));
+        }
+        if formatting.strip_periods {
+            text.map_plain(&mut |s| s.replace('.', ""));
+        }
+        if formatting.quotes {
+            let mut quoted = Text::plain(
+                self.term("open-quote", TermForm::Long, false)
+                    .unwrap_or("\u{201c}"),
+            );
+            quoted.append(text);
+            quoted.push_str(
+                self.term("close-quote", TermForm::Long, false)
+                    .unwrap_or("\u{201d}"),
+            );
+            text = quoted;
+        }
+        if formatting.italic {
+            text = italic(text);
+        }
+        if formatting.bold {
+            text = bold(text);
+        }
+        let mut out = Text::plain(&formatting.prefix);
+        push_piece(&mut out, text);
+        push_piece(&mut out, Text::plain(&formatting.suffix));
+        out
+    }
+
+    /// Move full stops and commas inside closing quotes, as in American
+    /// English.
+    fn finish(&self, mut text: Text) -> Text {
+        if !self.options.punctuation_in_quote {
+            return text;
+        }
+        for quote in ["close-quote", "close-inner-quote"] {
+            let quote = match self.term(quote, TermForm::Long, false) {
+                Some(q) if !q.is_empty() => q,
+                _ => continue,
+            };
+            for mark in [".", ","] {
+                text.map_plain(&mut |s| {
+                    s.replace(&format!("{}{}", quote, mark), &format!("{}{}", mark, quote))
+                });
+            }
+        }
+        text
+    }
+
+    /// The parts of a date element, with those of a localised date
+    /// taken from the locale and overridden by the element.
+    fn date_parts(&self, date: &DateElement) -> Vec<DatePart> {
+        let form = match date.form {
+            Some(form) => form,
+            None => return date.parts.clone(),
+        };
+        let part = |name, form: &str, suffix: &str| DatePart {
+            name,
+            form: Some(form.to_string()),
+            range_delimiter: None,
+            formatting: Formatting {
+                suffix: suffix.to_string(),
+                ..Default::default()
+            },
+        };
+        // as in the en-US locale
+        let mut parts = match form {
+            DateForm::Text => vec![
+                part(DatePartName::Month, "long", " "),
+                part(DatePartName::Day, "numeric", ", "),
+                part(DatePartName::Year, "long", ""),
+            ],
+            DateForm::Numeric => vec![
+                part(DatePartName::Month, "numeric-leading-zeros", "/"),
+                part(DatePartName::Day, "numeric-leading-zeros", "/"),
+                part(DatePartName::Year, "long", ""),
+            ],
+        };
+        parts.retain(|p| p.name <= date.limit);
+        for p in &mut parts {
+            // the affixes of a localised date can't be changed
+            if let Some(o) = date.parts.iter().find(|o| o.name == p.name) {
+                if o.form.is_some() {
+                    p.form = o.form.clone();
+                }
+                if o.range_delimiter.is_some() {
+                    p.range_delimiter = o.range_delimiter.clone();
+                }
+                p.formatting = Formatting {
+                    prefix: p.formatting.prefix.clone(),
+                    suffix: p.formatting.suffix.clone(),
+                    ..o.formatting.clone()
+                };
+            }
+        }
+        parts
+    }
+}
+
+impl FromStr for Style {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let document = Document::parse(s).map_err(|e| csl_error(e.to_string()))?;
+        Parser::default().style(document.root_element())
+    }
+}
+
+/// Parses the XML of a style, remembering the macros and whether the
+/// year suffix is written by the style.
+#[derive(Default)]
+struct Parser {
+    macro_names: HashSet<String>,
+    renders_year_suffix: bool,
+}
+
+impl Parser {
+    fn style(mut self, root: Node) -> Result<Style> {
+        if root.tag_name().name() != "style" {
+            return Err(csl_error("expected a <style> element"));
+        }
+        let class = match root.attribute("class") {
+            Some("note") => Class::Note,
+            _ => Class::InText,
+        };
+        let mut options = StyleOptions {
+            names: name_options(root, true),
+            page_range_format: match root.attribute("page-range-format") {
+                Some("minimal") => Some(PageFormat::Minimal),
+                Some("minimal-two") => Some(PageFormat::Mla),
+                Some("chicago") | Some("chicago-15") | Some("chicago-16") => {
+                    Some(PageFormat::Chicago)
+                }
+                Some("expanded") => Some(PageFormat::Expanded),
+                _ => None,
+            },
+            initialize_with_hyphen: root.attribute("initialize-with-hyphen") != Some("false"),
+            demote_particle: match root.attribute("demote-non-dropping-particle") {
+                Some("never") => Demote::Never,
+                Some("sort-only") => Demote::SortOnly,
+                _ => Demote::DisplayAndSort,
+            },
+            punctuation_in_quote: true,
+            renders_year_suffix: false,
+        };
+        let mut terms = en_us_terms();
+
+        let mut title = None;
+        let mut dependent = false;
+        for child in elements(root) {
+            match child.tag_name().name() {
+                "info" => {
+                    for info in elements(child) {
+                        match info.tag_name().name() {
+                            "title" => title = info.text().map(str::to_string),
+                            "link" => {
+                                dependent |= info.attribute("rel") == Some("independent-parent")
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                "locale" => {
+                    let lang = child.attribute(("http://www.w3.org/XML/1998/namespace", "lang"));
+                    if lang.is_none_or(|l| l.starts_with("en")) {
+                        locale(child, &mut terms, &mut options);
+                    }
+                }
+                "macro" => {
+                    let name = required(child, "name")?;
+                    self.macro_names.insert(name.to_string());
+                }
+                _ => (),
+            }
+        }
+
+        let mut macros = HashMap::new();
+        let mut citation = None;
+        let mut bibliography = None;
+        for child in elements(root) {
+            match child.tag_name().name() {
+                "macro" => {
+                    let elements = self.elements(child)?;
+                    macros.insert(required(child, "name")?.to_string(), elements);
+                }
+                "citation" => citation = Some(self.section(child)?),
+                "bibliography" => bibliography = Some(self.section(child)?),
+                _ => (),
+            }
+        }
+        let citation = match citation {
+            Some(c) => c,
+            None if dependent => {
+                return Err(csl_error(
+                    "dependent styles are not supported, use the parent style",
+                ))
+            }
+            None => return Err(csl_error("the style has no <citation>")),
+        };
+        options.renders_year_suffix = self.renders_year_suffix;
+
+        Ok(Style {
+            title,
+            class,
+            options,
+            terms,
+            macros,
+            citation,
+            bibliography,
+        })
+    }
+
+    fn section(&mut self, node: Node) -> Result<Section> {
+        let mut section = Section {
+            names: name_options(node, true),
+            names_delimiter: node.attribute("names-delimiter").map(str::to_string),
+            add_names: node.attribute("disambiguate-add-names") == Some("true"),
+            add_givenname: node.attribute("disambiguate-add-givenname") == Some("true"),
+            add_year_suffix: node.attribute("disambiguate-add-year-suffix") == Some("true"),
+            author_substitute: node
+                .attribute("subsequent-author-substitute")
+                .map(str::to_string),
+            ..Default::default()
+        };
+        let mut has_layout = false;
+        for child in elements(node) {
+            match child.tag_name().name() {
+                "sort" => {
+                    for key in elements(child) {
+                        let source = match (key.attribute("variable"), key.attribute("macro")) {
+                            (Some(v), _) => SortSource::Variable(v.to_string()),
+                            (None, Some(m)) => SortSource::Macro(self.macro_name(m)?),
+                            (None, None) => {
+                                return Err(csl_error("a sort key needs a variable or a macro"))
+                            }
+                        };
+                        section.sort.push(SortKey {
+                            source,
+                            descending: key.attribute("sort") == Some("descending"),
+                        });
+                    }
+                }
+                "layout" => {
+                    has_layout = true;
+                    section.layout = self.elements(child)?;
+                    section.layout_formatting = formatting(child);
+                    section.layout_delimiter =
+                        child.attribute("delimiter").unwrap_or_default().to_string();
+                }
+                _ => (),
+            }
+        }
+        if !has_layout {
+            let name = node.tag_name().name();
+            return Err(csl_error(format!("the <{}> has no <layout>", name)));
+        }
+        Ok(section)
+    }
+
+    fn elements(&mut self, node: Node) -> Result<Vec<Element>> {
+        elements(node).map(|child| self.element(child)).collect()
+    }
+
+    fn element(&mut self, node: Node) -> Result<Element> {
+        let element = match node.tag_name().name() {
+            "text" => {
+                let source = if let Some(v) = node.attribute("variable") {
+                    self.renders_year_suffix |= v == "year-suffix";
+                    let form = match node.attribute("form") {
+                        Some("short") => TermForm::Short,
+                        _ => TermForm::Long,
+                    };
+                    TextSource::Variable(v.to_string(), form)
+                } else if let Some(m) = node.attribute("macro") {
+                    TextSource::Macro(self.macro_name(m)?)
+                } else if let Some(t) = node.attribute("term") {
+                    let plural = node.attribute("plural") == Some("true");
+                    TextSource::Term(t.to_string(), term_form(node), plural)
+                } else if let Some(v) = node.attribute("value") {
+                    TextSource::Value(v.to_string())
+                } else {
+                    return Err(csl_error("a <text> needs a variable, macro, term or value"));
+                };
+                Element::Text(source, formatting(node))
+            }
+            "number" => {
+                let form = match node.attribute("form") {
+                    Some("ordinal") => NumberForm::Ordinal,
+                    Some("long-ordinal") => NumberForm::LongOrdinal,
+                    Some("roman") => NumberForm::Roman,
+                    _ => NumberForm::Numeric,
+                };
+                let variable = required(node, "variable")?.to_string();
+                Element::Number(variable, form, formatting(node))
+            }
+            "label" => {
+                let variable = required(node, "variable")?.to_string();
+                Element::Label(variable, label(node))
+            }
+            "names" => Element::Names(Box::new(self.names(node)?)),
+            "date" => Element::Date(Box::new(date(node)?)),
+            "group" => Element::Group(
+                self.elements(node)?,
+                node.attribute("delimiter").unwrap_or_default().to_string(),
+                formatting(node),
+            ),
+            "choose" => Element::Choose(
+                elements(node)
+                    .map(|branch| self.branch(branch))
+                    .collect::<Result<_>>()?,
+            ),
+            other => return Err(csl_error(format!("unknown element <{}>", other))),
+        };
+        Ok(element)
+    }
+
+    fn names(&mut self, node: Node) -> Result<Names> {
+        let mut names = Names {
+            variables: required(node, "variable")?
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            delimiter: node.attribute("delimiter").map(str::to_string),
+            formatting: formatting(node),
+            ..Default::default()
+        };
+        for child in elements(node) {
+            match child.tag_name().name() {
+                "name" => {
+                    names.own_name = true;
+                    names.name = name_options(child, false);
+                    names.name_formatting = formatting(child);
+                    for part in elements(child) {
+                        match part.attribute("name") {
+                            Some("family") => names.family = formatting(part),
+                            Some("given") => names.given = formatting(part),
+                            _ => (),
+                        }
+                    }
+                }
+                "et-al" => {
+                    let term = child.attribute("term").unwrap_or("et-al").to_string();
+                    names.et_al = Some((term, formatting(child)));
+                }
+                "label" => {
+                    names.label = Some(label(child));
+                    names.label_before = !names.own_name;
+                }
+                "substitute" => names.substitute = self.elements(child)?,
+                other => return Err(csl_error(format!("unknown element <{}> in <names>", other))),
+            }
+        }
+        Ok(names)
+    }
+
+    fn branch(&mut self, node: Node) -> Result<Branch> {
+        let mut conditions = Vec::new();
+        if node.tag_name().name() != "else" {
+            for attribute in node.attributes() {
+                let values = attribute.value().split_whitespace().map(str::to_string);
+                match attribute.name() {
+                    "type" => conditions.extend(values.map(Condition::Type)),
+                    "variable" => conditions.extend(values.map(Condition::Variable)),
+                    "is-numeric" => conditions.extend(values.map(Condition::IsNumeric)),
+                    "is-uncertain-date" => {
+                        conditions.extend(values.map(Condition::IsUncertainDate))
+                    }
+                    "locator" => conditions.extend(values.map(Condition::Locator)),
+                    "position" => conditions.extend(values.map(Condition::Position)),
+                    "disambiguate" => {
+                        conditions.push(Condition::Disambiguate(attribute.value() == "true"))
+                    }
+                    _ => (),
+                }
+            }
+            if conditions.is_empty() {
+                return Err(csl_error("a condition needs at least one test"));
+            }
+        }
+        Ok(Branch {
+            conditions,
+            matching: match node.attribute("match") {
+                Some("any") => Match::Any,
+                Some("none") => Match::None,
+                _ => Match::All,
+            },
+            elements: self.elements(node)?,
+        })
+    }
+
+    fn macro_name(&self, name: &str) -> Result<String> {
+        match self.macro_names.contains(name) {
+            true => Ok(name.to_string()),
+            false => Err(csl_error(format!("no macro called {:?}", name))),
+        }
+    }
+}
+
+/// The child elements of a node.
+fn elements<'a, 'input>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(Node::is_element)
+}
+
+fn required<'a>(node: Node<'a, '_>, attribute: &str) -> Result<&'a str> {
+    node.attribute(attribute).ok_or_else(|| {
+        let name = node.tag_name().name();
+        csl_error(format!("<{}> needs a {} attribute", name, attribute))
+    })
+}
+
+fn formatting(node: Node) -> Formatting {
+    let attribute = |name| node.attribute(name).unwrap_or_default().to_string();
+    Formatting {
+        prefix: attribute("prefix"),
+        suffix: attribute("suffix"),
+        italic: matches!(node.attribute("font-style"), Some("italic" | "oblique")),
+        bold: node.attribute("font-weight") == Some("bold"),
+        quotes: node.attribute("quotes") == Some("true"),
+        strip_periods: node.attribute("strip-periods") == Some("true"),
+        text_case: match node.attribute("text-case") {
+            Some("lowercase") => Some(TextCase::Lowercase),
+            Some("uppercase") => Some(TextCase::Uppercase),
+            Some("capitalize-first") => Some(TextCase::CapitalizeFirst),
+            Some("capitalize-all") => Some(TextCase::CapitalizeAll),
+            Some("sentence") => Some(TextCase::Sentence),
+            Some("title") => Some(TextCase::Title),
+            _ => None,
+        },
+    }
+}
+
+fn term_form(node: Node) -> TermForm {
+    match node.attribute("form") {
+        Some("short") => TermForm::Short,
+        Some("verb") => TermForm::Verb,
+        Some("verb-short") => TermForm::VerbShort,
+        Some("symbol") => TermForm::Symbol,
+        _ => TermForm::Long,
+    }
+}
+
+fn label(node: Node) -> Label {
+    Label {
+        form: term_form(node),
+        plural: match node.attribute("plural") {
+            Some("always") => Plural::Always,
+            Some("never") => Plural::Never,
+            _ => Plural::Contextual,
+        },
+        formatting: formatting(node),
+    }
+}
+
+/// The name options of a `<name>`, or the inheritable ones of a style,
+/// citation or bibliography, where some are named differently.
+fn name_options(node: Node, inherited: bool) -> NameOptions {
+    let attribute = |name: &str| node.attribute(name).map(str::to_string);
+    let number = |name: &str| node.attribute(name).and_then(|n| n.parse().ok());
+    let (delimiter, form) = match inherited {
+        true => ("name-delimiter", "name-form"),
+        false => ("delimiter", "form"),
+    };
+    NameOptions {
+        and: attribute("and"),
+        delimiter: attribute(delimiter),
+        delimiter_precedes_et_al: attribute("delimiter-precedes-et-al"),
+        delimiter_precedes_last: attribute("delimiter-precedes-last"),
+        et_al_min: number("et-al-min"),
+        et_al_use_first: number("et-al-use-first"),
+        et_al_use_last: node.attribute("et-al-use-last").map(|b| b == "true"),
+        form: attribute(form),
+        initialize: node.attribute("initialize").map(|b| b != "false"),
+        initialize_with: attribute("initialize-with"),
+        name_as_sort_order: attribute("name-as-sort-order"),
+        sort_separator: attribute("sort-separator"),
+    }
+}
+
+fn date(node: Node) -> Result<DateElement> {
+    let parts = elements(node)
+        .filter(|p| p.tag_name().name() == "date-part")
+        .map(|p| {
+            let name = match required(p, "name")? {
+                "year" => DatePartName::Year,
+                "month" => DatePartName::Month,
+                "day" => DatePartName::Day,
+                other => return Err(csl_error(format!("unknown date part {:?}", other))),
+            };
+            Ok(DatePart {
+                name,
+                form: p.attribute("form").map(str::to_string),
+                range_delimiter: p.attribute("range-delimiter").map(str::to_string),
+                formatting: formatting(p),
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(DateElement {
+        variable: required(node, "variable")?.to_string(),
+        form: match node.attribute("form") {
+            Some("text") => Some(DateForm::Text),
+            Some("numeric") => Some(DateForm::Numeric),
+            _ => None,
+        },
+        limit: match node.attribute("date-parts") {
+            Some("year") => DatePartName::Year,
+            Some("year-month") => DatePartName::Month,
+            _ => DatePartName::Day,
+        },
+        parts,
+        delimiter: node.attribute("delimiter").unwrap_or_default().to_string(),
+        formatting: formatting(node),
+    })
+}
+
+/// Read the terms and options of a `<locale>` in a style.
+fn locale(node: Node, terms: &mut HashMap<(String, TermForm), Term>, options: &mut StyleOptions) {
+    for child in elements(node) {
+        match child.tag_name().name() {
+            "style-options" => {
+                if let Some(p) = child.attribute("punctuation-in-quote") {
+                    options.punctuation_in_quote = p == "true";
+                }
+            }
+            "terms" => {
+                for term in elements(child) {
+                    let Some(name) = term.attribute("name") else {
+                        continue;
+                    };
+                    let text = |tag: &str| {
+                        elements(term)
+                            .find(|n| n.tag_name().name() == tag)
+                            .map(|n| n.text().unwrap_or_default().to_string())
+                    };
+                    let single = text("single")
+                        .or_else(|| term.text().map(str::to_string))
+                        .unwrap_or_default();
+                    let multiple = text("multiple").unwrap_or_else(|| single.clone());
+                    terms.insert(
+                        (name.to_string(), term_form(term)),
+                        Term { single, multiple },
+                    );
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The terms of the `en-US` locale which styles use most.
+fn en_us_terms() -> HashMap<(String, TermForm), Term> {
+    use TermForm::*;
+    const TERMS: &[(&str, TermForm, &str, &str)] = &[
+        ("accessed", Long, "accessed", "accessed"),
+        ("and", Long, "and", "and"),
+        ("and", Symbol, "&", "&"),
+        ("and others", Long, "and others", "and others"),
+        ("anonymous", Long, "anonymous", "anonymous"),
+        ("anonymous", Short, "anon.", "anon."),
+        ("at", Long, "at", "at"),
+        ("available at", Long, "available at", "available at"),
+        ("by", Long, "by", "by"),
+        ("circa", Long, "circa", "circa"),
+        ("circa", Short, "c.", "c."),
+        ("cited", Long, "cited", "cited"),
+        ("edition", Long, "edition", "editions"),
+        ("edition", Short, "ed.", "eds."),
+        ("et-al", Long, "et al.", "et al."),
+        ("forthcoming", Long, "forthcoming", "forthcoming"),
+        ("from", Long, "from", "from"),
+        ("ibid", Long, "ibid.", "ibid."),
+        ("in", Long, "in", "in"),
+        ("in press", Long, "in press", "in press"),
+        ("internet", Long, "internet", "internet"),
+        ("no date", Long, "no date", "no date"),
+        ("no date", Short, "n.d.", "n.d."),
+        ("online", Long, "online", "online"),
+        ("presented at", Long, "presented at the", "presented at the"),
+        ("reference", Long, "reference", "references"),
+        ("reference", Short, "ref.", "refs."),
+        ("retrieved", Long, "retrieved", "retrieved"),
+        ("version", Long, "version", "versions"),
+        ("open-quote", Long, "\u{201c}", "\u{201c}"),
+        ("close-quote", Long, "\u{201d}", "\u{201d}"),
+        ("open-inner-quote", Long, "\u{2018}", "\u{2018}"),
+        ("close-inner-quote", Long, "\u{2019}", "\u{2019}"),
+        ("page-range-delimiter", Long, "\u{2013}", "\u{2013}"),
+        ("ordinal", Long, "th", "th"),
+        ("ordinal-01", Long, "st", "st"),
+        ("ordinal-02", Long, "nd", "nd"),
+        ("ordinal-03", Long, "rd", "rd"),
+        ("ordinal-11", Long, "th", "th"),
+        ("ordinal-12", Long, "th", "th"),
+        ("ordinal-13", Long, "th", "th"),
+        ("long-ordinal-01", Long, "first", "first"),
+        ("long-ordinal-02", Long, "second", "second"),
+        ("long-ordinal-03", Long, "third", "third"),
+        ("long-ordinal-04", Long, "fourth", "fourth"),
+        ("long-ordinal-05", Long, "fifth", "fifth"),
+        ("long-ordinal-06", Long, "sixth", "sixth"),
+        ("long-ordinal-07", Long, "seventh", "seventh"),
+        ("long-ordinal-08", Long, "eighth", "eighth"),
+        ("long-ordinal-09", Long, "ninth", "ninth"),
+        ("long-ordinal-10", Long, "tenth", "tenth"),
+        // locators
+        ("book", Long, "book", "books"),
+        ("book", Short, "bk.", "bks."),
+        ("chapter", Long, "chapter", "chapters"),
+        ("chapter", Short, "chap.", "chaps."),
+        ("column", Long, "column", "columns"),
+        ("column", Short, "col.", "cols."),
+        ("figure", Long, "figure", "figures"),
+        ("figure", Short, "fig.", "figs."),
+        ("folio", Long, "folio", "folios"),
+        ("folio", Short, "fol.", "fols."),
+        ("issue", Long, "number", "numbers"),
+        ("issue", Short, "no.", "nos."),
+        ("line", Long, "line", "lines"),
+        ("line", Short, "l.", "ll."),
+        ("note", Long, "note", "notes"),
+        ("note", Short, "n.", "nn."),
+        ("number", Long, "number", "numbers"),
+        ("number", Short, "no.", "nos."),
+        ("page", Long, "page", "pages"),
+        ("page", Short, "p.", "pp."),
+        ("paragraph", Long, "paragraph", "paragraphs"),
+        ("paragraph", Short, "para.", "paras."),
+        ("part", Long, "part", "parts"),
+        ("part", Short, "pt.", "pts."),
+        ("section", Long, "section", "sections"),
+        ("section", Short, "sec.", "secs."),
+        ("verse", Long, "verse", "verses"),
+        ("verse", Short, "v.", "vv."),
+        ("volume", Long, "volume", "volumes"),
+        ("volume", Short, "vol.", "vols."),
+        // roles
+        ("editor", Long, "editor", "editors"),
+        ("editor", Short, "ed.", "eds."),
+        ("editor", Verb, "edited by", "edited by"),
+        ("editor", VerbShort, "ed.", "ed."),
+        ("collection-editor", Long, "editor", "editors"),
+        ("collection-editor", Short, "ed.", "eds."),
+        ("container-author", Verb, "by", "by"),
+        ("translator", Long, "translator", "translators"),
+        ("translator", Short, "tran.", "trans."),
+        ("translator", Verb, "translated by", "translated by"),
+        ("translator", VerbShort, "trans.", "trans."),
+    ];
+    const MONTHS: [(&str, &str); 12] = [
+        ("January", "Jan."),
+        ("February", "Feb."),
+        ("March", "Mar."),
+        ("April", "Apr."),
+        ("May", "May"),
+        ("June", "Jun."),
+        ("July", "Jul."),
+        ("August", "Aug."),
+        ("September", "Sep."),
+        ("October", "Oct."),
+        ("November", "Nov."),
+        ("December", "Dec."),
+    ];
+
+    let term = |single: &str, multiple: &str| Term {
+        single: single.to_string(),
+        multiple: multiple.to_string(),
+    };
+    let mut terms: HashMap<_, _> = TERMS
+        .iter()
+        .map(|(name, form, single, multiple)| ((name.to_string(), *form), term(single, multiple)))
+        .collect();
+    for (i, (long, short)) in MONTHS.iter().enumerate() {
+        let name = format!("month-{:02}", i + 1);
+        terms.insert((name.clone(), Long), term(long, long));
+        terms.insert((name, Short), term(short, short));
+    }
+    terms
+}
+
+/// A record cited in a citation, with an optional locator.
+///
+/// ```
+/// use refer::csl::Cite;
+///
+/// let cite = Cite {
+///     locator: Some("12-14".into()),
+///     ..Cite::from(0)
+/// };
+/// assert_eq!(cite.index, 0);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cite {
+    /// The index of the record in those given to the [`Processor`].
+    pub index: usize,
+    /// Where in the work is cited, e.g. `12-14`.
+    pub locator: Option<String>,
+    /// The kind of locator, as a CSL term such as `chapter` or
+    /// `figure`. The default is `page`.
+    pub label: Option<String>,
+}
+
+impl From<usize> for Cite {
+    fn from(index: usize) -> Self {
+        Cite {
+            index,
+            ..Default::default()
+        }
+    }
+}
+
+/// Formats records with a CSL [`Style`].
+///
+/// The records are sorted and disambiguated when the processor is made,
+/// so citation numbers and year suffixes (e.g. `2020a`) are the same in
+/// citations and the bibliography.
+#[derive(Debug)]
+pub struct Processor<'a> {
+    style: &'a Style,
+    items: Vec<Map<String, Value>>,
+    /// The order of the items in the bibliography.
+    order: Vec<usize>,
+    numbers: Vec<usize>,
+    states: Vec<ItemState>,
+    markup: Markup,
+}
+
+/// How an item is changed to tell it apart from the others.
+#[derive(Debug, Clone, Default)]
+struct ItemState {
+    et_al_use_first: Option<usize>,
+    add_givenname: bool,
+    disambiguate: bool,
+    year_suffix: Option<String>,
+}
+
+impl<'a> Processor<'a> {
+    /// A processor for the records, which are cited by their index.
+    pub fn new(style: &'a Style, records: &[Record]) -> Self {
+        let items: Vec<Map<String, Value>> = records
+            .iter()
+            .map(|record| {
+                let mut item = match csl_json::record_to_value(record) {
+                    Value::Object(item) => item,
+                    _ => Map::new(),
+                };
+                if let Some(label) = &record.label {
+                    item.insert("citation-label".into(), Value::String(label.clone()));
+                }
+                item
+            })
+            .collect();
+        let n = items.len();
+        let mut processor = Processor {
+            style,
+            items,
+            order: (0..n).collect(),
+            numbers: (1..=n).collect(),
+            states: vec![ItemState::default(); n],
+            markup: Markup::default(),
+        };
+
+        if let Some(bibliography) = &style.bibliography {
+            let mut order = processor.order.clone();
+            processor.sort(bibliography, &mut order, |&i| i);
+            processor.order = order;
+        }
+        for (rank, &i) in processor.order.iter().enumerate() {
+            processor.numbers[i] = rank + 1;
+        }
+        processor.disambiguate();
+        processor
+    }
+
+    /// Set the markup used by [`Processor::citation`] and
+    /// [`Processor::bibliography`].
+    pub fn set_markup(&mut self, markup: Markup) {
+        self.markup = markup;
+    }
+
+    /// A citation of one or more records, with the markup set. This
+    /// fails if a record doesn't exist.
+    pub fn citation(&self, cites: &[Cite]) -> Result<String> {
+        Ok(self.citation_text(cites)?.render(self.markup))
+    }
+
+    /// A citation of one or more records, as [`Text`].
+    pub fn citation_text(&self, cites: &[Cite]) -> Result<Text> {
+        if let Some(c) = cites.iter().find(|c| c.index >= self.items.len()) {
+            return Err(csl_error(format!("there is no record {} to cite", c.index)));
+        }
+        let section = &self.style.citation;
+        let mut cites: Vec<&Cite> = cites.iter().collect();
+        self.sort(section, &mut cites, |c| c.index);
+
+        let parts: Vec<Text> = cites
+            .iter()
+            .map(|c| {
+                self.renderer(section, c.index, Some(c), true)
+                    .elements(&section.layout)
+            })
+            .collect();
+        let citation = join_pieces(parts, &section.layout_delimiter);
+        let citation = self.style.decorate(citation, &section.layout_formatting);
+        Ok(self.style.finish(citation))
+    }
+
+    /// The bibliography entries of all the records, in order and with
+    /// the markup set. This is empty if the style has no bibliography.
+    pub fn bibliography(&self) -> Vec<String> {
+        self.bibliography_text()
+            .iter()
+            .map(|entry| entry.render(self.markup))
+            .collect()
+    }
+
+    /// The bibliography entries of all the records, as [`Text`].
+    pub fn bibliography_text(&self) -> Vec<Text> {
+        let Some(section) = &self.style.bibliography else {
+            return Vec::new();
+        };
+        let mut previous = None;
+        let mut entries = Vec::new();
+        for &i in &self.order {
+            let mut renderer = self.renderer(section, i, None, false);
+            renderer.previous_names = previous.take();
+            let entry = renderer.elements(&section.layout);
+            previous = renderer.first_names.take();
+            let entry = self.style.decorate(entry, &section.layout_formatting);
+            if !entry.is_empty() {
+                entries.push(self.style.finish(entry));
+            }
+        }
+        entries
+    }
+
+    fn renderer(
+        &'a self,
+        section: &'a Section,
+        index: usize,
+        cite: Option<&'a Cite>,
+        citation: bool,
+    ) -> Renderer<'a> {
+        Renderer {
+            style: self.style,
+            section,
+            item: &self.items[index],
+            number: self.numbers[index],
+            state: &self.states[index],
+            cite,
+            citation,
+            sorting: false,
+            suppressed: HashSet::new(),
+            rendered: Vec::new(),
+            called: 0,
+            found: 0,
+            year_suffix_done: false,
+            previous_names: None,
+            first_names: None,
+            depth: 0,
+        }
+    }
+
+    /// Sort by the keys of a section, with empty values last. The sort
+    /// is stable, so items with equal keys keep their order.
+    fn sort<T>(&self, section: &Section, list: &mut [T], index: impl Fn(&T) -> usize) {
+        if section.sort.is_empty() {
+            return;
+        }
+        let keys: HashMap<usize, Vec<Option<String>>> = list
+            .iter()
+            .map(|t| {
+                let i = index(t);
+                (
+                    i,
+                    section
+                        .sort
+                        .iter()
+                        .map(|k| self.sort_value(section, k, i))
+                        .collect(),
+                )
+            })
+            .collect();
+        list.sort_by(|a, b| {
+            let (a, b) = (&keys[&index(a)], &keys[&index(b)]);
+            for ((x, y), key) in a.iter().zip(b).zip(&section.sort) {
+                let ordering = match (x, y) {
+                    (Some(x), Some(y)) if key.descending => y.cmp(x),
+                    (Some(x), Some(y)) => x.cmp(y),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            Ordering::Equal
+        });
+    }
+
+    fn sort_value(&self, section: &Section, key: &SortKey, index: usize) -> Option<String> {
+        let value = match &key.source {
+            SortSource::Macro(m) => {
+                let mut renderer = self.renderer(section, index, None, false);
+                renderer.sorting = true;
+                renderer.elements(&self.style.macros[m]).to_string()
+            }
+            SortSource::Variable(v) if v == "citation-number" => {
+                format!("{:010}", self.numbers[index])
+            }
+            SortSource::Variable(v) => match self.items[index].get(v) {
+                Some(Value::Array(names)) => names
+                    .iter()
+                    .map(|n| {
+                        ["literal", "family", "given"]
+                            .iter()
+                            .filter_map(|p| n.get(p).and_then(Value::as_str))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                Some(date @ Value::Object(_)) => match date_value(date) {
+                    Some(DateValue::Parts(start, end, _)) => {
+                        let key = |d: &Ymd| {
+                            format!(
+                                "{:05}{:02}{:02}",
+                                d.year + 10000,
+                                d.month.unwrap_or(0),
+                                d.day.unwrap_or(0)
+                            )
+                        };
+                        format!(
+                            "{}{}",
+                            key(&start),
+                            end.as_ref().map(key).unwrap_or_default()
+                        )
+                    }
+                    _ => String::new(),
+                },
+                Some(Value::Number(n)) => format!("{:010}", n.as_u64().unwrap_or_default()),
+                Some(Value::String(s)) => match s.parse::<u64>() {
+                    Ok(n) => format!("{:010}", n),
+                    Err(_) => s.clone(),
+                },
+                _ => String::new(),
+            },
+        };
+        let value = value.trim().to_lowercase();
+        (!value.is_empty()).then_some(value)
+    }
+
+    /// Make the citations of different items different, as far as the
+    /// style allows: by showing more names, then given names, then with
+    /// the `disambiguate` condition, and then with year suffixes.
+    fn disambiguate(&mut self) {
+        let section = &self.style.citation;
+        let render =
+            |p: &Self, i: usize| p.renderer(section, i, None, true).elements(&section.layout);
+        let ambiguous = |p: &Self| -> Vec<Vec<usize>> {
+            let mut groups: HashMap<Text, Vec<usize>> = HashMap::new();
+            for i in 0..p.items.len() {
+                let cite = render(p, i);
+                if !cite.is_empty() {
+                    groups.entry(cite).or_default().push(i);
+                }
+            }
+            let mut groups: Vec<Vec<usize>> =
+                groups.into_values().filter(|g| g.len() > 1).collect();
+            groups.sort();
+            groups
+        };
+
+        if section.add_names {
+            for group in ambiguous(self) {
+                let most = group
+                    .iter()
+                    .filter_map(|&i| self.items[i].get("author")?.as_array().map(Vec::len))
+                    .max()
+                    .unwrap_or(0);
+                for n in 1..=most {
+                    for &i in &group {
+                        self.states[i].et_al_use_first = Some(n);
+                    }
+                    let cites: HashSet<Text> = group.iter().map(|&i| render(self, i)).collect();
+                    if cites.len() == group.len() {
+                        break;
+                    }
+                }
+            }
+        }
+        if section.add_givenname {
+            for group in ambiguous(self) {
+                let before: HashSet<Text> = group.iter().map(|&i| render(self, i)).collect();
+                for &i in &group {
+                    self.states[i].add_givenname = true;
+                }
+                // given names are only kept where they help
+                let after: HashSet<Text> = group.iter().map(|&i| render(self, i)).collect();
+                if after.len() <= before.len() {
+                    for &i in &group {
+                        self.states[i].add_givenname = false;
+                    }
+                }
+            }
+        }
+        for i in ambiguous(self).into_iter().flatten() {
+            self.states[i].disambiguate = true;
+        }
+        if section.add_year_suffix {
+            for mut group in ambiguous(self) {
+                group.sort_by_key(|&i| self.numbers[i]);
+                for (n, i) in group.into_iter().enumerate() {
+                    self.states[i].year_suffix = Some(suffix(n + 1));
+                }
+            }
+        }
+    }
+}
+
+/// Renders the elements of a style for one item.
+struct Renderer<'a> {
+    style: &'a Style,
+    section: &'a Section,
+    item: &'a Map<String, Value>,
+    number: usize,
+    state: &'a ItemState,
+    cite: Option<&'a Cite>,
+    /// Whether this is a citation, rather than a bibliography entry.
+    citation: bool,
+    /// Names are all inverted and given in full for sort keys.
+    sorting: bool,
+    /// Variables which have been substituted for names.
+    suppressed: HashSet<String>,
+    /// The variables which have been rendered.
+    rendered: Vec<String>,
+    /// The number of variables called and found, for suppressing groups.
+    called: usize,
+    found: usize,
+    year_suffix_done: bool,
+    previous_names: Option<Text>,
+    first_names: Option<Text>,
+    depth: usize,
+}
+
+impl<'a> Renderer<'a> {
+    fn elements(&mut self, elements: &'a [Element]) -> Text {
+        let parts: Vec<Text> = elements.iter().map(|e| self.element(e)).collect();
+        join_pieces(parts, "")
+    }
+
+    fn element(&mut self, element: &'a Element) -> Text {
+        match element {
+            Element::Text(source, formatting) => {
+                let text = match source {
+                    TextSource::Variable(name, form) => {
+                        let text = self.text_variable(name, *form);
+                        if matches!(name.as_str(), "DOI" | "URL") && !text.is_empty() {
+                            return self.link(name, &text, formatting);
+                        }
+                        Text::from(text)
+                    }
+                    TextSource::Macro(name) => {
+                        if self.depth >= MAX_MACRO_DEPTH {
+                            return Text::default();
+                        }
+                        self.depth += 1;
+                        let style = self.style;
+                        let text = self.elements(&style.macros[name]);
+                        self.depth -= 1;
+                        text
+                    }
+                    TextSource::Term(name, form, plural) => {
+                        Text::plain(self.style.term(name, *form, *plural).unwrap_or_default())
+                    }
+                    TextSource::Value(value) => Text::plain(value),
+                };
+                self.decorate(text, formatting)
+            }
+            Element::Number(variable, form, formatting) => match self.variable(variable) {
+                Some(value) => {
+                    let text = match variable.as_str() {
+                        "page" | "locator" => self.page_range(&value),
+                        _ => self.number(&value, *form),
+                    };
+                    self.decorate(text.into(), formatting)
+                }
+                None => Text::default(),
+            },
+            Element::Label(variable, label) => {
+                let (term, value) = match variable.as_str() {
+                    "locator" => match self.cite {
+                        Some(c) => (c.label.as_deref().unwrap_or("page"), c.locator.clone()),
+                        None => return Text::default(),
+                    },
+                    _ => (variable.as_str(), self.lookup(variable)),
+                };
+                match value {
+                    Some(value) => self.label(term, label, is_plural(variable, &value)),
+                    None => Text::default(),
+                }
+            }
+            Element::Names(names) => self.names(names, None),
+            Element::Date(date) => self.date(date),
+            Element::Group(elements, delimiter, formatting) => {
+                let (called, found) = (self.called, self.found);
+                (self.called, self.found) = (0, 0);
+                let parts: Vec<Text> = elements.iter().map(|e| self.element(e)).collect();
+                // a group is left out if it calls variables but none are set
+                let suppress = self.called > 0 && self.found == 0;
+                self.called += called;
+                self.found += found;
+                match suppress {
+                    true => Text::default(),
+                    false => self.decorate(join_pieces(parts, delimiter), formatting),
+                }
+            }
+            Element::Choose(branches) => match branches.iter().find(|b| self.branch_matches(b)) {
+                Some(branch) => self.elements(&branch.elements),
+                None => Text::default(),
+            },
+        }
+    }
+
+    fn decorate(&self, text: Text, formatting: &Formatting) -> Text {
+        self.style.decorate(text, formatting)
+    }
+
+    /// The value of a variable, which counts towards whether a group is
+    /// shown.
+    fn variable(&mut self, name: &str) -> Option<String> {
+        self.called += 1;
+        let value = self.lookup(name);
+        if value.is_some() {
+            self.found += 1;
+            self.rendered.push(name.to_string());
+        }
+        value
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        if self.suppressed.contains(name) {
+            return None;
+        }
+        let value = match name {
+            "citation-number" => Some(self.number.to_string()),
+            "year-suffix" => self.state.year_suffix.clone(),
+            "locator" => self.cite.and_then(|c| c.locator.clone()),
+            "page-first" => self.lookup("page").and_then(|p| {
+                p.split(['-', '\u{2013}', ',', '&'])
+                    .next()
+                    .map(str::to_string)
+            }),
+            _ => match self.item.get(name)? {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            },
+        };
+        value.filter(|v| !v.trim().is_empty())
+    }
+
+    fn text_variable(&mut self, name: &str, form: TermForm) -> String {
+        // the short form of a variable falls back to the long form
+        let short = match (form, name) {
+            (TermForm::Short, "title") => self.lookup("title-short"),
+            (TermForm::Short, "container-title") => self.lookup("container-title-short"),
+            _ => None,
+        };
+        let value = match short {
+            Some(s) => {
+                self.variable(name);
+                Some(s)
+            }
+            None => self.variable(name),
+        };
+        match value {
+            Some(v) if name == "page" => self.page_range(&v),
+            Some(v) => v,
+            None => String::new(),
+        }
+    }
+
+    /// A DOI or URL as a link. A DOI with a prefix such as
+    /// `https://doi.org/` is linked along with the prefix.
+    fn link(&self, name: &str, value: &str, formatting: &Formatting) -> Text {
+        let mut formatting = formatting.clone();
+        let text = match formatting.prefix.find("http") {
+            Some(at) if name == "DOI" => {
+                let url = format!("{}{}", &formatting.prefix[at..], value);
+                formatting.prefix.truncate(at);
+                link(&url, &url)
+            }
+            _ if name == "DOI" => link(&format!("https://doi.org/{}", value), value),
+            _ => link(value, value),
+        };
+        self.decorate(text, &formatting)
+    }
+
+    fn page_range(&self, value: &str) -> String {
+        let Some(format) = self.style.options.page_range_format else {
+            return value.to_string();
+        };
+        let delimiter = self
+            .style
+            .term("page-range-delimiter", TermForm::Long, false)
+            .unwrap_or("\u{2013}");
+        match value.parse::<PageRange>() {
+            Ok(range) => range.format(format, delimiter),
+            Err(_) => value.to_string(),
+        }
+    }
+
+    fn number(&self, value: &str, form: NumberForm) -> String {
+        let Ok(n) = value.trim().parse::<u32>() else {
+            return value.to_string();
+        };
+        let ordinal = |n: u32| {
+            let term = match (n % 100, n % 10) {
+                (11..=13, _) => "ordinal-1".to_string() + &(n % 10).to_string(),
+                (_, d @ 1..=3) => format!("ordinal-0{}", d),
+                _ => "ordinal".to_string(),
+            };
+            let suffix = self
+                .style
+                .term(&term, TermForm::Long, false)
+                .or_else(|| self.style.term("ordinal", TermForm::Long, false))
+                .unwrap_or_default();
+            format!("{}{}", n, suffix)
+        };
+        match form {
+            NumberForm::Numeric => n.to_string(),
+            NumberForm::Ordinal => ordinal(n),
+            NumberForm::LongOrdinal => {
+                let term = format!("long-ordinal-{:02}", n);
+                match self.style.term(&term, TermForm::Long, false) {
+                    Some(t) => t.to_string(),
+                    None => ordinal(n),
+                }
+            }
+            NumberForm::Roman => to_roman(n),
+        }
+    }
+
+    fn label(&self, term: &str, label: &Label, plural: bool) -> Text {
+        let plural = match label.plural {
+            Plural::Always => true,
+            Plural::Never => false,
+            Plural::Contextual => plural,
+        };
+        let text = self
+            .style
+            .term(term, label.form, plural)
+            .unwrap_or_default();
+        self.decorate(Text::plain(text), &label.formatting)
+    }
+
+    fn names(&mut self, names: &'a Names, parent: Option<&'a Names>) -> Text {
+        // in a substitute, names without a <name> are formatted as the
+        // names they are substituted for
+        let base = match parent {
+            Some(p) if !names.own_name => p,
+            _ => names,
+        };
+        let options = self
+            .style
+            .options
+            .names
+            .merge(&self.section.names)
+            .merge(&base.name);
+
+        let mut lists = Vec::new();
+        let mut count = 0;
+        for variable in &names.variables {
+            self.called += 1;
+            if self.suppressed.contains(variable) {
+                continue;
+            }
+            let list = match self.item.get(variable) {
+                Some(Value::Array(list)) if !list.is_empty() => list,
+                _ => continue,
+            };
+            self.found += 1;
+            self.rendered.push(variable.clone());
+            let (mut text, shown) = self.name_list(list, &options, base);
+            count += shown;
+            if let Some(label) = &base.label {
+                let mut label = self.label(variable, label, list.len() > 1);
+                text = match base.label_before {
+                    true => {
+                        label.append(text);
+                        label
+                    }
+                    false => {
+                        text.append(label);
+                        text
+                    }
+                };
+            }
+            lists.push(text);
+        }
+
+        let text = if options.form.as_deref() == Some("count") {
+            match count {
+                0 => Text::default(),
+                n => self.decorate(n.to_string().into(), &names.formatting),
+            }
+        } else if lists.is_empty() {
+            let mut substituted = Text::default();
+            for element in &names.substitute {
+                let mark = self.rendered.len();
+                let text = match element {
+                    Element::Names(n) => self.names(n, Some(base)),
+                    element => self.element(element),
+                };
+                if !text.is_empty() {
+                    // a substituted variable isn't repeated in the entry
+                    let used = self.rendered[mark..].to_vec();
+                    self.suppressed.extend(used);
+                    substituted = text;
+                    break;
+                }
+            }
+            substituted
+        } else {
+            let delimiter = names
+                .delimiter
+                .as_deref()
+                .or(self.section.names_delimiter.as_deref())
+                .unwrap_or(", ");
+            self.decorate(join_pieces(lists, delimiter), &names.formatting)
+        };
+
+        // the first names of a bibliography entry may be replaced when
+        // they are the same as the entry before
+        match &self.section.author_substitute {
+            Some(substitute) if !self.citation && !self.sorting && self.first_names.is_none() => {
+                self.first_names = Some(text.clone());
+                match self.previous_names.as_ref() == Some(&text) && !text.is_empty() {
+                    true => Text::plain(substitute),
+                    false => text,
+                }
+            }
+            _ => text,
+        }
+    }
+
+    /// A list of names, with `and` and `et al.`, and the number of names
+    /// shown.
+    fn name_list(&self, list: &[Value], options: &NameOptions, base: &Names) -> (Text, usize) {
+        let n = list.len();
+        let mut use_first = options.et_al_use_first.unwrap_or(n);
+        if self.citation {
+            if let Some(more) = self.state.et_al_use_first {
+                use_first = use_first.max(more);
+            }
+        }
+        let truncate = options.et_al_min.is_some_and(|min| n >= min) && use_first < n;
+        let shown = match truncate {
+            true => use_first.max(1),
+            false => n,
+        };
+        let inverted = |i: usize| {
+            self.sorting
+                || match options.name_as_sort_order.as_deref() {
+                    Some("all") => true,
+                    Some("first") => i == 0,
+                    _ => false,
+                }
+        };
+        let names: Vec<Text> = list[..shown]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| self.name(name, options, base, inverted(i)))
+            .collect();
+
+        let delimiter = options.delimiter.as_deref().unwrap_or(", ");
+        let and = match options.and.as_deref() {
+            Some("text") => self.style.term("and", TermForm::Long, false),
+            Some("symbol") => self.style.term("and", TermForm::Symbol, false),
+            _ => None,
+        };
+        let precedes = |option: &Option<String>, i: usize, contextual: bool| match option.as_deref()
+        {
+            Some("always") => true,
+            Some("never") => false,
+            Some("after-inverted-name") => inverted(i),
+            _ => contextual,
+        };
+
+        let mut text = Text::default();
+        for (i, name) in names.into_iter().enumerate() {
+            if i > 0 {
+                let last = i == shown - 1 && !truncate;
+                match and.filter(|_| last) {
+                    Some(and) => {
+                        let delimiter_precedes =
+                            precedes(&options.delimiter_precedes_last, i - 1, shown > 2);
+                        text.push_str(if delimiter_precedes { delimiter } else { " " });
+                        text.push_str(and);
+                        text.push(' ');
+                    }
+                    None => text.push_str(delimiter),
+                }
+            }
+            text.append(name);
+        }
+        if truncate {
+            if options.et_al_use_last == Some(true) && shown + 1 < n {
+                text.push_str(delimiter);
+                text.push_str("\u{2026} ");
+                text.append(self.name(&list[n - 1], options, base, false));
+            } else {
+                let (term, formatting) = match &base.et_al {
+                    Some((term, formatting)) => (term.as_str(), formatting.clone()),
+                    None => ("et-al", Formatting::default()),
+                };
+                let et_al = self
+                    .style
+                    .term(term, TermForm::Long, false)
+                    .unwrap_or_default();
+                if !et_al.is_empty() {
+                    let delimiter_precedes =
+                        precedes(&options.delimiter_precedes_et_al, shown - 1, shown > 1);
+                    text.push_str(if delimiter_precedes { delimiter } else { " " });
+                    text.append(self.decorate(Text::plain(et_al), &formatting));
+                }
+            }
+        }
+        (self.decorate(text, &base.name_formatting), shown)
+    }
+
+    fn name(&self, name: &Value, options: &NameOptions, base: &Names, inverted: bool) -> Text {
+        let part = |key: &str| name.get(key).and_then(Value::as_str).unwrap_or_default();
+        let literal = part("literal");
+        if !literal.is_empty() {
+            return self.decorate(Text::plain(literal), &base.family);
+        }
+        let (family, particle, dropping, suffix) = (
+            part("family"),
+            part("non-dropping-particle"),
+            part("dropping-particle"),
+            part("suffix"),
+        );
+        let given = match (&options.initialize_with, options.initialize) {
+            (Some(with), initialize) if initialize != Some(false) => initialize_given(
+                part("given"),
+                with,
+                self.style.options.initialize_with_hyphen,
+            ),
+            _ => part("given").to_string(),
+        };
+
+        // given names are added to short names to disambiguate
+        let short = options.form.as_deref() == Some("short")
+            && !(self.citation && self.state.add_givenname);
+        if short {
+            return self.decorate(join_words(&[particle, family]).into(), &base.family);
+        }
+        if !inverted {
+            let given = self.decorate(join_words(&[&given, dropping]).into(), &base.given);
+            let family = self.decorate(join_words(&[particle, family]).into(), &base.family);
+            return join_pieces(vec![given, family, Text::plain(suffix)], " ");
+        }
+
+        let demote = match self.style.options.demote_particle {
+            Demote::DisplayAndSort => true,
+            Demote::SortOnly => self.sorting,
+            Demote::Never => false,
+        };
+        let (family, given) = match demote {
+            true => (
+                family.to_string(),
+                join_words(&[&given, dropping, particle]),
+            ),
+            false => (
+                join_words(&[particle, family]),
+                join_words(&[&given, dropping]),
+            ),
+        };
+        let separator = options.sort_separator.as_deref().unwrap_or(", ");
+        let mut text = self.decorate(family.into(), &base.family);
+        for part in [
+            self.decorate(given.into(), &base.given),
+            Text::plain(suffix),
+        ] {
+            if !part.is_empty() {
+                text.push_str(separator);
+                text.append(part);
+            }
+        }
+        text
+    }
+
+    fn date(&mut self, date: &DateElement) -> Text {
+        self.called += 1;
+        if self.suppressed.contains(&date.variable) {
+            return Text::default();
+        }
+        let value = match self.item.get(&date.variable).and_then(date_value) {
+            Some(value) => value,
+            None => return Text::default(),
+        };
+        self.found += 1;
+        self.rendered.push(date.variable.clone());
+
+        let (start, end) = match value {
+            DateValue::Literal(literal) => return self.decorate(literal.into(), &date.formatting),
+            DateValue::Parts(start, end, _) => (start, end),
+        };
+        let parts = self.style.date_parts(date);
+        let text = match end.filter(|e| *e != start) {
+            None => self.date_side(&parts, &start, &date.delimiter, false, &date.variable),
+            Some(end) => {
+                let differing = if start.year != end.year {
+                    DatePartName::Year
+                } else if start.month != end.month {
+                    DatePartName::Month
+                } else {
+                    DatePartName::Day
+                };
+                // the parts from the largest which differs are written
+                // twice, e.g. `1 March–3 April 2020`
+                let range: Vec<usize> = (0..parts.len())
+                    .filter(|&i| parts[i].name >= differing)
+                    .collect();
+                match (range.first(), range.last()) {
+                    (Some(&lo), Some(&hi)) => {
+                        let range_delimiter = parts
+                            .iter()
+                            .find(|p| p.name == differing)
+                            .unwrap_or(&parts[lo])
+                            .range_delimiter
+                            .as_deref()
+                            .unwrap_or("\u{2013}");
+                        let variable = &date.variable;
+                        let before =
+                            self.date_side(&parts[..lo], &start, &date.delimiter, false, variable);
+                        let from = self.date_side(
+                            &parts[lo..=hi],
+                            &start,
+                            &date.delimiter,
+                            true,
+                            variable,
+                        );
+                        let to =
+                            self.date_side(&parts[lo..=hi], &end, &date.delimiter, false, variable);
+                        let after = self.date_side(
+                            &parts[hi + 1..],
+                            &end,
+                            &date.delimiter,
+                            false,
+                            variable,
+                        );
+                        let mut range = from;
+                        range.push_str(range_delimiter);
+                        range.append(to);
+                        join_pieces(vec![before, range, after], &date.delimiter)
+                    }
+                    _ => self.date_side(&parts, &start, &date.delimiter, false, &date.variable),
+                }
+            }
+        };
+        self.decorate(text, &date.formatting)
+    }
+
+    /// The parts of one end of a date. In the start of a range, the
+    /// suffix of the last part is left for the end.
+    fn date_side(
+        &mut self,
+        parts: &[DatePart],
+        date: &Ymd,
+        delimiter: &str,
+        range_start: bool,
+        variable: &str,
+    ) -> Text {
+        let mut pieces = Vec::new();
+        for (i, part) in parts.iter().enumerate() {
+            let form = part.form.as_deref();
+            let text = match part.name {
+                DatePartName::Year => {
+                    let mut year = match (form, date.year) {
+                        (Some("short"), y) => format!("{:02}", y.rem_euclid(100)),
+                        (_, y) if y < 0 => format!("{}BC", -y),
+                        (_, y) => y.to_string(),
+                    };
+                    // the year suffix follows the first year of the issued date
+                    if variable == "issued"
+                        && !self.year_suffix_done
+                        && !self.style.options.renders_year_suffix
+                    {
+                        if let Some(s) = &self.state.year_suffix {
+                            year.push_str(s);
+                            self.year_suffix_done = true;
+                        }
+                    }
+                    year
+                }
+                DatePartName::Month => match date.month.filter(|m| (1..=12).contains(m)) {
+                    Some(m) => match form {
+                        Some("numeric") => m.to_string(),
+                        Some("numeric-leading-zeros") => format!("{:02}", m),
+                        Some("short") => self.month(m, TermForm::Short),
+                        _ => self.month(m, TermForm::Long),
+                    },
+                    None => continue,
+                },
+                DatePartName::Day => match date.day {
+                    Some(d) => match form {
+                        Some("numeric-leading-zeros") => format!("{:02}", d),
+                        Some("ordinal") => self.number(&d.to_string(), NumberForm::Ordinal),
+                        _ => d.to_string(),
+                    },
+                    None => continue,
+                },
+            };
+            let mut formatting = part.formatting.clone();
+            if range_start && i == parts.len() - 1 {
+                formatting.suffix.clear();
+            }
+            pieces.push(self.decorate(text.into(), &formatting));
+        }
+        join_pieces(pieces, delimiter)
+    }
+
+    fn month(&self, month: u32, form: TermForm) -> String {
+        let term = format!("month-{:02}", month);
+        self.style
+            .term(&term, form, false)
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    fn branch_matches(&self, branch: &Branch) -> bool {
+        if branch.conditions.is_empty() {
+            return true;
+        }
+        let mut results = branch.conditions.iter().map(|c| self.condition(c));
+        match branch.matching {
+            Match::All => results.all(|r| r),
+            Match::Any => results.any(|r| r),
+            Match::None => !results.any(|r| r),
+        }
+    }
+
+    fn condition(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Type(t) => self.item.get("type").and_then(Value::as_str) == Some(t),
+            Condition::Variable(v) => {
+                !self.suppressed.contains(v)
+                    && match self.item.get(v) {
+                        Some(Value::Array(a)) => !a.is_empty(),
+                        Some(Value::Object(_)) => true,
+                        _ => self.lookup(v).is_some(),
+                    }
+            }
+            Condition::IsNumeric(v) => self.lookup(v).is_some_and(|v| is_numeric(&v)),
+            Condition::IsUncertainDate(v) => self
+                .item
+                .get(v)
+                .and_then(|d| d.get("circa"))
+                .is_some_and(|c| c.as_bool().unwrap_or(!c.is_null())),
+            Condition::Locator(l) => self
+                .cite
+                .is_some_and(|c| c.locator.is_some() && c.label.as_deref().unwrap_or("page") == l),
+            // every cite is a first cite
+            Condition::Position(p) => self.citation && p == "first",
+            Condition::Disambiguate(d) => self.citation && self.state.disambiguate == *d,
+        }
+    }
+}
+
+impl NameOptions {
+    /// These options, overridden by those set in `other`.
+    fn merge(&self, other: &NameOptions) -> NameOptions {
+        NameOptions {
+            and: other.and.clone().or(self.and.clone()),
+            delimiter: other.delimiter.clone().or(self.delimiter.clone()),
+            delimiter_precedes_et_al: other
+                .delimiter_precedes_et_al
+                .clone()
+                .or(self.delimiter_precedes_et_al.clone()),
+            delimiter_precedes_last: other
+                .delimiter_precedes_last
+                .clone()
+                .or(self.delimiter_precedes_last.clone()),
+            et_al_min: other.et_al_min.or(self.et_al_min),
+            et_al_use_first: other.et_al_use_first.or(self.et_al_use_first),
+            et_al_use_last: other.et_al_use_last.or(self.et_al_use_last),
+            form: other.form.clone().or(self.form.clone()),
+            initialize: other.initialize.or(self.initialize),
+            initialize_with: other
+                .initialize_with
+                .clone()
+                .or(self.initialize_with.clone()),
+            name_as_sort_order: other
+                .name_as_sort_order
+                .clone()
+                .or(self.name_as_sort_order.clone()),
+            sort_separator: other.sort_separator.clone().or(self.sort_separator.clone()),
+        }
+    }
+}
+
+impl TextCase {
+    /// Change the case of the text, or of a part of it which doesn't
+    /// come `first`, after a span.
+    fn apply(self, text: &str, first: bool) -> String {
+        match self {
+            TextCase::Lowercase => text.to_lowercase(),
+            TextCase::Uppercase => text.to_uppercase(),
+            TextCase::CapitalizeFirst | TextCase::Sentence if !first => text.to_string(),
+            TextCase::CapitalizeFirst => capitalize(text),
+            TextCase::CapitalizeAll => text
+                .split(' ')
+                .map(capitalize)
+                .collect::<Vec<_>>()
+                .join(" "),
+            // only a title which is all in capitals is lower cased first
+            TextCase::Sentence if text == text.to_uppercase() => capitalize(&text.to_lowercase()),
+            TextCase::Sentence => capitalize(text),
+            TextCase::Title => {
+                const SMALL: [&str; 25] = [
+                    "a", "an", "and", "as", "at", "but", "by", "down", "for", "from", "in", "into",
+                    "nor", "of", "on", "onto", "or", "over", "so", "the", "till", "to", "up",
+                    "via", "with",
+                ];
+                let words: Vec<&str> = text.split(' ').collect();
+                let last = words.len() - 1;
+                words
+                    .iter()
+                    .enumerate()
+                    .map(
+                        |(i, word)| match SMALL.contains(word) && i != 0 && i != last {
+                            true => word.to_string(),
+                            false => capitalize(word),
+                        },
+                    )
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+        }
+    }
+}
+
+/// Upper case the first letter, leaving the rest.
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// A date of a CSL item.
+#[derive(Debug, Clone, PartialEq)]
+enum DateValue {
+    /// The start, the end of a range and whether it's approximate.
+    Parts(Ymd, Option<Ymd>, bool),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Ymd {
+    year: i32,
+    month: Option<u32>,
+    day: Option<u32>,
+}
+
+/// Read a date from its `date-parts`, or a `literal`.
+fn date_value(value: &Value) -> Option<DateValue> {
+    let number = |v: &Value| match v {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    let ymd = |parts: &Value| {
+        let parts = parts.as_array()?;
+        Some(Ymd {
+            year: number(parts.first()?)? as i32,
+            month: parts.get(1).and_then(number).map(|m| m as u32),
+            day: parts.get(2).and_then(number).map(|d| d as u32),
+        })
+    };
+    if let Some(parts) = value.get("date-parts").and_then(Value::as_array) {
+        let start = ymd(parts.first()?)?;
+        let end = parts.get(1).and_then(ymd);
+        let circa = value.get("circa").is_some_and(|c| !c.is_null());
+        return Some(DateValue::Parts(start, end, circa));
+    }
+    ["literal", "raw"]
+        .iter()
+        .find_map(|k| value.get(k).and_then(Value::as_str))
+        .map(|l| DateValue::Literal(l.to_string()))
+}
+
+/// `Max James` to `M. J.` with `initialize-with=". "`, or `J.-A.` for
+/// `Jo-Anne`.
+fn initialize_given(given: &str, with: &str, hyphen: bool) -> String {
+    let mark = with.trim_end();
+    let spacing = &with[mark.len()..];
+    let mut initials = String::new();
+    for word in given.split_whitespace() {
+        let word: Vec<String> = word
+            .split('-')
+            .filter_map(|part| part.chars().next())
+            .map(|c| format!("{}{}", c.to_uppercase(), mark))
+            .collect();
+        initials.push_str(&word.join(if hyphen { "-" } else { "" }));
+        initials.push_str(spacing);
+    }
+    initials.trim_end().to_string()
+}
+
+/// Join the non-empty parts of a name with spaces.
+fn join_words(words: &[&str]) -> String {
+    words
+        .iter()
+        .filter(|w| !w.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Join the non-empty pieces of output with a delimiter.
+fn join_pieces(pieces: Vec<Text>, delimiter: &str) -> Text {
+    let mut out = Text::default();
+    for (i, piece) in pieces.into_iter().filter(|p| !p.is_empty()).enumerate() {
+        if i > 0 {
+            push_piece(&mut out, Text::plain(delimiter));
+        }
+        push_piece(&mut out, piece);
+    }
+    out
+}
+
+/// Push a piece of output, without doubling a full stop, e.g. from
+/// `et al.` and a `.` suffix.
+fn push_piece(out: &mut Text, mut piece: Text) {
+    let end = out
+        .rev_chars()
+        .find(|c| !['\u{201d}', '\u{2019}', '"'].contains(c));
+    if matches!(end, Some('.' | '?' | '!')) {
+        piece.strip_prefix('.');
+    }
+    out.append(piece);
+}
+
+/// Whether a page range or a list of names is plural.
+fn is_plural(variable: &str, value: &str) -> bool {
+    match variable.strip_prefix("number-of-") {
+        Some(_) => value.trim().parse::<u32>().is_ok_and(|n| n > 1),
+        None => value.contains(['-', '\u{2013}', ',', '&']) || value.contains(" and "),
+    }
+}
+
+/// Whether a value is a number, a range or list of numbers, or numbers
+/// with letters, such as `12`, `12-14`, `2nd` or `L2`.
+fn is_numeric(value: &str) -> bool {
+    let mut tokens = value
+        .split(['-', '\u{2013}', ',', '&'])
+        .map(str::trim)
+        .peekable();
+    tokens.peek().is_some()
+        && tokens.all(|token| {
+            token.chars().any(|c| c.is_ascii_digit())
+                && token.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+fn csl_error(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::Csl(message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Author;
+
+    fn author(last: &str, rest: &str) -> Author {
+        Author {
+            last: last.into(),
+            rest: rest.into(),
+            ..Default::default()
+        }
+    }
+
+    fn record(authors: &[(&str, &str)], date: &str, title: &str) -> Record {
+        Record {
+            author: authors.iter().map(|(l, r)| author(l, r)).collect(),
+            date: Some(date.into()),
+            title: Some(title.into()),
+            ..Default::default()
+        }
+    }
+
+    const AUTHOR_DATE: &str = r#"<style xmlns="http://purl.org/net/xbiblio/csl" class="in-text" version="1.0">
+  <info><title>Test author-date</title></info>
+  <macro name="author">
+    <names variable="author">
+      <name name-as-sort-order="first" and="text" initialize-with=". " delimiter=", "/>
+      <substitute><names variable="editor"/><text variable="title"/></substitute>
+    </names>
+  </macro>
+  <citation et-al-min="3" et-al-use-first="1" disambiguate-add-names="true"
+            disambiguate-add-givenname="true" disambiguate-add-year-suffix="true">
+    <layout prefix="(" suffix=")" delimiter="; ">
+      <group delimiter=", ">
+        <names variable="author">
+          <name form="short" and="symbol" initialize-with=". "/>
+          <substitute><text variable="title" font-style="italic"/></substitute>
+        </names>
+        <date variable="issued"><date-part name="year"/></date>
+        <group delimiter=" ">
+          <label variable="locator" form="short"/>
+          <text variable="locator"/>
+        </group>
+      </group>
+    </layout>
+  </citation>
+  <bibliography subsequent-author-substitute="———">
+    <sort><key macro="author"/><key variable="issued"/></sort>
+    <layout suffix=".">
+      <group delimiter=". ">
+        <text macro="author"/>
+        <date variable="issued"><date-part name="year"/></date>
+        <text variable="title"/>
+      </group>
+    </layout>
+  </bibliography>
+</style>"#;
+
+    #[test]
+    fn author_date() {
+        let style: Style = AUTHOR_DATE.parse().unwrap();
+        assert_eq!(style.title(), Some("Test author-date"));
+        assert_eq!(style.class(), Class::InText);
+
+        let records = [
+            record(&[("Twyford", "Alex"), ("Brown", "Max")], "2020", "Hosts"),
+            record(&[("Brown", "Max James")], "2023", "Parasitic plants"),
+            record(
+                &[("Brown", "Max"), ("Twyford", "Alex"), ("Smith", "Jo")],
+                "2021",
+                "Haustoria",
+            ),
+            Record {
+                title: Some("Anonymous works".into()),
+                date: Some("2019".into()),
+                ..Default::default()
+            },
+        ];
+        let processor = Processor::new(&style, &records);
+
+        let cite = Cite {
+            locator: Some("12-14".into()),
+            ..Cite::from(0)
+        };
+        assert_eq!(
+            processor.citation(&[cite, 2.into(), 3.into()]).unwrap(),
+            "(Twyford & Brown, 2020, pp. 12-14; Brown et al., 2021; Anonymous works, 2019)"
+        );
+        assert_eq!(
+            processor.bibliography(),
+            [
+                "Anonymous works. 2019.",
+                "Brown, M. J. 2023. Parasitic plants.",
+                "Brown, M., A. Twyford, and J. Smith. 2021. Haustoria.",
+                "Twyford, A. and M. Brown. 2020. Hosts.",
+            ]
+        );
+        assert!(matches!(
+            processor.citation(&[9.into()]).unwrap_err().kind(),
+            ErrorKind::Csl(_)
+        ));
+    }
+
+    #[test]
+    fn disambiguation() {
+        let style: Style = AUTHOR_DATE.parse().unwrap();
+        let records = [
+            // more names tell these apart
+            record(
+                &[("Brown", "Max"), ("Smith", "Jo"), ("Lee", "Al")],
+                "2020",
+                "A",
+            ),
+            record(
+                &[("Brown", "Max"), ("Twyford", "Alex"), ("Lee", "Al")],
+                "2020",
+                "B",
+            ),
+            // given names tell these apart
+            record(&[("Jones", "Ann")], "2018", "C"),
+            record(&[("Jones", "Bea")], "2018", "D"),
+            // only a year suffix tells these apart
+            record(&[("Lee", "Al")], "2019", "F"),
+            record(&[("Lee", "Al")], "2019", "E"),
+        ];
+        let processor = Processor::new(&style, &records);
+        let cite = |i: usize| processor.citation(&[i.into()]).unwrap();
+
+        assert_eq!(cite(0), "(Brown, Smith, et al., 2020)");
+        assert_eq!(cite(1), "(Brown, Twyford, et al., 2020)");
+        assert_eq!(cite(2), "(A. Jones, 2018)");
+        assert_eq!(cite(3), "(B. Jones, 2018)");
+        assert_eq!(cite(4), "(Lee, 2019a)");
+        assert_eq!(cite(5), "(Lee, 2019b)");
+        assert_eq!(
+            &processor.bibliography()[4..],
+            ["Lee, A. 2019a. F.", "———. 2019b. E."]
+        );
+    }
+
+    #[test]
+    fn numeric() {
+        let style: Style =
+            r#"<style xmlns="http://purl.org/net/xbiblio/csl" class="in-text" version="1.0"
+      page-range-format="expanded">
+  <citation>
+    <sort><key variable="citation-number"/></sort>
+    <layout prefix="[" suffix="]" delimiter=", ">
+      <text variable="citation-number"/>
+    </layout>
+  </citation>
+  <bibliography et-al-min="7" et-al-use-first="6">
+    <layout>
+      <text variable="citation-number" prefix="[" suffix="] "/>
+      <group delimiter=", " suffix=".">
+        <names variable="author"><name initialize-with=". " and="text"/></names>
+        <text variable="title" quotes="true"/>
+        <text variable="container-title" font-style="italic"/>
+        <group delimiter=" ">
+          <label variable="page" form="short"/>
+          <text variable="page"/>
+        </group>
+        <date variable="issued" form="text" date-parts="year-month"/>
+      </group>
+    </layout>
+  </bibliography>
+</style>"#
+                .parse()
+                .unwrap();
+
+        let mut first = record(
+            &[("Brown", "Max James"), ("Twyford", "Alex")],
+            "2023-03",
+            "Hosts",
+        );
+        first.journal = Some("New Phytologist".into());
+        first.page_number = Some("345-67".into());
+        let records = [first, record(&[("Lee", "Al")], "2019", "Haustoria")];
+        let mut processor = Processor::new(&style, &records);
+        processor.set_markup(Markup::Plain);
+
+        assert_eq!(processor.citation(&[1.into(), 0.into()]).unwrap(), "[1, 2]");
+        assert_eq!(
+            processor.bibliography(),
+            [
+                "[1] M. J. Brown and A. Twyford, \u{201c}Hosts,\u{201d} New Phytologist, pp. 345\u{2013}367, March 2023.",
+                "[2] A. Lee, \u{201c}Haustoria,\u{201d} 2019.",
+            ]
+        );
+        assert_eq!(
+            processor.bibliography_text()[0].render(Markup::Html),
+            "[1] M. J. Brown and A. Twyford, \u{201c}Hosts,\u{201d} <i>New Phytologist</i>, \
+             pp. 345\u{2013}367, March 2023."
+        );
+
+        // the values are kept as they are, and can't add markup of their own
+        let mut sneaky = record(&[("Lee", "Al")], "2019", "A <b>sneaky</b> \u{e000}title");
+        sneaky.journal = Some("J\u{e001}".into());
+        let records = [sneaky];
+        let mut processor = Processor::new(&style, &records);
+        processor.set_markup(Markup::Html);
+        assert_eq!(
+            processor.bibliography(),
+            [
+                "[1] A. Lee, \u{201c}A &lt;b&gt;sneaky&lt;/b&gt; \u{e000}title,\u{201d} \
+              <i>J\u{e001}</i>, 2019."
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let bad = [
+            "<style",
+            "<citation/>",
+            r#"<style><bibliography><layout/></bibliography></style>"#,
+            r#"<style><citation><layout><text macro="missing"/></layout></citation></style>"#,
+            r#"<style><citation><layout><blink/></layout></citation></style>"#,
+        ];
+        for style in bad {
+            let err = style.parse::<Style>().unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::Csl(_)), "{}", style);
+        }
+    }
+
+    #[test]
+    fn initials() {
+        assert_eq!(initialize_given("Max James", ". ", true), "M. J.");
+        assert_eq!(initialize_given("Jo-Anne", ".", true), "J.-A.");
+        assert_eq!(initialize_given("Jo-Anne", "", false), "JA");
+    }
+}
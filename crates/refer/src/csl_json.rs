@@ -0,0 +1,520 @@
+/*!
+Conversion between refer [`Record`]s and CSL-JSON, the format read by
+Pandoc and citeproc processors.
+
+| refer | CSL-JSON                          |
+|-------|-----------------------------------|
+| %A    | author (or a literal for %Q)      |
+| %B    | container-title (for chapters)    |
+| %C    | publisher-place                   |
+| %D    | issued                            |
+| %E    | editor                            |
+| %I    | publisher                         |
+| %J    | container-title                   |
+| %K    | keyword                           |
+| %L    | id                                |
+| %N    | issue                             |
+| %O    | DOI (as `doi:...`), URL or note   |
+| %P    | page                              |
+| %R    | number                            |
+| %S    | collection-title                  |
+| %T    | title                             |
+| %V    | volume                            |
+| %X    | abstract                          |
+
+# Example
+
+```
+use refer::{csl_json, Author, Record};
+
+let record = Record {
+    author: vec![Author { last: "Brown".into(), rest: "M.".into(), ..Default::default() }],
+    title: Some("A title".into()),
+    journal: Some("PNAS".into()),
+    date: Some("March 2023".into()),
+    ..Default::default()
+};
+
+let json = csl_json::to_string(&[record.clone()]).unwrap();
+assert!(json.contains(r#""issued":{"date-parts":[[2023,3]]}"#));
+
+let parsed = csl_json::from_str(&json).unwrap();
+assert_eq!(parsed[0].date, record.date);
+```
+*/
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    date::{Date, DatePart},
+    error::{Error, ErrorKind, Result},
+    record::{Author, Record, RecordType},
+};
+
+/// Parse a CSL-JSON string, either an array of items or a single item,
+/// into records.
+pub fn from_str(input: &str) -> Result<Vec<Record>> {
+    let value: Value =
+        serde_json::from_str(input).map_err(|e| Error::new(ErrorKind::CslJson(e.to_string())))?;
+    match value {
+        Value::Array(items) => items.iter().map(record_from_value).collect(),
+        item @ Value::Object(_) => Ok(vec![record_from_value(&item)?]),
+        _ => Err(csl_error("expected an array of items or a single item")),
+    }
+}
+
+/// Write records as a CSL-JSON array.
+pub fn to_string(records: &[Record]) -> Result<String> {
+    let items: Vec<Value> = records.iter().map(record_to_value).collect();
+    serde_json::to_string(&items).map_err(|e| Error::new(ErrorKind::CslJson(e.to_string())))
+}
+
+/// Convert a record into a CSL-JSON item.
+pub fn record_to_value(record: &Record) -> Value {
+    let mut item = Map::new();
+    let typ = csl_type(record);
+
+    item.insert("id".into(), json!(item_id(record)));
+    item.insert("type".into(), json!(typ));
+
+    if !record.author.is_empty() {
+        let authors = record.author.iter().map(author_to_value).collect();
+        item.insert("author".into(), Value::Array(authors));
+    } else if let Some(q) = &record.author_np {
+        item.insert("author".into(), json!([{ "literal": q }]));
+    }
+    if !record.editor.is_empty() {
+        let editors = record
+            .editor
+            .iter()
+            .map(|e| author_to_value(&editor_to_author(e)))
+            .collect();
+        item.insert("editor".into(), Value::Array(editors));
+    }
+
+    let mut insert = |key: &str, value: &Option<String>| {
+        if let Some(v) = value {
+            item.insert(key.into(), json!(v));
+        }
+    };
+    match typ {
+        "book" => insert("title", &record.title.clone().or(record.book.clone())),
+        _ => {
+            insert("title", &record.title);
+            insert(
+                "container-title",
+                &record.journal.clone().or(record.book.clone()),
+            );
+        }
+    }
+    insert("collection-title", &record.series);
+    insert("volume", &record.volume);
+    insert("issue", &record.issue_number);
+    insert("page", &record.page_number);
+    insert("publisher", &record.issuer);
+    insert("publisher-place", &record.place);
+    insert("number", &record.report);
+    insert("abstract", &record.annotation);
+    insert("keyword", &record.keywords.as_ref().map(|k| k.join(", ")));
+    if let Some(o) = &record.other {
+        let o = o.trim();
+        if let Some(doi) = o.strip_prefix("doi:") {
+            insert("DOI", &Some(doi.trim().to_string()));
+        } else if o.starts_with("http://") || o.starts_with("https://") {
+            insert("URL", &Some(o.to_string()));
+        } else {
+            insert("note", &Some(o.to_string()));
+        }
+    }
+    if let Some(d) = &record.date {
+        item.insert("issued".into(), date_to_value(d));
+    }
+
+    Value::Object(item)
+}
+
+/// Convert a CSL-JSON item into a record.
+pub fn record_from_value(value: &Value) -> Result<Record> {
+    let item = value
+        .as_object()
+        .ok_or_else(|| csl_error("expected an item to be an object"))?;
+    let typ = item.get("type").and_then(Value::as_str).unwrap_or_default();
+    let string = |key: &str| item.get(key).and_then(string_or_number);
+
+    let mut record = Record {
+        label: string("id"),
+        title: string("title"),
+        series: string("collection-title"),
+        volume: string("volume"),
+        issue_number: string("issue"),
+        page_number: string("page"),
+        issuer: string("publisher"),
+        place: string("publisher-place"),
+        report: string("number"),
+        annotation: string("abstract"),
+        rec_type: match typ {
+            "article-journal" | "article-magazine" | "article-newspaper" => RecordType::Journal,
+            "book" => RecordType::Book,
+            "chapter" => RecordType::ArticleInBook,
+            "report" => RecordType::Report,
+            "thesis" => RecordType::Thesis,
+            "paper-conference" => RecordType::Conference,
+            "document" | "manuscript" => RecordType::Misc,
+            _ => RecordType::None,
+        },
+        ..Default::default()
+    };
+
+    match typ {
+        "article-journal" | "article-magazine" | "article-newspaper" | "article" => {
+            record.journal = string("container-title")
+        }
+        _ => record.book = string("container-title"),
+    }
+
+    if let Some(authors) = item.get("author") {
+        for name in names(authors)? {
+            match name_literal(name) {
+                Some(l) if record.author_np.is_none() => record.author_np = Some(l),
+                _ => record.author.push(author_from_value(name)?),
+            }
+        }
+    }
+    if let Some(editors) = item.get("editor") {
+        for name in names(editors)? {
+            let editor = match name_literal(name) {
+                Some(l) => l,
+                None => author_from_value(name)?.full_name(),
+            };
+            record.editor.push(editor);
+        }
+    }
+
+    record.keywords = string("keyword").map(|k| {
+        k.split([',', ' '])
+            .filter(|k| !k.is_empty())
+            .map(String::from)
+            .collect()
+    });
+    record.other = string("DOI")
+        .map(|d| format!("doi:{}", d))
+        .or_else(|| string("URL"))
+        .or_else(|| string("note"));
+    if let Some(issued) = item.get("issued") {
+        record.date = date_from_value(issued)?;
+    }
+
+    Ok(record)
+}
+
+/// Convert an author into a CSL-JSON name object. Corporate names are
+/// written as a `literal`.
+pub fn author_to_value(author: &Author) -> Value {
+    let mut name = Map::new();
+    if author.corporate {
+        name.insert("literal".into(), json!(author.last));
+        return Value::Object(name);
+    }
+    name.insert("family".into(), json!(author.last));
+    if !author.rest.is_empty() {
+        name.insert("given".into(), json!(author.rest));
+    }
+    if let Some(p) = &author.particle {
+        name.insert("non-dropping-particle".into(), json!(p));
+    }
+    if let Some(s) = &author.suffix {
+        name.insert("suffix".into(), json!(s));
+    }
+    Value::Object(name)
+}
+
+/// Convert a CSL-JSON name object into an author. A `literal` name is
+/// kept whole as a corporate name.
+pub fn author_from_value(value: &Value) -> Result<Author> {
+    if let Some(l) = name_literal(value) {
+        return Ok(Author {
+            last: l,
+            corporate: true,
+            ..Default::default()
+        });
+    }
+    let part = |key: &str| value.get(key).and_then(Value::as_str).map(str::trim);
+    let family = part("family").ok_or_else(|| csl_error("a name has no family part"))?;
+
+    // refer has a single particle, which goes before the family name
+    let particle = [part("dropping-particle"), part("non-dropping-particle")]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Ok(Author {
+        last: family.to_string(),
+        rest: part("given").unwrap_or_default().to_string(),
+        particle: (!particle.is_empty()).then_some(particle),
+        suffix: part("suffix").map(String::from),
+        corporate: false,
+    })
+}
+
+fn names(value: &Value) -> Result<&Vec<Value>> {
+    value
+        .as_array()
+        .ok_or_else(|| csl_error("expected a list of names"))
+}
+
+fn name_literal(value: &Value) -> Option<String> {
+    value
+        .get("literal")
+        .and_then(Value::as_str)
+        .map(String::from)
+}
+
+/// Editors are free strings in refer, usually `Last, First`.
+fn editor_to_author(editor: &str) -> Author {
+    editor.parse().unwrap_or_else(|_| Author {
+        last: editor.trim().into(),
+        ..Default::default()
+    })
+}
+
+/// CSL allows numbers in a number of string fields.
+fn string_or_number(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Turn a refer date into `date-parts`, with both ends of a range,
+/// `season` for a season and `circa` for an approximate date. CSL has no
+/// place for the letter after a year, as processors add their own.
+/// Anything else, including `n.d.` and `in press`, is kept as a literal.
+fn date_to_value(date: &str) -> Value {
+    match date.parse() {
+        Ok(Date::Dated {
+            start, end, circa, ..
+        }) => {
+            let parts: Vec<Value> = std::iter::once(start)
+                .chain(end)
+                .map(|p| {
+                    let mut part = vec![json!(p.year)];
+                    part.extend(p.month.map(|m| json!(m)));
+                    part.extend(p.day.map(|d| json!(d)));
+                    Value::Array(part)
+                })
+                .collect();
+            let mut value = json!({ "date-parts": parts });
+            if let Some(s) = start.season {
+                value["season"] = json!(s);
+            }
+            if circa {
+                value["circa"] = json!(true);
+            }
+            value
+        }
+        _ => json!({ "literal": date }),
+    }
+}
+
+/// The reverse of [`date_to_value`].
+fn date_from_value(value: &Value) -> Result<Option<String>> {
+    if let Some(l) = value
+        .get("literal")
+        .or_else(|| value.get("raw"))
+        .and_then(Value::as_str)
+    {
+        return Ok(Some(l.to_string()));
+    }
+    let parts = match value.get("date-parts").and_then(Value::as_array) {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    let mut parts = parts
+        .iter()
+        .filter_map(Value::as_array)
+        .filter(|p| !p.is_empty())
+        .map(|p| date_part(p));
+    let mut start = match parts.next() {
+        Some(p) => p?,
+        None => return Ok(None),
+    };
+    if start.month.is_none() {
+        start.season = value
+            .get("season")
+            .and_then(string_or_number)
+            .and_then(|s| s.parse().ok())
+            .filter(|s| (1..=4).contains(s));
+    }
+    let date = Date::Dated {
+        start,
+        end: parts.next().transpose()?,
+        circa: value
+            .get("circa")
+            .is_some_and(|c| c.as_bool().unwrap_or(true)),
+        suffix: None,
+    };
+    Ok(Some(date.to_string()))
+}
+
+/// A year, month and day from `date-parts`.
+fn date_part(parts: &[Value]) -> Result<DatePart> {
+    let parts: Vec<i64> = parts
+        .iter()
+        .map(|p| {
+            string_or_number(p)
+                .and_then(|p| p.parse().ok())
+                .ok_or_else(|| csl_error("date-parts must be numbers"))
+        })
+        .collect::<Result<_>>()?;
+    let month = |m: i64| match m {
+        1..=12 => Ok(m as u8),
+        _ => Err(csl_error("a month in date-parts is not between 1 and 12")),
+    };
+    let day = |d: i64| match d {
+        1..=31 => Ok(d as u8),
+        _ => Err(csl_error("a day in date-parts is not between 1 and 31")),
+    };
+    let year =
+        |y: i64| i32::try_from(y).map_err(|_| csl_error("a year in date-parts is too large"));
+    Ok(match parts[..] {
+        [y] => DatePart {
+            year: year(y)?,
+            month: None,
+            day: None,
+            season: None,
+        },
+        [y, m] => DatePart {
+            year: year(y)?,
+            month: Some(month(m)?),
+            day: None,
+            season: None,
+        },
+        [y, m, d, ..] => DatePart {
+            year: year(y)?,
+            month: Some(month(m)?),
+            day: Some(day(d)?),
+            season: None,
+        },
+        [] => unreachable!("empty date-parts are skipped"),
+    })
+}
+
+/// The CSL type for a record.
+fn csl_type(record: &Record) -> &'static str {
+    match record.record_type() {
+        RecordType::Journal => "article-journal",
+        RecordType::Book => "book",
+        RecordType::ArticleInBook => "chapter",
+        RecordType::Report | RecordType::Government => "report",
+        RecordType::Thesis => "thesis",
+        RecordType::Conference => "paper-conference",
+        RecordType::Misc | RecordType::None => "document",
+    }
+}
+
+fn item_id(record: &Record) -> String {
+    if let Some(l) = &record.label {
+        return l.clone();
+    }
+    let name = record
+        .author
+        .first()
+        .map(|a| a.last.as_str())
+        .or(record.author_np.as_deref())
+        .unwrap_or("anon");
+    let year = record
+        .date
+        .as_deref()
+        .and_then(|d| d.split_whitespace().find(|w| w.len() == 4));
+    format!("{}{}", name, year.unwrap_or_default())
+        .replace(char::is_whitespace, "")
+        .to_lowercase()
+}
+
+fn csl_error(message: &str) -> Error {
+    Error::new(ErrorKind::CslJson(message.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        let json = r#"[{
+            "id": "smith",
+            "type": "chapter",
+            "author": [
+                {"family": "Berg", "given": "P.", "non-dropping-particle": "van der"},
+                {"literal": "World Health Organization"}
+            ],
+            "editor": [{"family": "Jones", "given": "K."}],
+            "title": "A chapter",
+            "container-title": "The book",
+            "page": "45-67",
+            "volume": 2,
+            "DOI": "10.1000/xyz",
+            "issued": {"date-parts": [["2020", 3, 1]]}
+        }]"#;
+        let record = from_str(json).unwrap().remove(0);
+
+        assert_eq!(record.label.as_deref(), Some("smith"));
+        assert_eq!(record.author[0].family_name(), "van der Berg");
+        assert_eq!(
+            record.author_np.as_deref(),
+            Some("World Health Organization")
+        );
+        assert_eq!(record.editor, vec!["Jones, K.".to_string()]);
+        assert_eq!(record.book.as_deref(), Some("The book"));
+        assert_eq!(record.volume.as_deref(), Some("2"));
+        assert_eq!(record.other.as_deref(), Some("doi:10.1000/xyz"));
+        assert_eq!(record.date.as_deref(), Some("1 March 2020"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let record = Record {
+            author: vec![Author {
+                last: "Brown".into(),
+                rest: "M.".into(),
+                ..Default::default()
+            }],
+            editor: vec!["Twyford, A. D.".into(), "van der Berg, P., Jr.".into()],
+            title: Some("A title".into()),
+            journal: Some("PNAS".into()),
+            date: Some("in press".into()),
+            keywords: Some(vec!["plants".into(), "parasites".into()]),
+            other: Some("https://example.com".into()),
+            ..Default::default()
+        };
+        let value = record_to_value(&record);
+        assert_eq!(value["issued"], json!({ "literal": "in press" }));
+        assert_eq!(value["type"], json!("article-journal"));
+
+        let parsed = record_from_value(&value).unwrap();
+        assert_eq!(
+            parsed,
+            Record {
+                label: Some("brown".into()),
+                rec_type: RecordType::Journal,
+                ..record
+            }
+        );
+    }
+
+    #[test]
+    fn test_seasons() {
+        let value = date_to_value("Spring 2020");
+        assert_eq!(value, json!({ "date-parts": [[2020]], "season": 1 }));
+        assert_eq!(date_from_value(&value).unwrap().unwrap(), "Spring 2020");
+        assert_eq!(date_to_value("1999a"), json!({ "date-parts": [[1999]] }));
+    }
+
+    #[test]
+    fn test_bad_month() {
+        assert!(from_str(r#"{"issued": {"date-parts": [[2020, 13]]}}"#).is_err());
+        assert!(from_str(r#"{"issued": {"date-parts": [[2020, 0]]}}"#).is_err());
+    }
+}
@@ -0,0 +1,622 @@
+use std::{fmt::Display, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, ErrorKind, Result};
+
+/// The months of the year, as written in dates.
+pub(crate) const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// The seasons, as written in dates, e.g. `Spring 2020`.
+pub(crate) const SEASONS: [&str; 4] = ["Spring", "Summer", "Autumn", "Winter"];
+
+/// A date parsed from the `%D` field of a record.
+///
+/// Dates order chronologically. Records without a date ("n.d.") come
+/// before all dated records, and forthcoming and in press works after
+/// them, which is the order used in reference lists.
+///
+/// # Example
+///
+/// ```
+/// use refer::Date;
+///
+/// let date: Date = "c. 1850".parse().unwrap();
+/// assert_eq!(date.year(), Some(1850));
+/// assert!(date.is_circa());
+///
+/// let range: Date = "May-June 2020".parse().unwrap();
+/// assert_eq!(range.to_string(), "May-June 2020");
+///
+/// // a year can have a letter after it, to tell apart works by the same
+/// // authors, and a season in place of a month
+/// assert_eq!("1999a".parse::<Date>().unwrap().suffix(), Some('a'));
+/// assert_eq!("Spring 2020".parse::<Date>().unwrap().to_string(), "Spring 2020");
+///
+/// assert!("n.d.".parse::<Date>().unwrap() < range);
+/// assert!(range < "in press".parse().unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Date {
+    /// No date, written as `n.d.`.
+    NoDate,
+    /// A date, or a range of dates.
+    Dated {
+        /// The date, or the start of the range.
+        start: DatePart,
+        /// The end of a range, e.g. `2019-2020`.
+        end: Option<DatePart>,
+        /// An approximate date, e.g. `c. 1850`.
+        circa: bool,
+        /// A letter after the year, e.g. the `a` of `1999a`.
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Option::is_none")
+        )]
+        suffix: Option<char>,
+    },
+    /// Accepted for publication, written as `in press`.
+    InPress,
+    /// Not yet published, written as `forthcoming`.
+    Forthcoming,
+}
+
+/// A year, with an optional month and day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DatePart {
+    /// The year.
+    pub year: i32,
+    /// The month, from 1 to 12.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub month: Option<u8>,
+    /// The day of the month, which is only set along with the month.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub day: Option<u8>,
+    /// The season, from 1 (spring) to 4 (winter), which is only set
+    /// without a month.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub season: Option<u8>,
+}
+
+impl Date {
+    /// A date in a single year.
+    pub fn year_only(year: i32) -> Self {
+        Date::Dated {
+            start: DatePart {
+                year,
+                month: None,
+                day: None,
+                season: None,
+            },
+            end: None,
+            circa: false,
+            suffix: None,
+        }
+    }
+
+    /// The year of the date, or the first year of a range.
+    pub fn year(&self) -> Option<i32> {
+        self.start().map(|s| s.year)
+    }
+
+    /// The date, or the start of the range.
+    pub fn start(&self) -> Option<&DatePart> {
+        match self {
+            Date::Dated { start, .. } => Some(start),
+            _ => None,
+        }
+    }
+
+    /// The end of the range, if the date is a range.
+    pub fn end(&self) -> Option<&DatePart> {
+        match self {
+            Date::Dated { end, .. } => end.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Whether the date is approximate.
+    pub fn is_circa(&self) -> bool {
+        matches!(self, Date::Dated { circa: true, .. })
+    }
+
+    /// The letter after the year, e.g. `a` for `1999a`.
+    pub fn suffix(&self) -> Option<char> {
+        match self {
+            Date::Dated { suffix, .. } => *suffix,
+            _ => None,
+        }
+    }
+
+    /// Only the years of the date, e.g. `2019-2020`, `1999a` or `c. 1850`, with
+    /// `n.d.`, `in press` and `forthcoming` as they are. `circa` is put
+    /// before an approximate date, and `dash` goes between the years of a
+    /// range.
+    pub fn years(&self, circa: &str, dash: &str) -> String {
+        match self {
+            Date::Dated {
+                start,
+                end,
+                circa: is_circa,
+                suffix,
+            } => {
+                let mut s = String::new();
+                if *is_circa {
+                    s.push_str(circa);
+                }
+                s.push_str(&start.year.to_string());
+                if let Some(e) = end.filter(|e| e.year != start.year) {
+                    s.push_str(dash);
+                    s.push_str(&e.year.to_string());
+                }
+                s.extend(suffix);
+                s
+            }
+            other => other.to_string(),
+        }
+    }
+}
+
+impl DatePart {
+    /// The name of the month, e.g. `March`.
+    pub fn month_name(&self) -> Option<&'static str> {
+        self.month.map(|m| MONTHS[m as usize - 1])
+    }
+
+    /// The name of the season, e.g. `Spring`.
+    pub fn season_name(&self) -> Option<&'static str> {
+        self.season.map(|s| SEASONS[s as usize - 1])
+    }
+}
+
+impl Display for DatePart {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(d) = self.day {
+            write!(f, "{} ", d)?;
+        }
+        if let Some(m) = self.month_name().or(self.season_name()) {
+            write!(f, "{} ", m)?;
+        }
+        write!(f, "{}", self.year)
+    }
+}
+
+/// Written as refer would, e.g. `1 March 2020`, `c. 1850`, `1999a` or
+/// `2019-2020`.
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Date::NoDate => write!(f, "n.d."),
+            Date::InPress => write!(f, "in press"),
+            Date::Forthcoming => write!(f, "forthcoming"),
+            Date::Dated {
+                start,
+                end,
+                circa,
+                suffix,
+            } => {
+                if *circa {
+                    write!(f, "c. ")?;
+                }
+                match end {
+                    // the year is only written once, e.g. `May-June 2020`
+                    Some(e) if e.year == start.year && start.month.is_some() => {
+                        if let Some(d) = start.day {
+                            write!(f, "{} ", d)?;
+                        }
+                        write!(f, "{}-{}", start.month_name().unwrap_or_default(), e)?
+                    }
+                    Some(e) => write!(f, "{}-{}", start, e)?,
+                    None => write!(f, "{}", start)?,
+                }
+                match suffix {
+                    Some(c) => write!(f, "{}", c),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Date {
+    type Err = Error;
+
+    /// Parse a date. Years can be given with a month and day in most
+    /// orders (`1 March 2020`, `March 1, 2020`, `Mar. 2020`, or ISO
+    /// `2020-03-01`), or with a season (`Spring 2020`). Ranges are
+    /// separated by a hyphen, an en dash or a slash (`2019-20`, `May-June
+    /// 2020`). `c.`, `ca.` or `circa` before a date makes it approximate,
+    /// and a lower case letter after it tells apart works of the same
+    /// year (`1999a`). `n.d.`, `in press` and `forthcoming` are also
+    /// understood.
+    fn from_str(s: &str) -> Result<Self> {
+        let date_error =
+            |message: &str| Error::new(ErrorKind::Date(format!("{}: {:?}", message, s)));
+        let input = s.trim().trim_end_matches(['.', ',']).trim();
+        match input.to_lowercase().as_str() {
+            "" => return Err(date_error("Empty date")),
+            "n.d" | "nd" | "no date" | "undated" => return Ok(Date::NoDate),
+            "in press" | "in the press" => return Ok(Date::InPress),
+            "forthcoming" => return Ok(Date::Forthcoming),
+            _ => (),
+        }
+
+        let (circa, input) = match strip_circa(input) {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+        let (input, suffix) = strip_suffix(input);
+        if let Some(iso) = iso_date(input) {
+            return Ok(Date::Dated {
+                start: iso,
+                end: None,
+                circa,
+                suffix,
+            });
+        }
+
+        let (start, end) = match input.split_once(['-', '\u{2013}', '/']) {
+            Some((s, e)) => (s.trim(), Some(e.trim())),
+            None => (input, None),
+        };
+        let mut start = parse_part(start).ok_or_else(|| date_error("Could not parse the date"))?;
+        let mut end = match end {
+            Some(e) => {
+                Some(parse_part(e).ok_or_else(|| date_error("Could not parse the date range"))?)
+            }
+            None => None,
+        };
+
+        if let Some(end) = &mut end {
+            if end.year.is_none() {
+                return Err(date_error("The end of a date range must have a year"));
+            }
+            // a range of days, e.g. `3-5 June 2021` or `June 3-5, 2021`
+            if start.month.is_none()
+                && start.day.is_none()
+                && start.season.is_none()
+                && start.year_digits <= Some(2)
+            {
+                start.day = start.year.and_then(|d| u8::try_from(d).ok());
+                (start.year, start.year_digits) = (None, None);
+                start.month = end.month;
+            }
+            if end.month.is_none() && end.day.is_some() {
+                end.month = start.month;
+            }
+            // the year is shared, e.g. `May-June 2020`
+            if start.year.is_none() {
+                start.year = end.year;
+            }
+        }
+        let start = start
+            .date_part()
+            .ok_or_else(|| date_error("Could not parse the date"))?;
+        let end = match end {
+            Some(mut e) => {
+                // an abbreviated year, e.g. `2019-20`
+                if let (Some(y), Some(n @ 1..=3)) = (e.year, e.year_digits) {
+                    let scale = 10i32.pow(n);
+                    let year = start.year - start.year.rem_euclid(scale) + y;
+                    e.year = Some(if year < start.year {
+                        year + scale
+                    } else {
+                        year
+                    });
+                }
+                Some(
+                    e.date_part()
+                        .ok_or_else(|| date_error("Could not parse the date range"))?,
+                )
+            }
+            None => None,
+        };
+
+        if end.is_some_and(|e| e < start) {
+            return Err(date_error("The date range ends before it starts"));
+        }
+        Ok(Date::Dated {
+            start,
+            end,
+            circa,
+            suffix,
+        })
+    }
+}
+
+/// One side of a date range, which may be missing parts until the
+/// sides are put together.
+#[derive(Clone, Copy)]
+struct Part {
+    year: Option<i32>,
+    /// The number of digits the year was written with.
+    year_digits: Option<u32>,
+    month: Option<u8>,
+    day: Option<u8>,
+    season: Option<u8>,
+}
+
+impl Part {
+    /// Check the part is a valid date.
+    fn date_part(self) -> Option<DatePart> {
+        match (self.month, self.day) {
+            (None, Some(_)) => return None,
+            (Some(_), _) if self.season.is_some() => return None,
+            (Some(m), d) if !valid_month_day(m, d) => return None,
+            _ => (),
+        }
+        Some(DatePart {
+            year: self.year?,
+            month: self.month,
+            day: self.day,
+            season: self.season,
+        })
+    }
+}
+
+fn strip_circa(input: &str) -> Option<&str> {
+    let lower = input.to_lowercase();
+    ["circa ", "ca. ", "ca ", "c. ", "c.", "c "]
+        .iter()
+        .find(|p| lower.starts_with(*p))
+        .map(|p| input[p.len()..].trim_start())
+}
+
+/// Split a lower case letter off the end of a year, e.g. `1999a`.
+fn strip_suffix(input: &str) -> (&str, Option<char>) {
+    let mut chars = input.chars().rev();
+    match (chars.next(), chars.next()) {
+        (Some(c @ 'a'..='z'), Some(d)) if d.is_ascii_digit() => {
+            (&input[..input.len() - 1], Some(c))
+        }
+        _ => (input, None),
+    }
+}
+
+/// An ISO 8601 date, `2020-03` or `2020-03-01`.
+fn iso_date(input: &str) -> Option<DatePart> {
+    let mut parts = input.split('-');
+    let year = parts.next().filter(|y| y.len() == 4)?.parse().ok()?;
+    let month = parts.next().filter(|m| m.len() == 2)?.parse().ok()?;
+    let day = match parts.next() {
+        Some(d) if d.len() == 2 => Some(d.parse().ok()?),
+        Some(_) => return None,
+        None => None,
+    };
+    if parts.next().is_some() || !valid_month_day(month, day) {
+        return None;
+    }
+    Some(DatePart {
+        year,
+        month: Some(month),
+        day,
+        season: None,
+    })
+}
+
+/// One side of a date, e.g. `1 March 2020`, `March 1, 2020`, `June` or
+/// `Spring 2020`.
+fn parse_part(input: &str) -> Option<Part> {
+    let mut part = Part {
+        year: None,
+        year_digits: None,
+        month: None,
+        day: None,
+        season: None,
+    };
+    let mut numbers = Vec::new();
+    for word in input.split([' ', ',']).filter(|w| !w.is_empty()) {
+        let word = word.trim_end_matches('.');
+        if !word.is_empty() && word.chars().all(|c| c.is_ascii_digit()) {
+            numbers.push(word);
+            continue;
+        }
+        let lower = word.to_lowercase();
+        let season = SEASONS
+            .iter()
+            .position(|s| s.to_lowercase() == lower)
+            // as written in American English
+            .or_else(|| (lower == "fall").then_some(2));
+        if let Some(season) = season {
+            if part.season.replace(season as u8 + 1).is_some() {
+                return None;
+            }
+            continue;
+        }
+        let month = MONTHS
+            .iter()
+            .position(|m| lower.len() >= 3 && m.to_lowercase().starts_with(&lower))
+            // `Sept.` is common
+            .or_else(|| (lower == "sept").then_some(8))?;
+        if part.month.replace(month as u8 + 1).is_some() {
+            return None;
+        }
+    }
+
+    let mut set_year = |y: &str| {
+        part.year = y.parse().ok();
+        part.year_digits = Some(y.len() as u32);
+        part.year.is_some()
+    };
+    match numbers[..] {
+        [] => (),
+        [y] if y.len() > 2 || part.month.is_none() => {
+            set_year(y).then_some(())?;
+        }
+        // the day of a month, where the year is after the range
+        [d] => part.day = Some(d.parse().ok()?),
+        [a, b] => {
+            let (d, y) = if a.len() <= 2 { (a, b) } else { (b, a) };
+            set_year(y).then_some(())?;
+            part.day = Some(d.parse().ok()?);
+        }
+        _ => return None,
+    }
+    Some(part)
+}
+
+fn valid_month_day(month: u8, day: Option<u8>) -> bool {
+    let days = match month {
+        2 => 29,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return false,
+    };
+    day.is_none_or(|d| (1..=days).contains(&d))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(year: i32, month: Option<u8>, day: Option<u8>) -> DatePart {
+        DatePart {
+            year,
+            month,
+            day,
+            season: None,
+        }
+    }
+
+    #[test]
+    fn parse_dates() {
+        let parse = |s: &str| s.parse::<Date>().unwrap();
+
+        assert_eq!(parse("2020"), Date::year_only(2020));
+        for s in ["1 March 2020", "March 1, 2020", "Mar. 1 2020", "2020-03-01"] {
+            assert_eq!(
+                parse(s).start(),
+                Some(&part(2020, Some(3), Some(1))),
+                "{}",
+                s
+            );
+        }
+        assert_eq!(
+            parse("Sept. 1999").start(),
+            Some(&part(1999, Some(9), None))
+        );
+        assert_eq!(parse("n.d."), Date::NoDate);
+        assert_eq!(parse("In press"), Date::InPress);
+        assert_eq!(parse("forthcoming"), Date::Forthcoming);
+
+        let circa = parse("ca. 1850");
+        assert!(circa.is_circa());
+        assert_eq!(circa.to_string(), "c. 1850");
+
+        let range = parse("2019-20");
+        assert_eq!(range.end(), Some(&part(2020, None, None)));
+        assert_eq!(range.years("c. ", "\u{2013}"), "2019\u{2013}2020");
+        assert_eq!(
+            parse("1999\u{2013}2001").end(),
+            Some(&part(2001, None, None))
+        );
+
+        let days = parse("3-5 June 2021");
+        assert_eq!(days.start(), Some(&part(2021, Some(6), Some(3))));
+        assert_eq!(days.end(), Some(&part(2021, Some(6), Some(5))));
+        assert_eq!(days, parse("June 3-5, 2021"));
+        assert_eq!(days.to_string(), "3 June-5 June 2021");
+        assert_eq!(parse(&days.to_string()), days);
+    }
+
+    #[test]
+    fn parse_suffixes() {
+        let parse = |s: &str| s.parse::<Date>().unwrap();
+
+        let date = parse("1999a");
+        assert_eq!(date.year(), Some(1999));
+        assert_eq!(date.suffix(), Some('a'));
+        assert_eq!(date.to_string(), "1999a");
+        assert_eq!(date.years("c. ", "-"), "1999a");
+        assert!(parse("1999") < date && date < parse("1999b"));
+
+        assert_eq!(parse("c. 1850b").to_string(), "c. 1850b");
+        assert_eq!(parse("2019-20c").years("", "-"), "2019-2020c");
+        assert_eq!(parse("March 2020a").to_string(), "March 2020a");
+        assert_eq!(parse("2020-03-01a").suffix(), Some('a'));
+        assert_eq!(parse("2020").suffix(), None);
+
+        for s in ["1999A", "1999ab", "a"] {
+            assert!(s.parse::<Date>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn parse_seasons() {
+        let parse = |s: &str| s.parse::<Date>().unwrap();
+
+        let spring = parse("Spring 2020");
+        assert_eq!(spring.start().unwrap().season, Some(1));
+        assert_eq!(spring.start().unwrap().season_name(), Some("Spring"));
+        assert_eq!(spring.start().unwrap().month, None);
+        assert_eq!(spring.to_string(), "Spring 2020");
+        assert_eq!(spring.years("", "-"), "2020");
+
+        assert_eq!(parse("fall 2019").to_string(), "Autumn 2019");
+        assert_eq!(parse("Winter, 1999b").to_string(), "Winter 1999b");
+
+        let range = parse("Spring-Summer 2020");
+        assert_eq!(range.start().unwrap().year, 2020);
+        assert_eq!(range.end().unwrap().season, Some(2));
+        assert_eq!(parse(&range.to_string()), range);
+
+        for s in ["Spring", "Spring March 2020", "Spring Summer 2020"] {
+            assert!(s.parse::<Date>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        for s in [
+            "",
+            "sometime",
+            "32 March 2020",
+            "2020-1999",
+            "March",
+            "2020 2021",
+        ] {
+            let err = s.parse::<Date>().unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::Date(_)), "{}", s);
+        }
+    }
+
+    #[test]
+    fn order() {
+        let mut dates: Vec<Date> = ["in press", "2020", "n.d.", "March 2019", "2019", "c. 1850"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        dates.sort();
+        let sorted: Vec<String> = dates.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            sorted,
+            ["n.d.", "c. 1850", "2019", "March 2019", "2020", "in press"]
+        );
+    }
+}
@@ -1,4 +1,9 @@
-use std::{error::Error as StdError, fmt, io, result, str};
+use std::{
+    error::Error as StdError,
+    fmt, io,
+    path::{Path, PathBuf},
+    result, str,
+};
 
 // I'm aware the error handling is slightly mad
 // first time I have done a lib like this.
@@ -7,23 +12,188 @@ use std::{error::Error as StdError, fmt, io, result, str};
 pub type Result<T> = result::Result<T, Error>;
 
 /// An error that can happen when processing refer data.
+///
+/// Errors from parsing a field carry the [`Position`] of the problem,
+/// and [`Error::render`] shows it as a diagnostic with the offending
+/// line of the input.
 #[derive(Debug)]
-pub struct Error(Box<ErrorKind>);
+pub struct Error {
+    kind: Box<ErrorKind>,
+    position: Option<Box<Position>>,
+}
 
 impl Error {
     /// A crate private constructor for `Error`.
     pub(crate) fn new(kind: ErrorKind) -> Error {
-        Error(Box::new(kind))
+        Error {
+            kind: Box::new(kind),
+            position: None,
+        }
     }
 
     /// Return the specific type of this error.
     pub fn kind(&self) -> &ErrorKind {
-        &self.0
+        &self.kind
     }
 
     /// Unwrap this error into its underlying type.
     pub fn into_kind(self) -> ErrorKind {
-        *self.0
+        *self.kind
+    }
+
+    /// Where in the input the error happened, if it came from parsing
+    /// a field.
+    pub fn position(&self) -> Option<&Position> {
+        self.position.as_deref()
+    }
+
+    pub(crate) fn with_position(mut self, position: Position) -> Error {
+        self.position = Some(Box::new(position));
+        self
+    }
+
+    /// Set the path of the file the error is in.
+    pub(crate) fn with_path(mut self, path: Option<&Path>) -> Error {
+        if let Some(p) = self.position.as_mut() {
+            p.path = path.map(Path::to_path_buf);
+        }
+        self
+    }
+
+    /// Move the position on by `byte`, the offset of the field in the
+    /// input.
+    pub(crate) fn offset_by(mut self, byte: u64) -> Error {
+        if let Some(p) = self.position.as_mut() {
+            p.byte += byte;
+        }
+        self
+    }
+
+    /// Render the error as a diagnostic, in the style of rustc, with a
+    /// caret under the problem.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use refer::Reader;
+    ///
+    /// let data = "%T A title\n\n%T Another\n%A Brown,\n";
+    /// let err = Reader::new(data.as_bytes()).records().nth(1).unwrap().unwrap_err();
+    /// assert_eq!(
+    ///     err.render(),
+    ///     "error[%A]: Author field incorrectly specified: Input error: Brown,. \
+    ///      Names separated by a comma can't be empty
+    ///  --> line 4, column 4
+    ///   |
+    /// 4 | %A Brown,
+    ///   |    ^^^^^^
+    /// "
+    /// );
+    /// ```
+    pub fn render(&self) -> String {
+        let position = match self.position() {
+            Some(p) => p,
+            None => return format!("error: {}\n", self.kind),
+        };
+        let mut out = match position.tag {
+            Some(t) => format!("error[%{}]: {}\n", t, self.kind),
+            None => format!("error: {}\n", self.kind),
+        };
+        let line = position.line.to_string();
+        let pad = " ".repeat(line.len());
+        out.push_str(&format!("{}--> {}\n", pad, position));
+
+        if let Some(source) = &position.source {
+            let before = (position.column as usize).saturating_sub(1);
+            let width = source.chars().count().saturating_sub(before).max(1);
+            out.push_str(&format!("{} |\n", pad));
+            out.push_str(&format!("{} | {}\n", line, source));
+            out.push_str(&format!(
+                "{} | {}{}\n",
+                pad,
+                " ".repeat(before),
+                "^".repeat(width)
+            ));
+        }
+        out
+    }
+}
+
+/// The position of an error in refer input.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Position {
+    path: Option<PathBuf>,
+    line: u64,
+    column: u64,
+    byte: u64,
+    tag: Option<char>,
+    source: Option<String>,
+}
+
+impl Position {
+    /// The position `offset` bytes into the field `field`, which starts
+    /// on line `line`. An offset into a continuation line is put on that
+    /// line.
+    pub(crate) fn in_field(field: &str, line: u64, offset: usize) -> Position {
+        let mut offset = offset.min(field.len());
+        while !field.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        // the line of the field the offset is in
+        let start = field[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let source = field[start..].lines().next().unwrap_or_default().trim_end();
+        let mut column = (offset - start).min(source.len());
+        while !source.is_char_boundary(column) {
+            column -= 1;
+        }
+        Position {
+            path: None,
+            line: line + field[..start].matches('\n').count() as u64,
+            column: source[..column].chars().count() as u64 + 1,
+            byte: (start + column) as u64,
+            tag: field.strip_prefix('%').and_then(|t| t.chars().next()),
+            source: Some(source.to_string()),
+        }
+    }
+
+    /// The path of the file, if the input was read from a path.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The line number, starting from 1.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// The column, in characters and starting from 1.
+    pub fn column(&self) -> u64 {
+        self.column
+    }
+
+    /// The offset in bytes from the start of the input.
+    pub fn byte(&self) -> u64 {
+        self.byte
+    }
+
+    /// The tag of the field, e.g. `'A'` for `%A`.
+    pub fn tag(&self) -> Option<char> {
+        self.tag
+    }
+
+    /// The first line of the field.
+    pub fn source_line(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+}
+
+/// Written as `path:line:column`, or `line 3, column 4` without a path.
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.path {
+            Some(p) => write!(f, "{}:{}:{}", p.display(), self.line, self.column),
+            None => write!(f, "line {}, column {}", self.line, self.column),
+        }
     }
 }
 
@@ -42,8 +212,24 @@ pub enum ErrorKind {
     Author(String),
     /// If the keyword parsing goes awry
     Keyword(String),
-    /// For fetching the type of the record
-    RecordType(String),
+    /// If a BibTeX entry cannot be parsed
+    Bibtex(String),
+    /// If an RIS record cannot be parsed
+    Ris(String),
+    /// If CSL-JSON cannot be parsed, or is not a valid CSL item
+    CslJson(String),
+    /// If a date (%D) cannot be parsed
+    Date(String),
+    /// If a page range (%P) cannot be parsed, or ends before it starts
+    Pages(String),
+    /// If an index file cannot be read
+    Index(String),
+    /// If a citation in a document is not closed, or matches no record
+    Citation(String),
+    /// If a label expression cannot be parsed
+    Label(String),
+    /// If a CSL style cannot be parsed, or a citation can't be made
+    Csl(String),
 }
 
 impl From<io::Error> for Error {
@@ -54,7 +240,7 @@ impl From<io::Error> for Error {
 
 impl From<Error> for io::Error {
     fn from(err: Error) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, err)
+        io::Error::other(err)
     }
 }
 
@@ -62,7 +248,16 @@ impl StdError for Error {}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self.0 {
+        match self.position() {
+            Some(p) => write!(f, "{} at {}", self.kind, p),
+            None => self.kind.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
             ErrorKind::Io(ref err) => err.fmt(f),
             ErrorKind::Utf8(ref err) => {
                 write!(f, "refer invalid UTF-8 error: {}", err)
@@ -83,12 +278,32 @@ impl fmt::Display for Error {
             ErrorKind::Keyword(ref err) => {
                 write!(f, "Keyword field incorrectly specified: {}", err)
             }
-            ErrorKind::RecordType(ref err) => {
-                write!(
-                    f,
-                    "Record type cannot be both book and journal, or neither: {}",
-                    err
-                )
+            ErrorKind::Bibtex(ref err) => {
+                write!(f, "BibTeX parsing error: {}", err)
+            }
+            ErrorKind::Ris(ref err) => {
+                write!(f, "RIS parsing error: {}", err)
+            }
+            ErrorKind::CslJson(ref err) => {
+                write!(f, "CSL-JSON error: {}", err)
+            }
+            ErrorKind::Date(ref err) => {
+                write!(f, "Date parsing error: {}", err)
+            }
+            ErrorKind::Pages(ref err) => {
+                write!(f, "Page range error: {}", err)
+            }
+            ErrorKind::Index(ref err) => {
+                write!(f, "Index error: {}", err)
+            }
+            ErrorKind::Citation(ref err) => {
+                write!(f, "Citation error: {}", err)
+            }
+            ErrorKind::Label(ref err) => {
+                write!(f, "Label expression error: {}", err)
+            }
+            ErrorKind::Csl(ref err) => {
+                write!(f, "CSL style error: {}", err)
             }
         }
     }
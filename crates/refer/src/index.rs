@@ -0,0 +1,478 @@
+//! A keyword index over a refer database, like `indxbib` and `lookbib`
+//! in groff.
+//!
+//! The words in the fields of each record are made into keys, which
+//! are lower case and cut to a maximum length. An [`Index`] maps each key
+//! to the byte offsets of the records which contain it, so a query reads
+//! only the records which match. The index can be written next to the
+//! database, and is stale when the database has been changed since.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use refer::index::{Index, IndexBuilder};
+//! use std::fs::File;
+//!
+//! # fn main() -> Result<(), refer::Error> {
+//! // read the index, or build it if the database has changed
+//! let index = IndexBuilder::new().open("bib.refer")?;
+//!
+//! for record in index.lookup(File::open("bib.refer")?, "parasitic plants")? {
+//!     println!("{}", record?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{self, BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    reader::Reader,
+    record::Record,
+};
+
+/// The first line of an index file, with the version of the format.
+const MAGIC: &str = "refer-index 1";
+
+/// The fields which are not indexed by default, as in `indxbib`.
+const IGNORED_FIELDS: [char; 3] = ['X', 'Y', 'Z'];
+
+/// Common words which are not indexed by default.
+const COMMON_WORDS: [&str; 24] = [
+    "the", "and", "for", "with", "from", "that", "this", "are", "was", "were", "its", "into",
+    "their", "between", "which", "over", "under", "about", "after", "not", "but", "all", "than",
+    "has",
+];
+
+/// Options for building an [`Index`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexBuilder {
+    /// The tags of the fields to index, or `None` for all but %X, %Y and %Z.
+    fields: Option<Vec<char>>,
+    /// Words shorter than this are not indexed.
+    min_len: usize,
+    /// Keys are cut to this many characters.
+    truncate: usize,
+    /// Words which are not indexed.
+    common_words: Vec<String>,
+}
+
+impl Default for IndexBuilder {
+    fn default() -> Self {
+        IndexBuilder::new()
+    }
+}
+
+impl IndexBuilder {
+    /// A new [`IndexBuilder`], which indexes all fields but %X, %Y and
+    /// %Z, with words of at least 3 characters cut to 6, as `indxbib`
+    /// does.
+    pub fn new() -> Self {
+        IndexBuilder {
+            fields: None,
+            min_len: 3,
+            truncate: 6,
+            common_words: COMMON_WORDS.iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    /// Index only the fields with these tags, e.g. `"ATK"`.
+    pub fn set_fields(&mut self, tags: &str) {
+        self.fields = Some(tags.chars().collect());
+    }
+
+    /// Skip words shorter than `min_len` characters.
+    pub fn set_min_len(&mut self, min_len: usize) {
+        self.min_len = min_len.max(1);
+    }
+
+    /// Cut keys to `truncate` characters, so that a query for a prefix
+    /// of a long word finds it.
+    pub fn set_truncate(&mut self, truncate: usize) {
+        self.truncate = truncate.max(1);
+    }
+
+    /// Words which are not indexed, in place of the default list.
+    pub fn set_common_words<I, T>(&mut self, words: I)
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        self.common_words = words
+            .into_iter()
+            .map(|w| w.as_ref().to_lowercase())
+            .collect();
+    }
+
+    /// Build an index from a reader over a database.
+    pub fn build<R: io::Read>(&self, mut rdr: Reader<R>) -> Result<Index> {
+        let mut offsets = Vec::new();
+        let mut keys: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+
+        for raw in rdr.raw_records() {
+            let raw = raw?;
+            let start = match raw.fields().next() {
+                Some(f) => f.byte(),
+                // blank lines and comments at the end of the file
+                None => continue,
+            };
+            let n = offsets.len() as u32;
+            offsets.push(start);
+            for field in raw.fields() {
+                match field.tag() {
+                    Some(t) if self.indexes(t) => (),
+                    _ => continue,
+                }
+                for key in self.keys(&field.value()) {
+                    let records = keys.entry(key).or_default();
+                    if records.last() != Some(&n) {
+                        records.push(n);
+                    }
+                }
+            }
+        }
+
+        Ok(Index {
+            options: self.clone(),
+            modified: None,
+            offsets,
+            keys,
+        })
+    }
+
+    /// Build an index of the database at `path`, recording when the
+    /// database was last changed.
+    pub fn build_from_path<P: AsRef<Path>>(&self, path: P) -> Result<Index> {
+        // taken before reading, so that a change while reading makes
+        // the index stale
+        let modified = modified(path.as_ref())?;
+        let mut index = self.build(Reader::from_path(path)?)?;
+        index.modified = Some(modified);
+        Ok(index)
+    }
+
+    /// Read the index of the database at `path` from
+    /// [`Index::default_path`], or build it and write it there if it is
+    /// missing, stale or was built with other options.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<Index> {
+        let index_path = Index::default_path(&path);
+        if let Ok(index) = Index::from_path(&index_path) {
+            if index.options == *self && !index.is_stale(&path)? {
+                return Ok(index);
+            }
+        }
+        let index = self.build_from_path(&path)?;
+        index.write_to_path(&index_path)?;
+        Ok(index)
+    }
+
+    fn indexes(&self, tag: char) -> bool {
+        match &self.fields {
+            Some(fields) => fields.contains(&tag),
+            None => !IGNORED_FIELDS.contains(&tag),
+        }
+    }
+
+    /// The keys of some text: its words in lower case, cut to length,
+    /// without short and common words.
+    fn keys(&self, text: &str) -> BTreeSet<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| w.chars().count() >= self.min_len)
+            .map(str::to_lowercase)
+            .filter(|w| !self.common_words.contains(w))
+            .map(|w| w.chars().take(self.truncate).collect())
+            .collect()
+    }
+}
+
+/// A keyword index over a refer database. See the [module
+/// docs](index.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    /// The options the index was built with, which queries also use.
+    options: IndexBuilder,
+    /// When the database was last changed, as seconds and nanoseconds
+    /// since the Unix epoch.
+    modified: Option<(u64, u32)>,
+    /// The byte offset of the first field of each record.
+    offsets: Vec<u64>,
+    /// Each key, and the records which contain it.
+    keys: BTreeMap<String, Vec<u32>>,
+}
+
+impl Index {
+    /// Where the index of the database at `path` is kept, the path with
+    /// `.i` added, as in `indxbib`.
+    pub fn default_path<P: AsRef<Path>>(path: P) -> PathBuf {
+        let mut index_path = path.as_ref().as_os_str().to_owned();
+        index_path.push(".i");
+        PathBuf::from(index_path)
+    }
+
+    /// The number of records in the index.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Whether the index has no records.
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// The byte offsets of the records which contain every keyword of
+    /// `query`, in the order of the database. Keywords which are too
+    /// short or common to be indexed are ignored, and a query with no
+    /// other keywords matches nothing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use refer::{index::IndexBuilder, Reader};
+    ///
+    /// let data = "%T Parasitic plants\n%A Brown, M.\n\n%T Plant hosts\n";
+    /// let index = IndexBuilder::new().build(Reader::new(data.as_bytes())).unwrap();
+    ///
+    /// assert_eq!(index.search("plants"), [0]);
+    /// assert_eq!(index.search("PLANT"), [34]);
+    /// assert_eq!(index.search("the brown parasites"), [0]);
+    /// ```
+    pub fn search(&self, query: &str) -> Vec<u64> {
+        let keys = self.options.keys(query);
+        let mut records: Option<Vec<u32>> = None;
+        for key in &keys {
+            let found = self.keys.get(key).map(Vec::as_slice).unwrap_or_default();
+            records = Some(match records {
+                Some(r) => r.into_iter().filter(|n| found.contains(n)).collect(),
+                None => found.to_vec(),
+            });
+        }
+        records
+            .unwrap_or_default()
+            .into_iter()
+            .map(|n| self.offsets[n as usize])
+            .collect()
+    }
+
+    /// The records which match `query`, see [`Index::search`], read
+    /// from the database at their offsets.
+    pub fn lookup<R: io::Read + io::Seek>(
+        &self,
+        rdr: R,
+        query: &str,
+    ) -> Result<Vec<Result<Record>>> {
+        let mut rdr = Reader::new(rdr);
+        let mut records = Vec::new();
+        for offset in self.search(query) {
+            let record = rdr.read_record_at(offset).transpose().ok_or_else(|| {
+                index_error(format!("No record at byte {}, the index is stale", offset))
+            })?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    /// Whether the database at `path` has changed since the index was
+    /// built. An index not built from a path is always stale.
+    pub fn is_stale<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        Ok(self.modified != Some(modified(path.as_ref())?))
+    }
+
+    /// Read an index written by [`Index::write_to_path`].
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Index> {
+        Index::read(io::BufReader::new(File::open(path)?))
+    }
+
+    /// Write the index to `path`.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut wtr = BufWriter::new(File::create(path)?);
+        self.write(&mut wtr)?;
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Write the index as text: a header, the options, the offset of
+    /// each record, then each key with the numbers of its records.
+    fn write<W: Write>(&self, wtr: &mut W) -> Result<()> {
+        let options = &self.options;
+        writeln!(wtr, "{}", MAGIC)?;
+        match self.modified {
+            Some((secs, nanos)) => writeln!(wtr, "modified {} {}", secs, nanos)?,
+            None => writeln!(wtr, "modified")?,
+        }
+        match &options.fields {
+            Some(fields) => writeln!(wtr, "fields {}", fields.iter().collect::<String>())?,
+            None => writeln!(wtr, "fields")?,
+        }
+        writeln!(wtr, "min-len {}", options.min_len)?;
+        writeln!(wtr, "truncate {}", options.truncate)?;
+        writeln!(wtr, "common {}", options.common_words.join(" "))?;
+        writeln!(wtr, "records {}", self.offsets.len())?;
+        for offset in &self.offsets {
+            writeln!(wtr, "{}", offset)?;
+        }
+        for (key, records) in &self.keys {
+            write!(wtr, "{}", key)?;
+            for n in records {
+                write!(wtr, " {}", n)?;
+            }
+            writeln!(wtr)?;
+        }
+        Ok(())
+    }
+
+    fn read<R: BufRead>(rdr: R) -> Result<Index> {
+        let mut lines = rdr.lines();
+        let mut next_line = |name: &str| -> Result<String> {
+            let line = lines
+                .next()
+                .ok_or_else(|| index_error(format!("Missing {} line", name)))??;
+            match line.strip_prefix(name) {
+                Some(rest) => Ok(rest.trim().to_string()),
+                None => Err(index_error(format!("Expected {} line: {:?}", name, line))),
+            }
+        };
+
+        if !next_line(MAGIC)?.is_empty() {
+            return Err(index_error("Unknown index version".into()));
+        }
+        let modified = match next_line("modified")?.split_once(' ') {
+            Some((secs, nanos)) => Some((parse_number(secs)?, parse_number(nanos)?)),
+            None => None,
+        };
+        let fields = next_line("fields")?;
+        let options = IndexBuilder {
+            fields: (!fields.is_empty()).then(|| fields.chars().collect()),
+            min_len: parse_number(&next_line("min-len")?)?,
+            truncate: parse_number(&next_line("truncate")?)?,
+            common_words: next_line("common")?
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+        };
+        let n: usize = parse_number(&next_line("records")?)?;
+        let offsets = (0..n)
+            .map(|_| parse_number(&next_line("")?))
+            .collect::<Result<Vec<u64>>>()?;
+
+        let mut keys = BTreeMap::new();
+        for line in lines {
+            let line = line?;
+            let mut words = line.split(' ');
+            let key = words.next().unwrap_or_default().to_string();
+            let records = words.map(parse_number).collect::<Result<Vec<u32>>>()?;
+            if records.iter().any(|r| *r as usize >= n) {
+                return Err(index_error(format!("Record out of range: {:?}", line)));
+            }
+            keys.insert(key, records);
+        }
+
+        Ok(Index {
+            options,
+            modified,
+            offsets,
+            keys,
+        })
+    }
+}
+
+/// When the file at `path` was last changed.
+fn modified(path: &Path) -> Result<(u64, u32)> {
+    let time = fs::metadata(path)?.modified()?;
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok((since.as_secs(), since.subsec_nanos()))
+}
+
+fn parse_number<T: std::str::FromStr>(s: &str) -> Result<T> {
+    s.parse()
+        .map_err(|_| index_error(format!("Expected a number: {:?}", s)))
+}
+
+fn index_error(message: String) -> Error {
+    Error::new(ErrorKind::Index(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    const DATABASE: &str = "\
+%T Parasitic plants and their hosts
+%A Brown, M.
+%J New Phytologist
+%X This annotation is not indexed
+
+# a comment
+%T Eyebright genomics
+%A Twyford, A. D.
+%K euphrasia parasitic
+";
+
+    #[test]
+    fn search_and_lookup() {
+        let index = IndexBuilder::new()
+            .build(Reader::new(DATABASE.as_bytes()))
+            .unwrap();
+        let second = DATABASE.find("%T Eye").unwrap() as u64;
+
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.search("parasitic"), [0, second]);
+        assert_eq!(index.search("Parasitism brown"), [0]);
+        assert_eq!(index.search("annotation"), Vec::<u64>::new());
+        assert_eq!(index.search("the and"), Vec::<u64>::new());
+
+        let found = index
+            .lookup(io::Cursor::new(DATABASE), "euphrasia")
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            found[0].as_ref().unwrap().title.as_deref(),
+            Some("Eyebright genomics")
+        );
+
+        let mut builder = IndexBuilder::new();
+        builder.set_fields("K");
+        let index = builder.build(Reader::new(DATABASE.as_bytes())).unwrap();
+        assert_eq!(index.search("parasitic"), [second]);
+    }
+
+    #[test]
+    fn write_read_and_staleness() {
+        let dir = std::env::temp_dir().join("refer_index_test");
+        fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("bib.refer");
+        fs::write(&db, DATABASE).unwrap();
+        let index_path = Index::default_path(&db);
+        let _ = fs::remove_file(&index_path);
+
+        let builder = IndexBuilder::new();
+        let index = builder.open(&db).unwrap();
+        assert!(index_path.exists());
+        assert!(!index.is_stale(&db).unwrap());
+        assert_eq!(Index::from_path(&index_path).unwrap(), index);
+
+        // the database changes, so the index is rebuilt
+        let file = fs::OpenOptions::new().append(true).open(&db).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        drop(file);
+        assert!(index.is_stale(&db).unwrap());
+        let rebuilt = builder.open(&db).unwrap();
+        assert!(!rebuilt.is_stale(&db).unwrap());
+        assert_eq!(Index::from_path(&index_path).unwrap(), rebuilt);
+
+        fs::write(&index_path, "refer-index 2\n").unwrap();
+        assert!(matches!(
+            Index::from_path(&index_path).unwrap_err().kind(),
+            ErrorKind::Index(_)
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
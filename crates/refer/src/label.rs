@@ -0,0 +1,375 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    record::Record,
+};
+
+/// A label expression, as in the `label` command of groff `refer`,
+/// which makes citation labels such as `Smith 1999a`, `[1]` or `SJ99`
+/// from records.
+///
+/// Text is copied into the label, apart from these fields:
+///
+/// | Field | Meaning |
+/// |-------|---------|
+/// | `%a`  | The family name of the first author, or the %Q field |
+/// | `%A`  | The family names of the authors, e.g. `Smith and Jones`, with `et al.` after the first two of three or more |
+/// | `%i`  | The initials of the family names of the authors, e.g. `SJ` |
+/// | `%d`  | The year, or `n.d.` without one |
+/// | `%t`  | The first word of the title which is not an article |
+/// | `%n`  | The number of the record in the set being labelled |
+/// | `%s`  | Where the `a`, `b`, `c`, ... which tell apart records with the same label go |
+/// | `%%`  | A `%` |
+///
+/// A number after the `%` limits the field. `%3a` and `%3t` are cut to
+/// three characters, `%2d` is the last two digits of the year, `%2i` is
+/// the initials of the first two authors, and `%1A` puts `et al.` after
+/// the first author of two or more.
+///
+/// When records are labelled together with [`LabelFormat::labels`],
+/// records which would have the same label are told apart with a
+/// letter. Without a `%s`, the letter goes after the last year, or at the
+/// end of the label.
+///
+/// # Example
+///
+/// ```
+/// use refer::{Author, LabelFormat, Record};
+///
+/// let record = |last: &str, date: &str| Record {
+///     author: vec![Author { last: last.into(), rest: "J.".into(), ..Default::default() }],
+///     date: Some(date.into()),
+///     ..Default::default()
+/// };
+/// let records = [record("Smith", "1999"), record("Jones", "2001"), record("Smith", "1999")];
+///
+/// let format: LabelFormat = "%a %d".parse().unwrap();
+/// assert_eq!(format.labels(&records), ["Smith 1999a", "Jones 2001", "Smith 1999b"]);
+///
+/// let format: LabelFormat = "[%n]".parse().unwrap();
+/// assert_eq!(format.labels(&records), ["[1]", "[2]", "[3]"]);
+///
+/// let format: LabelFormat = "%i%2d".parse().unwrap();
+/// assert_eq!(format.label(&records[0], 1), "S99");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelFormat {
+    /// The expression as it was written.
+    expression: String,
+    /// The parsed parts of the expression.
+    parts: Vec<Part>,
+}
+
+/// A part of a label expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(Field, Option<usize>),
+    Suffix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Author,
+    Authors,
+    Initials,
+    Year,
+    Title,
+    Number,
+}
+
+/// Articles which are skipped for `%t`.
+const ARTICLES: [&str; 3] = ["a", "an", "the"];
+
+impl LabelFormat {
+    /// The label of a single record, which is the `n`th of its set.
+    /// Records are not told apart, so `%s` is left out.
+    pub fn label(&self, record: &Record, n: usize) -> String {
+        self.render(record, n, "")
+    }
+
+    /// The labels of a set of records, in order, with a letter after
+    /// the records which would have the same label.
+    pub fn labels(&self, records: &[Record]) -> Vec<String> {
+        let plain: Vec<String> = records
+            .iter()
+            .zip(1..)
+            .map(|(r, n)| self.label(r, n))
+            .collect();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for label in &plain {
+            *counts.entry(label).or_default() += 1;
+        }
+
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        records
+            .iter()
+            .zip(1..)
+            .zip(&plain)
+            .map(|((record, n), label)| match counts[label.as_str()] {
+                1 => label.clone(),
+                _ => {
+                    let i = seen.entry(label).or_default();
+                    *i += 1;
+                    self.render(record, n, &suffix(*i))
+                }
+            })
+            .collect()
+    }
+
+    /// Set the %L field of each record which has no label, from the
+    /// labels of the whole set.
+    pub fn fill_labels(&self, records: &mut [Record]) {
+        let labels = self.labels(records);
+        for (record, label) in records.iter_mut().zip(labels) {
+            if record.label.is_none() {
+                record.label = Some(label);
+            }
+        }
+    }
+
+    fn render(&self, record: &Record, n: usize, suffix: &str) -> String {
+        // without a `%s`, the suffix goes after the last year
+        let suffix_at = match self.parts.contains(&Part::Suffix) {
+            true => None,
+            false => self
+                .parts
+                .iter()
+                .rposition(|p| matches!(p, Part::Field(Field::Year, _)))
+                .or(self.parts.len().checked_sub(1)),
+        };
+
+        let mut label = String::new();
+        for (i, part) in self.parts.iter().enumerate() {
+            match part {
+                Part::Text(t) => label.push_str(t),
+                Part::Field(field, limit) => label.push_str(&field.value(record, n, *limit)),
+                Part::Suffix => label.push_str(suffix),
+            }
+            if suffix_at == Some(i) {
+                label.push_str(suffix);
+            }
+        }
+        label
+    }
+}
+
+impl Field {
+    fn value(self, record: &Record, n: usize, limit: Option<usize>) -> String {
+        let cut = |s: String| match limit {
+            Some(l) => s.chars().take(l).collect(),
+            None => s,
+        };
+        let names = family_names(record);
+        match self {
+            Field::Author => cut(names.first().cloned().unwrap_or_default()),
+            Field::Authors => {
+                let before_et_al = limit.unwrap_or(2).max(1);
+                match &names[..] {
+                    [] => String::new(),
+                    names if names.len() > before_et_al => {
+                        format!("{} et al.", names[..before_et_al].join(", "))
+                    }
+                    [one] => one.clone(),
+                    [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+                }
+            }
+            Field::Initials => names
+                .iter()
+                .take(limit.unwrap_or(usize::MAX))
+                .filter_map(|name| name.chars().find(|c| c.is_alphanumeric()))
+                .flat_map(char::to_uppercase)
+                .collect(),
+            Field::Year => match (year(record), limit) {
+                (Some(y), Some(l)) => {
+                    let y = y.to_string();
+                    y[y.len().saturating_sub(l)..].to_string()
+                }
+                (Some(y), None) => y.to_string(),
+                (None, Some(_)) => String::new(),
+                (None, None) => "n.d.".to_string(),
+            },
+            Field::Title => {
+                let title = record.title.as_deref().unwrap_or_default();
+                let word = title
+                    .split_whitespace()
+                    .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
+                    .find(|w| !w.is_empty() && !ARTICLES.contains(&w.to_lowercase().as_str()))
+                    .unwrap_or_default();
+                cut(word.to_string())
+            }
+            Field::Number => n.to_string(),
+        }
+    }
+}
+
+/// The family names of the authors, or the %Q field.
+fn family_names(record: &Record) -> Vec<String> {
+    match (&record.author[..], &record.author_np) {
+        ([], Some(q)) => vec![q.clone()],
+        (authors, _) => authors.iter().map(|a| a.family_name()).collect(),
+    }
+}
+
+/// The year of the record, from the date if it can be parsed, or else
+/// the first four digits of the %D field.
+fn year(record: &Record) -> Option<i32> {
+    if let Some(y) = record.parsed_date().ok().and_then(|d| d.year()) {
+        return Some(y);
+    }
+    let date = record.date.as_deref()?;
+    let digits: Vec<char> = date.chars().collect();
+    digits
+        .windows(4)
+        .find(|w| w.iter().all(char::is_ascii_digit))
+        .and_then(|w| w.iter().collect::<String>().parse().ok())
+}
+
+/// `a` to `z`, then `aa`, `ab`, ...
+pub(crate) fn suffix(mut i: usize) -> String {
+    let mut letters = Vec::new();
+    while i > 0 {
+        i -= 1;
+        letters.push((b'a' + (i % 26) as u8) as char);
+        i /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+impl FromStr for LabelFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let label_error =
+            |message: &str| Error::new(ErrorKind::Label(format!("{} in {:?}", message, s)));
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            let mut digits = String::new();
+            while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                digits.push(d);
+            }
+            let limit = match digits.parse::<usize>() {
+                Ok(0) => return Err(label_error("A field can't be limited to 0")),
+                Ok(l) => Some(l),
+                Err(_) => None,
+            };
+            let field = match chars.next() {
+                Some('%') if limit.is_none() => {
+                    text.push('%');
+                    continue;
+                }
+                Some('s') if limit.is_none() => {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                    parts.push(Part::Suffix);
+                    continue;
+                }
+                Some('a') => Field::Author,
+                Some('A') => Field::Authors,
+                Some('i') => Field::Initials,
+                Some('d') => Field::Year,
+                Some('t') => Field::Title,
+                Some('n') if limit.is_none() => Field::Number,
+                Some(c) => return Err(label_error(&format!("Unknown field %{}{}", digits, c))),
+                None => return Err(label_error("The expression ends with %")),
+            };
+            parts.push(Part::Text(std::mem::take(&mut text)));
+            parts.push(Part::Field(field, limit));
+        }
+        parts.push(Part::Text(text));
+        parts.retain(|p| *p != Part::Text(String::new()));
+
+        Ok(LabelFormat {
+            expression: s.to_string(),
+            parts,
+        })
+    }
+}
+
+/// The expression as it was written.
+impl Display for LabelFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::Author;
+
+    fn record(names: &[&str], date: &str, title: &str) -> Record {
+        Record {
+            author: names.iter().map(|n| n.parse::<Author>().unwrap()).collect(),
+            date: Some(date.into()),
+            title: Some(title.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn label_fields() {
+        let r = record(
+            &["Smith, J.", "Jones, A.", "van der Berg, P."],
+            "May 1999",
+            "The Parasitic Plants",
+        );
+        let label = |s: &str| s.parse::<LabelFormat>().unwrap().label(&r, 4);
+        assert_eq!(label("%a %d"), "Smith 1999");
+        assert_eq!(label("%3a%2d"), "Smi99");
+        assert_eq!(label("%A"), "Smith, Jones et al.");
+        assert_eq!(label("%1A, %d"), "Smith et al., 1999");
+        assert_eq!(label("%3A"), "Smith, Jones and van der Berg");
+        assert_eq!(label("%i%2d"), "SJV99");
+        assert_eq!(label("%2i"), "SJ");
+        assert_eq!(label("%t:%5t"), "Parasitic:Paras");
+        assert_eq!(label("[%n] 100%%"), "[4] 100%");
+
+        let corporate = Record {
+            author_np: Some("World Health Organization".into()),
+            date: Some("in press".into()),
+            ..Default::default()
+        };
+        let format: LabelFormat = "%a (%d)".parse().unwrap();
+        assert_eq!(
+            format.label(&corporate, 1),
+            "World Health Organization (n.d.)"
+        );
+        assert_eq!(format.to_string(), "%a (%d)");
+
+        for bad in ["%", "%x", "%0a", "%2n", "%3%"] {
+            let err = bad.parse::<LabelFormat>().unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::Label(_)), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn disambiguation() {
+        let mut records: Vec<Record> = (0..28)
+            .map(|i| record(&["Smith, J."], "1999", &format!("Title {}", i)))
+            .collect();
+        records.push(record(&["Jones, A."], "c. 2001", "Other"));
+        records[1].label = Some("kept".into());
+
+        let format: LabelFormat = "[%a %d]".parse().unwrap();
+        let labels = format.labels(&records);
+        assert_eq!(labels[0], "[Smith 1999a]");
+        assert_eq!(labels[25], "[Smith 1999z]");
+        assert_eq!(labels[26], "[Smith 1999aa]");
+        assert_eq!(labels[28], "[Jones 2001]");
+
+        let format: LabelFormat = "%a%s, %d".parse().unwrap();
+        assert_eq!(format.labels(&records)[1], "Smithb, 1999");
+
+        format.fill_labels(&mut records);
+        assert_eq!(records[0].label.as_deref(), Some("Smitha, 1999"));
+        assert_eq!(records[1].label.as_deref(), Some("kept"));
+    }
+}
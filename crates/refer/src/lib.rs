@@ -1,5 +1,12 @@
 /*!
-The `refer` crate provides a refer bibliographic format reader and writer. There is currently no Serde support.
+The `refer` crate provides a refer bibliographic format reader and writer.
+
+With the `serde` feature enabled, [`Record`](struct.Record.html),
+[`Author`](struct.Author.html), [`RecordType`](enum.RecordType.html) and
+[`Date`](enum.Date.html) implement `Serialize` and `Deserialize`. The encoding is described on
+[`Record`](struct.Record.html). The `csl-json` feature adds the
+[`csl_json`](csl_json/index.html) module and the `csl` feature the
+[`csl`](csl/index.html) module, both of which depend on `serde_json`.
 
 The primary types in this crate are
 [`Reader`](struct.Reader.html)
@@ -9,13 +16,29 @@ for reading and writing refer data.
 
 All records are parsed into the
 [`Record`](struct.Record.html)
-which contains an exhaustive list of refer format fields.
+which contains an exhaustive list of refer format fields. Dates and pages
+can be parsed into a [`Date`](enum.Date.html) and a
+[`PageRange`](struct.PageRange.html) for sorting and formatting, and
+citation labels such as `Smith 1999a` are made with a
+[`LabelFormat`](struct.LabelFormat.html). A
+[`StyleBuilder`](struct.StyleBuilder.html) formats a record in a citation
+style, as [`Text`](struct.Text.html) which can be rendered as plain text,
+Markdown, HTML, LaTeX, troff or RTF with a [`Markup`](enum.Markup.html).
 
 The errors which can occur when parsing and writing are
 described in the
 [`Error`](struct.Error.html)
 type.
 
+Records can also be converted to and from other bibliography formats,
+see the [`bibtex`](bibtex/index.html), [`ris`](ris/index.html) and
+[`csl_json`](csl_json/index.html) (with the `csl-json` feature) modules. A keyword index of a database, as
+made by `indxbib` in groff, is in the [`index`](index/index.html) module, and the
+[`preprocessor`](preprocessor/index.html) module replaces the citations in
+roff documents, as groff `refer` does. Records can be formatted with
+any CSL style file by the [`csl`](csl/index.html) module, with the `csl`
+feature.
+
 # Example
 
 This example shows the simple API to read in and iterate over
@@ -43,17 +66,38 @@ fn main() -> Result<(), Box<dyn error::Error>> {
 ```
  */
 
+pub mod bibtex;
+#[cfg(feature = "csl")]
+pub mod csl;
+#[cfg(feature = "csl-json")]
+pub mod csl_json;
+mod date;
 mod error;
+pub mod index;
+mod label;
+mod pages;
+pub mod preprocessor;
+mod raw;
 mod reader;
 mod record;
+pub mod ris;
 mod style;
+mod text;
 mod writer;
 
 pub use crate::{
-    error::{Error, ErrorKind},
-    reader::{Reader, RecordsIntoIter, RecordsIter},
-    record::{Author, Record},
-    style::{Style, StyleBuilder},
+    date::{Date, DatePart},
+    error::{Error, ErrorKind, Position},
+    label::LabelFormat,
+    pages::{Page, PageFormat, PageRange},
+    raw::{RawField, RawNode, RawRecord},
+    reader::{
+        RawRecordsIter, Reader, RecordPosition, RecordsIntoIter, RecordsIter,
+        RecordsWithPositionIter,
+    },
+    record::{Author, Record, RecordType},
+    style::{Note, Style, StyleBuilder},
+    text::{Markup, Span, Text},
     writer::Writer,
 };
 
@@ -0,0 +1,323 @@
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use crate::error::{Error, ErrorKind, Result};
+
+/// A page, or a range of pages, parsed from the `%P` field of a record.
+///
+/// Pages can be numbers, roman numerals for front matter (`xiv`), or
+/// have a prefix, as for article numbers (`e1002345`) and supplements
+/// (`S12`). A range is separated by a hyphen or an en dash, and the end
+/// of a range can be abbreviated, so `123-9` is pages 123 to 129.
+///
+/// # Example
+///
+/// ```
+/// use refer::{PageFormat, PageRange};
+///
+/// let pages: PageRange = "1496-504".parse().unwrap();
+/// assert_eq!(pages.to_string(), "1496-1504");
+/// assert_eq!(pages.format(PageFormat::Minimal, "-"), "1496-504");
+///
+/// // the end of a range can't come before the start
+/// assert!("145-23".parse::<PageRange>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PageRange {
+    /// The page, or the first page of the range.
+    pub start: Page,
+    /// The last page of the range.
+    pub end: Option<Page>,
+}
+
+/// A single page.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Page {
+    /// A page number, e.g. `123`.
+    Number(u32),
+    /// A page in roman numerals, e.g. `xiv`, usually front matter.
+    Roman(u32),
+    /// A page or article number with a prefix, e.g. `e1002345` or `S12`.
+    Prefixed(String, u32),
+}
+
+/// How the end of a page range is written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PageFormat {
+    /// In full, e.g. `321-328`.
+    #[default]
+    Expanded,
+    /// Only the digits which differ from the start, e.g. `321-8`, as in
+    /// the Vancouver style.
+    Minimal,
+    /// As in the Chicago Manual of Style, e.g. `3-10`, `101-8`,
+    /// `321-28` and `1496-1504`.
+    Chicago,
+    /// At least two digits above ninety-nine, as in the MLA Handbook,
+    /// e.g. `21-32`, `103-04` and `395-401`.
+    Mla,
+}
+
+impl PageRange {
+    /// Whether this is a range, rather than a single page.
+    pub fn is_range(&self) -> bool {
+        self.end.is_some()
+    }
+
+    /// Write the range with `dash` between the pages, with the end
+    /// written as in `format`. Roman numerals are always written in
+    /// full.
+    pub fn format(&self, format: PageFormat, dash: &str) -> String {
+        let end = match &self.end {
+            Some(e) => e,
+            None => return self.start.to_string(),
+        };
+        let (start_n, end_n) = match (&self.start, end) {
+            (Page::Number(s), Page::Number(e)) => (*s, *e),
+            (Page::Prefixed(p, s), Page::Prefixed(q, e)) if p == q => (*s, *e),
+            _ => return format!("{}{}{}", self.start, dash, end),
+        };
+        let end_digits = match format {
+            PageFormat::Expanded => end.to_string(),
+            PageFormat::Minimal => minimal_end(start_n, end_n, 1),
+            PageFormat::Chicago => chicago_end(start_n, end_n),
+            PageFormat::Mla if start_n < 100 => end.to_string(),
+            PageFormat::Mla => minimal_end(start_n, end_n, 2),
+        };
+        format!("{}{}{}", self.start, dash, end_digits)
+    }
+
+    /// Compare the start and end, if they are the same kind of page.
+    fn compare_ends(&self) -> Option<Ordering> {
+        match (&self.start, self.end.as_ref()?) {
+            (Page::Number(s), Page::Number(e))
+            | (Page::Roman(s), Page::Roman(e))
+            | (Page::Prefixed(_, s), Page::Prefixed(_, e)) => Some(s.cmp(e)),
+            _ => None,
+        }
+    }
+}
+
+/// The end of a range without the leading digits it shares with the
+/// start, keeping at least `keep` digits.
+fn minimal_end(start: u32, end: u32, keep: usize) -> String {
+    let (s, e) = (start.to_string(), end.to_string());
+    if s.len() != e.len() {
+        return e;
+    }
+    let common = s.chars().zip(e.chars()).take_while(|(a, b)| a == b).count();
+    let from = common.min(e.len().saturating_sub(keep));
+    e[from..].to_string()
+}
+
+/// Chicago abbreviates ranges by how far the start is into a hundred.
+fn chicago_end(start: u32, end: u32) -> String {
+    match start % 100 {
+        _ if start < 100 => end.to_string(),
+        0 => end.to_string(),
+        1..=9 => minimal_end(start, end, 1),
+        _ => {
+            let e = minimal_end(start, end, 2);
+            // three changed digits of a four digit number are written
+            // in full, e.g. 1496-1504
+            if e.len() == 3 && end >= 1000 {
+                end.to_string()
+            } else {
+                e
+            }
+        }
+    }
+}
+
+impl Display for Page {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Page::Number(n) => write!(f, "{}", n),
+            Page::Roman(n) => write!(f, "{}", to_roman(*n)),
+            Page::Prefixed(p, n) => write!(f, "{}{}", p, n),
+        }
+    }
+}
+
+/// Written in full, with a hyphen, as in refer.
+impl Display for PageRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.format(PageFormat::Expanded, "-"))
+    }
+}
+
+impl FromStr for Page {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Ok(n) = s.parse() {
+            return Ok(Page::Number(n));
+        }
+        if let Some(n) = from_roman(s) {
+            return Ok(Page::Roman(n));
+        }
+        let digits = s.trim_start_matches(|c: char| !c.is_ascii_digit());
+        let prefix = &s[..s.len() - digits.len()];
+        match digits.parse() {
+            Ok(n) if !prefix.is_empty() && prefix.chars().all(char::is_alphabetic) => {
+                Ok(Page::Prefixed(prefix.to_string(), n))
+            }
+            _ => Err(Error::new(ErrorKind::Pages(format!(
+                "Could not parse the page: {:?}",
+                s
+            )))),
+        }
+    }
+}
+
+impl FromStr for PageRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let pages_error =
+            |message: &str| Error::new(ErrorKind::Pages(format!("{}: {:?}", message, s)));
+        let (start, end) = match s.split_once(['-', '\u{2013}']) {
+            // BibTeX style `--` ranges are also read
+            Some((start, end)) => (start, Some(end.trim_start_matches(['-', '\u{2013}']))),
+            None => (s, None),
+        };
+        let start: Page = start.parse()?;
+        let end = match end {
+            Some(e) => e.trim(),
+            None => return Ok(PageRange { start, end: None }),
+        };
+
+        let end = match (&start, end.parse()?) {
+            (Page::Number(s), Page::Number(e)) => Page::Number(expand(*s, e, end)),
+            // the prefix can be left off the end, e.g. `S12-19`
+            (Page::Prefixed(p, s), Page::Number(e)) => {
+                Page::Prefixed(p.clone(), expand(*s, e, end))
+            }
+            (Page::Prefixed(p, s), Page::Prefixed(q, e)) if *p == q => {
+                Page::Prefixed(q, expand(*s, e, end))
+            }
+            (Page::Roman(_), e @ Page::Roman(_)) => e,
+            // e.g. front matter running into the body, `xi-4`
+            (Page::Roman(_), e @ Page::Number(_)) => e,
+            _ => return Err(pages_error("The pages of the range don't match")),
+        };
+        let range = PageRange {
+            start,
+            end: Some(end),
+        };
+        if range.compare_ends() == Some(Ordering::Greater) {
+            return Err(pages_error("The page range ends before it starts"));
+        }
+        Ok(range)
+    }
+}
+
+/// Expand an abbreviated end of a range, e.g. the `9` of `123-9`.
+fn expand(start: u32, end: u32, written: &str) -> u32 {
+    let digits = written
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .len() as u32;
+    let scale = 10u32.saturating_pow(digits);
+    if end >= start || scale > start {
+        return end;
+    }
+    start - start % scale + end
+}
+
+const NUMERALS: [(u32, &str); 13] = [
+    (1000, "m"),
+    (900, "cm"),
+    (500, "d"),
+    (400, "cd"),
+    (100, "c"),
+    (90, "xc"),
+    (50, "l"),
+    (40, "xl"),
+    (10, "x"),
+    (9, "ix"),
+    (5, "v"),
+    (4, "iv"),
+    (1, "i"),
+];
+
+pub(crate) fn to_roman(mut n: u32) -> String {
+    let mut s = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            s.push_str(numeral);
+            n -= value;
+        }
+    }
+    s
+}
+
+/// Read a roman numeral, in lower case so that a single capital, e.g.
+/// `C`, is not read as one. Only numerals in the usual form are read, so
+/// that `ic` is not 99.
+fn from_roman(s: &str) -> Option<u32> {
+    let mut rest = s;
+    let mut n = 0;
+    for (value, numeral) in NUMERALS {
+        while let Some(r) = rest.strip_prefix(numeral) {
+            n += value;
+            rest = r;
+        }
+    }
+    (rest.is_empty() && n > 0 && to_roman(n) == s).then_some(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> PageRange {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_pages() {
+        assert_eq!(parse("12").start, Page::Number(12));
+        assert!(!parse("12").is_range());
+        assert_eq!(parse("123-9").end, Some(Page::Number(129)));
+        assert_eq!(parse("45\u{2013}67").end, Some(Page::Number(67)));
+        assert_eq!(parse("45--67").end, Some(Page::Number(67)));
+        assert_eq!(parse("e1002345").start, Page::Prefixed("e".into(), 1002345));
+        assert_eq!(parse("S12-19").to_string(), "S12-S19");
+        assert_eq!(parse("xiv-xx").start, Page::Roman(14));
+        assert_eq!(parse("xi-4").to_string(), "xi-4");
+
+        for s in [
+            "", "12-", "145-23", "xx-x", "S12-e14", "12a", "ic", "C", "XIV",
+        ] {
+            let err = s.parse::<PageRange>().unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::Pages(_)), "{}", s);
+        }
+    }
+
+    #[test]
+    fn format_ranges() {
+        let chicago = |s: &str| parse(s).format(PageFormat::Chicago, "-");
+        assert_eq!(chicago("3-10"), "3-10");
+        assert_eq!(chicago("71-72"), "71-72");
+        assert_eq!(chicago("100-104"), "100-104");
+        assert_eq!(chicago("101-108"), "101-8");
+        assert_eq!(chicago("1103-1104"), "1103-4");
+        assert_eq!(chicago("321-328"), "321-28");
+        assert_eq!(chicago("498-532"), "498-532");
+        assert_eq!(chicago("1087-1089"), "1087-89");
+        assert_eq!(chicago("1496-1504"), "1496-1504");
+        assert_eq!(chicago("11564-11568"), "11564-68");
+
+        let minimal = |s: &str| parse(s).format(PageFormat::Minimal, "\u{2013}");
+        assert_eq!(minimal("321-328"), "321\u{2013}8");
+        assert_eq!(minimal("98-102"), "98\u{2013}102");
+        assert_eq!(minimal("e101-e109"), "e101\u{2013}9");
+        assert_eq!(minimal("xi-xiv"), "xi\u{2013}xiv");
+
+        let mla = |s: &str| parse(s).format(PageFormat::Mla, "-");
+        assert_eq!(mla("21-32"), "21-32");
+        assert_eq!(mla("96-113"), "96-113");
+        assert_eq!(mla("103-104"), "103-04");
+        assert_eq!(mla("395-401"), "395-401");
+    }
+}
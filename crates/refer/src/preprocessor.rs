@@ -0,0 +1,639 @@
+//! A citation preprocessor for roff documents, compatible with groff
+//! `refer`.
+//!
+//! A citation is a block of keywords between `.[` and `.]` lines, or
+//! keywords in brackets in a line of text, e.g. `[brown parasitic]`. The
+//! keywords are looked up in a database with an [`Index`], and the
+//! citation is replaced by a label between the `[.` and `.]` strings.
+//! After it, the fields of the record are defined as strings for the
+//! macro package, and the `.][` macro is called to print the reference,
+//! as groff `refer` does:
+//!
+//! ```text
+//! Parasitic plants are common\*([.1\*(.].
+//! .ds [F 1
+//! .]-
+//! .ds [A M. Brown
+//! .nr [A 0
+//! .ds [D 2023
+//! .ds [J New Phytologist
+//! .ds [T Parasitic plants
+//! .nr [T 0
+//! .][ 1 journal-article
+//! ```
+//!
+//! Text after `.[` goes before the label and text after `.]` goes
+//! after it, and the label is added to the line of text before the
+//! citation. Lines starting with `%` in a citation block add fields to the
+//! record.
+//!
+//! Records are labelled with numbers in the order they are first cited,
+//! unless a [`LabelFormat`] is set, either with
+//! [`Preprocessor::set_label_format`] or with a `label` command between
+//! `.R1` and `.R2`:
+//!
+//! ```text
+//! .R1
+//! label "%a %d"
+//! .R2
+//! ```
+//!
+//! Other commands between `.R1` and `.R2` are not supported, and the
+//! lines from `.R1` to `.R2` are removed from the output.
+//!
+//! # Example
+//!
+//! ```
+//! use refer::preprocessor::Preprocessor;
+//!
+//! let database = "%A Brown, M.\n%T Parasitic plants\n%J New Phytologist\n%D 2023\n";
+//! let mut preprocessor = Preprocessor::new(database.as_bytes()).unwrap();
+//!
+//! let document = ".PP\nParasitic plants are common\n.[\nbrown parasitic\n.].\n";
+//! let mut output = Vec::new();
+//! preprocessor.process(document.as_bytes(), &mut output).unwrap();
+//!
+//! let output = String::from_utf8(output).unwrap();
+//! assert!(output.starts_with(".PP\nParasitic plants are common\\*([.1\\*(.].\n.ds [F 1\n"));
+//! assert!(output.contains(".ds [T Parasitic plants\n"));
+//! assert!(output.ends_with(".][ 1 journal-article\n.lf 6\n"));
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use crate::{
+    error::{Error, ErrorKind, Position, Result},
+    index::{Index, IndexBuilder},
+    label::LabelFormat,
+    reader::{split_fields, Reader},
+    record::{Author, Record, RecordType},
+};
+
+/// Fields which are not defined as strings, as in groff `refer`.
+const DISCARDED_FIELDS: [char; 3] = ['X', 'Y', 'Z'];
+
+/// Replaces the citations in roff documents with labels and
+/// references. See the [module docs](index.html).
+#[derive(Debug)]
+pub struct Preprocessor {
+    /// The records of the database, by their byte offset.
+    records: BTreeMap<u64, Record>,
+    /// The keyword index of the database.
+    index: Index,
+    /// The labels of the records cited so far, by their byte offset.
+    labels: BTreeMap<u64, String>,
+    /// How labels are made, if not by numbers.
+    label_format: Option<LabelFormat>,
+    /// The label of every record in the database, from the label format.
+    database_labels: BTreeMap<u64, String>,
+    /// The problems with citations found so far.
+    diagnostics: Vec<Error>,
+}
+
+impl Preprocessor {
+    /// A preprocessor which cites records from the refer database
+    /// `database`.
+    pub fn new<R: io::Read>(mut database: R) -> Result<Preprocessor> {
+        let mut data = Vec::new();
+        database.read_to_end(&mut data)?;
+        Preprocessor::from_bytes(&data, &IndexBuilder::new())
+    }
+
+    /// A preprocessor which cites records from the refer database at
+    /// `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Preprocessor> {
+        Preprocessor::from_bytes(&fs::read(path)?, &IndexBuilder::new())
+    }
+
+    /// A preprocessor whose index is built with `builder`, e.g. to
+    /// search only some fields.
+    pub fn with_index<R: io::Read>(
+        mut database: R,
+        builder: &IndexBuilder,
+    ) -> Result<Preprocessor> {
+        let mut data = Vec::new();
+        database.read_to_end(&mut data)?;
+        Preprocessor::from_bytes(&data, builder)
+    }
+
+    fn from_bytes(data: &[u8], builder: &IndexBuilder) -> Result<Preprocessor> {
+        let index = builder.build(Reader::new(data))?;
+        let records = Reader::new(data)
+            .records_with_position()
+            .map(|r| r.map(|(position, record)| (position.byte(), record)))
+            .collect::<Result<_>>()?;
+        Ok(Preprocessor {
+            records,
+            index,
+            labels: BTreeMap::new(),
+            label_format: None,
+            database_labels: BTreeMap::new(),
+            diagnostics: Vec::new(),
+        })
+    }
+
+    /// Label records with `format`, rather than by numbers. Labels are
+    /// made for the whole database at once, so that a record has the
+    /// same label in every document. Records with a %L field keep it as
+    /// their label.
+    pub fn set_label_format(&mut self, format: LabelFormat) {
+        let mut records: Vec<Record> = self.records.values().cloned().collect();
+        format.fill_labels(&mut records);
+        self.database_labels = self
+            .records
+            .keys()
+            .copied()
+            .zip(records.into_iter().map(|r| r.label.unwrap_or_default()))
+            .collect();
+        self.label_format = Some(format);
+    }
+
+    /// Citations which matched no record, or more than one record, in
+    /// the documents processed so far. A citation with no match is
+    /// removed, and the first of several matches is used, as in groff
+    /// `refer`.
+    pub fn diagnostics(&self) -> &[Error] {
+        &self.diagnostics
+    }
+
+    /// Read a roff document from `input`, and write it to `output` with
+    /// its citations replaced.
+    pub fn process<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> Result<()> {
+        let mut lines = input.lines().zip(1u64..);
+        // the last line of text, which a label can be added to, and the
+        // references cited in it
+        let mut pending: Option<(String, Vec<String>)> = None;
+
+        while let Some((line, line_no)) = lines.next() {
+            let line = line?;
+
+            if is_request(&line, "R1") {
+                for (l, n) in lines.by_ref() {
+                    let l = l?;
+                    if is_request(&l, "R2") {
+                        break;
+                    }
+                    if let Some(expression) = l.trim().strip_prefix("label ") {
+                        let expression = expression.trim().trim_matches('"');
+                        let format = expression
+                            .parse()
+                            .map_err(|e: Error| e.with_position(Position::in_field(&l, n, 0)))?;
+                        self.set_label_format(format);
+                    }
+                }
+                continue;
+            }
+
+            if let Some(opening) = line.strip_prefix(".[") {
+                let mut body = Vec::new();
+                let mut closing = None;
+                for (l, _) in lines.by_ref() {
+                    let l = l?;
+                    if let Some(c) = l.strip_prefix(".]") {
+                        closing = Some(c.to_string());
+                        break;
+                    }
+                    body.push(l);
+                }
+                let closing = closing.ok_or_else(|| {
+                    citation_error("The citation has no closing .]", &line, line_no)
+                })?;
+                let next_line = line_no + body.len() as u64 + 2;
+
+                let line_directive = format!(".lf {}\n", next_line);
+                let (label, reference) = match self.cite(&body, &line, line_no)? {
+                    Some(cited) => cited,
+                    // the citation is removed
+                    None => {
+                        match pending.as_mut() {
+                            Some((_, references)) => references.push(line_directive),
+                            None => output.write_all(line_directive.as_bytes())?,
+                        }
+                        continue;
+                    }
+                };
+                let cited = format!("{}\\*([.{}\\*(.]{}", opening, label, closing);
+                let (text, mut references) = match pending.take() {
+                    Some((mut text, references)) if !is_control(&text) => {
+                        text.push_str(&cited);
+                        (text, references)
+                    }
+                    other => {
+                        flush(&mut output, other)?;
+                        (cited, Vec::new())
+                    }
+                };
+                references.push(reference);
+                references.push(line_directive);
+                flush(&mut output, Some((text, references)))?;
+                continue;
+            }
+
+            flush(&mut output, pending.take())?;
+            pending = match is_control(&line) {
+                true => Some((line, Vec::new())),
+                false => Some(self.cite_inline(&line, line_no)),
+            };
+        }
+        flush(&mut output, pending)?;
+        Ok(())
+    }
+
+    /// Find the record of a citation block, and return its label and
+    /// reference.
+    fn cite(
+        &mut self,
+        body: &[String],
+        line: &str,
+        line_no: u64,
+    ) -> Result<Option<(String, String)>> {
+        let (fields, keywords): (Vec<&String>, Vec<&String>) =
+            body.iter().partition(|l| l.starts_with('%'));
+        let query = keywords
+            .iter()
+            .map(|k| k.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let (offset, mut text) = match query.trim().is_empty() {
+            true => (None, String::new()),
+            false => match self.find(&query, line, line_no) {
+                Some(offset) => (Some(offset), self.records[&offset].to_string()),
+                None => return Ok(None),
+            },
+        };
+        // fields in the citation are added to the record
+        for field in fields {
+            text.push_str(field);
+            text.push('\n');
+        }
+        let record = match Reader::new(text.as_bytes()).records().next() {
+            Some(record) => record?,
+            None => return Ok(None),
+        };
+
+        let label = self.label(offset, &record);
+        Ok(Some((label.clone(), reference(&record, &label))))
+    }
+
+    /// Replace the citations in brackets in a line of text. Brackets
+    /// which don't match a record are left as they are.
+    fn cite_inline(&mut self, line: &str, line_no: u64) -> (String, Vec<String>) {
+        let mut text = String::new();
+        let mut references = Vec::new();
+        let mut rest = line;
+
+        while let Some(open) = rest.find('[') {
+            // `\[em]` and the like are escapes, not citations
+            let escaped = rest[..open].ends_with('\\');
+            let close = rest[open..].find(']').map(|c| open + c);
+            let (close, query) = match close {
+                Some(c) if !escaped => (c, &rest[open + 1..c]),
+                _ => {
+                    text.push_str(&rest[..open + 1]);
+                    rest = &rest[open + 1..];
+                    continue;
+                }
+            };
+            // only brackets which match a record are citations
+            let found = match self.index.search(query).is_empty() {
+                true => None,
+                false => self.find(query, line, line_no),
+            };
+            match found {
+                Some(offset) => {
+                    let record = self.records[&offset].clone();
+                    let label = self.label(Some(offset), &record);
+                    text.push_str(&rest[..open]);
+                    text.push_str(&format!("\\*([.{}\\*(.]", label));
+                    references.push(reference(&self.records[&offset], &label));
+                }
+                None => text.push_str(&rest[..close + 1]),
+            }
+            rest = &rest[close + 1..];
+        }
+        text.push_str(rest);
+        (text, references)
+    }
+
+    /// The offset of the record matching `query`, noting when there is
+    /// no match or more than one.
+    fn find(&mut self, query: &str, line: &str, line_no: u64) -> Option<u64> {
+        let found = self.index.search(query);
+        let message = match found.len() {
+            0 => format!("No matches for {:?}", query.trim()),
+            1 => return found.first().copied(),
+            n => format!("{} matches for {:?}, the first is used", n, query.trim()),
+        };
+        self.diagnostics
+            .push(citation_error(&message, line, line_no));
+        found.first().copied()
+    }
+
+    /// The label of `record`, which is at `offset` in the database.
+    /// Without a label format, records are numbered in the order they
+    /// are first cited. A record only given in the citation always has a
+    /// new label.
+    fn label(&mut self, offset: Option<u64>, record: &Record) -> String {
+        let n = self.labels.len() + 1;
+        let label = match (offset, &self.label_format) {
+            (Some(o), Some(_)) => self.database_labels[&o].clone(),
+            (None, Some(format)) => format.label(record, n),
+            (_, None) => n.to_string(),
+        };
+        // a record only given in the citation is keyed past the end of
+        // the database, so it is never reused
+        let key = offset.unwrap_or(u64::MAX - n as u64);
+        self.labels.entry(key).or_insert(label).clone()
+    }
+}
+
+/// The string definitions and the `.][` call for a record, as groff
+/// `refer` writes them.
+fn reference(record: &Record, label: &str) -> String {
+    let mut out = format!(".ds [F {}\n.]-\n", label);
+    let mut authors_done = false;
+    let mut editors_done = false;
+
+    for field in split_fields(&record.to_string()) {
+        let tag = match field.strip_prefix('%').and_then(|t| t.chars().next()) {
+            Some(t) => t,
+            None => continue,
+        };
+        if DISCARDED_FIELDS.contains(&tag) {
+            continue;
+        }
+        let value = match tag {
+            'A' if authors_done => continue,
+            'A' => {
+                authors_done = true;
+                join_names(record.author.iter().map(natural_name).collect())
+            }
+            'E' if editors_done => continue,
+            'E' => {
+                editors_done = true;
+                join_names(record.editor.clone())
+            }
+            _ => field[1 + tag.len_utf8()..].trim().to_string(),
+        };
+        define_string(&mut out, tag, &value);
+        match tag {
+            'A' | 'T' | 'O' => {
+                let punctuated = value.ends_with(['.', '?', '!']);
+                out.push_str(&format!(".nr [{} {}\n", tag, punctuated as u8));
+            }
+            'P' => {
+                let range = value.contains(['-', '\u{2013}']);
+                out.push_str(&format!(".nr [P {}\n", range as u8));
+            }
+            _ => (),
+        }
+    }
+
+    let (number, name) = reference_type(record);
+    out.push_str(&format!(".][ {} {}\n", number, name));
+    out
+}
+
+/// The type of reference which the `.][` macro prints, by number and
+/// name.
+fn reference_type(record: &Record) -> (u8, &'static str) {
+    match record.record_type() {
+        RecordType::Journal => (1, "journal-article"),
+        RecordType::Conference if record.journal.is_some() => (1, "journal-article"),
+        RecordType::ArticleInBook | RecordType::Conference => (3, "article-in-book"),
+        RecordType::Book => (2, "book"),
+        RecordType::Report | RecordType::Government => (4, "tech-report"),
+        RecordType::Thesis | RecordType::Misc | RecordType::None => (0, "other"),
+    }
+}
+
+/// `.ds [T value`, keeping a leading quote, which `.ds` would remove.
+fn define_string(out: &mut String, tag: char, value: &str) {
+    let quote = match value.starts_with('"') {
+        true => "\"",
+        false => "",
+    };
+    out.push_str(&format!(".ds [{} {}{}\n", tag, quote, value));
+}
+
+/// A name as it is written, e.g. `M. J. van Brown Jr.`.
+fn natural_name(author: &Author) -> String {
+    [author.rest.as_str(), &author.family_name()]
+        .into_iter()
+        .chain(author.suffix.as_deref())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Names joined as groff `refer` does by default, e.g. `A, B, and C`.
+fn join_names(names: Vec<String>) -> String {
+    match &names[..] {
+        [] => String::new(),
+        [one] => one.clone(),
+        [a, b] => format!("{} and {}", a, b),
+        [rest @ .., last] => format!("{}, and {}", rest.join(", "), last),
+    }
+}
+
+fn is_request(line: &str, name: &str) -> bool {
+    line.strip_prefix('.')
+        .and_then(|l| l.strip_prefix(name))
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+/// Whether a line is a request or a macro call, rather than text.
+fn is_control(line: &str) -> bool {
+    line.starts_with(['.', '\''])
+}
+
+/// Write a line, and then the references cited in it.
+fn flush<W: Write>(output: &mut W, pending: Option<(String, Vec<String>)>) -> Result<()> {
+    if let Some((line, references)) = pending {
+        writeln!(output, "{}", line)?;
+        for reference in references {
+            output.write_all(reference.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn citation_error(message: &str, line: &str, line_no: u64) -> Error {
+    Error::new(ErrorKind::Citation(message.to_string()))
+        .with_position(Position::in_field(line, line_no, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATABASE: &str = "\
+%A Brown, M.
+%A Twyford, A. D.
+%T Parasitic plants and their hosts
+%J New Phytologist
+%V 12
+%P 45-67
+%D 2023
+
+%A Smith, J.
+%T Eyebright genomics
+%B The Book of Plants
+%I Plant Press
+%D 2020
+%X Not in the reference
+
+%A Brown, M.
+%T A second paper.
+%R Report 12
+%I Institute
+%D 2021
+";
+
+    fn process(document: &str) -> (String, Vec<Error>) {
+        let mut preprocessor = Preprocessor::new(DATABASE.as_bytes()).unwrap();
+        let mut output = Vec::new();
+        preprocessor
+            .process(document.as_bytes(), &mut output)
+            .unwrap();
+        let diagnostics = std::mem::take(&mut preprocessor.diagnostics);
+        (String::from_utf8(output).unwrap(), diagnostics)
+    }
+
+    #[test]
+    fn citation_blocks() {
+        let document = "\
+.PP
+Hosts
+.[
+parasitic
+.]
+and eyebright
+.[[
+genomics
+.]],
+.R1
+accumulate
+.R2
+.[
+parasitic hosts
+.]
+";
+        let (output, diagnostics) = process(document);
+        assert!(diagnostics.is_empty());
+        assert_eq!(
+            output,
+            "\
+.PP
+Hosts\\*([.1\\*(.]
+.ds [F 1
+.]-
+.ds [A M. Brown and A. D. Twyford
+.nr [A 0
+.ds [D 2023
+.ds [J New Phytologist
+.ds [P 45-67
+.nr [P 1
+.ds [T Parasitic plants and their hosts
+.nr [T 0
+.ds [V 12
+.][ 1 journal-article
+.lf 6
+and eyebright[\\*([.2\\*(.]],
+.ds [F 2
+.]-
+.ds [A J. Smith
+.nr [A 0
+.ds [B The Book of Plants
+.ds [D 2020
+.ds [I Plant Press
+.ds [T Eyebright genomics
+.nr [T 0
+.][ 3 article-in-book
+.lf 10
+\\*([.1\\*(.]
+.ds [F 1
+.]-
+.ds [A M. Brown and A. D. Twyford
+.nr [A 0
+.ds [D 2023
+.ds [J New Phytologist
+.ds [P 45-67
+.nr [P 1
+.ds [T Parasitic plants and their hosts
+.nr [T 0
+.ds [V 12
+.][ 1 journal-article
+.lf 16
+"
+        );
+    }
+
+    #[test]
+    fn label_formats() {
+        let document = "\
+.R1
+label \"%a%s\"
+.R2
+See [twyford] and
+.[
+report
+.]
+.[
+%A Brown, M.
+%D 2024
+.]
+";
+        let (output, _) = process(document);
+        let labels: Vec<&str> = output
+            .lines()
+            .filter_map(|l| l.strip_prefix(".ds [F "))
+            .collect();
+        assert_eq!(labels, ["Browna", "Brownb", "Brown"]);
+        assert!(output.starts_with("See \\*([.Browna\\*(.] and\\*([.Brownb\\*(.]\n"));
+
+        let mut preprocessor = Preprocessor::new(DATABASE.as_bytes()).unwrap();
+        let err = preprocessor
+            .process(".R1\nlabel \"%q\"\n.R2\n".as_bytes(), io::sink())
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Label(_)));
+        assert_eq!(err.position().unwrap().line(), 2);
+    }
+
+    #[test]
+    fn inline_citations_and_diagnostics() {
+        let document = "See [brown report] and [1], or \\[em] [nothing here].\n.[\nbrown\n%O An extra note.\n.]\n";
+        let (output, diagnostics) = process(document);
+        let mut lines = output.lines();
+        assert_eq!(
+            lines.next(),
+            Some("See \\*([.1\\*(.] and [1], or \\[em] [nothing here].\\*([.2\\*(.]")
+        );
+        assert!(output.contains(".ds [T A second paper.\n.nr [T 1\n"));
+        assert!(output.contains(".ds [R Report 12\n"));
+        assert!(output.contains(".][ 4 tech-report\n"));
+        assert!(output.contains(".ds [O An extra note.\n.nr [O 1\n"));
+        assert!(!output.contains("Not in the reference"));
+
+        // "brown" matches two records, and the first is used
+        assert_eq!(diagnostics.len(), 1);
+        assert!(matches!(diagnostics[0].kind(), ErrorKind::Citation(_)));
+        assert_eq!(diagnostics[0].position().unwrap().line(), 2);
+
+        let (output, diagnostics) = process("text\n.[\nnothing\n.]\nmore\n");
+        assert_eq!(output, "text\n.lf 5\nmore\n");
+        assert_eq!(diagnostics.len(), 1);
+
+        let mut preprocessor = Preprocessor::new(DATABASE.as_bytes()).unwrap();
+        let err = preprocessor
+            .process(".[\nbrown\n".as_bytes(), io::sink())
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Citation(_)));
+    }
+}
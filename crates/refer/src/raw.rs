@@ -0,0 +1,395 @@
+use std::fmt::Display;
+
+use crate::{
+    error::{Error, Position, Result},
+    reader::{parse_input_line, RecordPosition},
+    record::Record,
+};
+
+/// A refer record exactly as it was written, which can be written back
+/// out byte-for-byte.
+///
+/// Where a [`Record`] only keeps the values of the fields, a raw record
+/// keeps the original text of each field (along with its continuation
+/// lines), the order the fields were in, comment lines (starting with
+/// `#`), and the blank lines before and after the record. Writing out
+/// each raw record read from a file reproduces the file exactly.
+///
+/// A raw record can be changed with [`RawRecord::update`], which only
+/// rewrites the fields whose values have changed.
+///
+/// # Example
+///
+/// ```
+/// use refer::Reader;
+///
+/// let data = "# my papers\n%T A title\n%A Brown,  M.\n\n%T Another\n";
+/// let mut rdr = Reader::new(data.as_bytes());
+/// let mut raw: Vec<_> = rdr.raw_records().collect::<Result<_, _>>().unwrap();
+///
+/// // change the title of the first record
+/// let mut record = raw[0].record().unwrap();
+/// record.title = Some("A new title".into());
+/// raw[0].update(&record);
+///
+/// let written: String = raw.iter().map(|r| r.to_string()).collect();
+/// assert_eq!(written, "# my papers\n%T A new title\n%A Brown,  M.\n\n%T Another\n");
+/// ```
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RawRecord {
+    /// The lines of the record, in order.
+    nodes: Vec<RawNode>,
+}
+
+/// A part of a [`RawRecord`].
+#[derive(Debug, PartialEq, Clone)]
+pub enum RawNode {
+    /// A field and its continuation lines.
+    Field(RawField),
+    /// A comment line, starting with `#`, including the line ending.
+    Comment(String),
+    /// A blank line (which may contain spaces), including the line ending.
+    Blank(String),
+}
+
+/// The original text of a single field.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RawField {
+    /// The text, including line endings.
+    text: String,
+    /// The line number the field starts on.
+    line: u64,
+    /// The offset in bytes of the start of the field in the input.
+    byte: u64,
+}
+
+impl RawField {
+    pub(crate) fn new(text: String, line: u64, byte: u64) -> Self {
+        RawField { text, line, byte }
+    }
+
+    /// The tag of the field, e.g. `'T'` for `%T`. This is `None` for a
+    /// line which does not start with a tag, which is not valid refer.
+    pub fn tag(&self) -> Option<char> {
+        self.text.strip_prefix('%')?.chars().next()
+    }
+
+    /// The value of the field, with any continuation lines joined on
+    /// with a space.
+    pub fn value(&self) -> String {
+        let joined = self.joined();
+        match self.tag() {
+            Some(t) => joined[1 + t.len_utf8()..].trim().to_string(),
+            None => joined,
+        }
+    }
+
+    /// The original text of the field, including line endings.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The line number the field starts on.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// The offset in bytes of the start of the field in the input.
+    pub fn byte(&self) -> u64 {
+        self.byte
+    }
+
+    /// Add a continuation line.
+    pub(crate) fn push_line(&mut self, line: &str) {
+        self.text.push_str(line);
+    }
+
+    /// The whole field on one line, as given to `parse_input_line`.
+    fn joined(&self) -> String {
+        let mut lines = self.text.lines();
+        let mut joined = lines.next().unwrap_or_default().trim_end().to_string();
+        for l in lines {
+            joined.push(' ');
+            joined.push_str(l.trim());
+        }
+        joined
+    }
+
+    /// Parse the field into a record. An error in a continuation line
+    /// is put on that line.
+    fn parse_into(&self, record: &mut Record) -> Result<()> {
+        parse_input_line(self.joined(), record, self.line).map_err(|e| {
+            let offset = e.position().map(|p| self.text_offset(p.byte() as usize));
+            let e = match offset {
+                Some(o) => e.with_position(Position::in_field(&self.text, self.line, o)),
+                None => e,
+            };
+            e.offset_by(self.byte)
+        })?;
+        Ok(())
+    }
+
+    /// The offset in the original text of the byte `offset` of the
+    /// joined field.
+    fn text_offset(&self, offset: usize) -> usize {
+        let mut joined = 0;
+        let mut start = 0;
+        for (i, line) in self.text.split_inclusive('\n').enumerate() {
+            let content = match i {
+                0 => line.trim_end(),
+                _ => {
+                    // the space the line is joined on with
+                    joined += 1;
+                    line.trim()
+                }
+            };
+            let indent = line.len() - line.trim_start().len();
+            let indent = if i == 0 { 0 } else { indent };
+            if offset <= joined + content.len() {
+                return start + indent + offset.saturating_sub(joined);
+            }
+            joined += content.len();
+            start += line.len();
+        }
+        self.text.trim_end().len()
+    }
+
+    /// Parse the field on its own into a new record.
+    fn parse_into_new(&self) -> Result<Record> {
+        let mut record = Record::default();
+        self.parse_into(&mut record)?;
+        Ok(record)
+    }
+}
+
+impl RawRecord {
+    /// The lines of the record, in order.
+    pub fn nodes(&self) -> &[RawNode] {
+        &self.nodes
+    }
+
+    /// An iterator over the fields of the record, in order.
+    pub fn fields(&self) -> impl Iterator<Item = &RawField> {
+        self.nodes.iter().filter_map(|n| match n {
+            RawNode::Field(f) => Some(f),
+            _ => None,
+        })
+    }
+
+    /// Where the fields of the record are in the input, or `None` if
+    /// there are no fields.
+    pub fn position(&self) -> Option<RecordPosition> {
+        let first = self.fields().next()?;
+        let last = self.fields().last()?;
+        let end_line = last.line + last.text.lines().count().max(1) as u64 - 1;
+        Some(RecordPosition::new(first.byte, first.line, end_line))
+    }
+
+    /// Whether the record has any fields. The blank lines and comments
+    /// at the end of a file are read as a raw record with no fields.
+    pub fn has_fields(&self) -> bool {
+        self.fields().next().is_some()
+    }
+
+    /// Parse the fields into a [`Record`].
+    pub fn record(&self) -> Result<Record> {
+        let mut record = Record::default();
+        for field in self.fields() {
+            field.parse_into(&mut record)?;
+        }
+        Ok(record)
+    }
+
+    /// Parse the fields into a [`Record`], skipping those which do not
+    /// parse, and return their errors.
+    pub(crate) fn record_recovering(&self) -> (Record, Vec<Error>) {
+        let mut record = Record::default();
+        let errors = self
+            .fields()
+            .filter_map(|field| field.parse_into(&mut record).err())
+            .collect();
+        (record, errors)
+    }
+
+    pub(crate) fn push(&mut self, node: RawNode) {
+        self.nodes.push(node);
+    }
+
+    /// The last node, if it is a field, for adding continuation lines to.
+    pub(crate) fn last_field_mut(&mut self) -> Option<&mut RawField> {
+        match self.nodes.last_mut() {
+            Some(RawNode::Field(f)) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Update the raw record to hold the values of `record`, keeping the
+    /// original text of every field whose value has not changed.
+    ///
+    /// Changed fields are rewritten in place, fields no longer in the
+    /// record are removed, and new fields are added after the last field,
+    /// formatted as in the `Display` of [`Record`]. Comments and blank
+    /// lines are kept. Fields which do not parse are left as they are.
+    pub fn update(&mut self, record: &Record) {
+        let line_ending = match self.to_string().contains("\r\n") {
+            true => "\r\n",
+            false => "\n",
+        };
+        // each new field, with the record holding only that field
+        let mut new_fields: Vec<Option<(String, Record)>> = field_texts(&record.to_string())
+            .into_iter()
+            .map(|text| {
+                let field = RawField::new(text.replace('\n', line_ending), 0, 0);
+                let single = field.parse_into_new().ok()?;
+                Some((field.text, single))
+            })
+            .collect();
+
+        // first find the fields which are unchanged, so that they are
+        // matched before any changed fields with the same tag
+        let mut unchanged = vec![false; self.nodes.len()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            let single = match node {
+                RawNode::Field(f) => f.parse_into_new(),
+                _ => continue,
+            };
+            match single {
+                // fields which don't parse, or which are empty, are kept
+                Err(_) => unchanged[i] = true,
+                Ok(r) if r == Record::default() => unchanged[i] = true,
+                Ok(r) => {
+                    let same = new_fields
+                        .iter_mut()
+                        .find(|n| matches!(n, Some((_, new)) if *new == r));
+                    if let Some(n) = same {
+                        *n = None;
+                        unchanged[i] = true;
+                    }
+                }
+            }
+        }
+
+        let mut nodes = Vec::new();
+        let mut last_field = None;
+        for (node, unchanged) in self.nodes.drain(..).zip(unchanged) {
+            let field = match node {
+                RawNode::Field(f) if !unchanged => f,
+                RawNode::Field(f) => {
+                    nodes.push(RawNode::Field(f));
+                    last_field = Some(nodes.len());
+                    continue;
+                }
+                other => {
+                    nodes.push(other);
+                    continue;
+                }
+            };
+            // a changed value is rewritten in place, otherwise the
+            // field has been removed
+            let tag = field.tag();
+            let changed = new_fields
+                .iter_mut()
+                .find(|n| matches!(n, Some((text, _)) if text[1..].chars().next() == tag));
+            if let Some((text, _)) = changed.and_then(Option::take) {
+                nodes.push(RawNode::Field(RawField::new(text, field.line, field.byte)));
+                last_field = Some(nodes.len());
+            }
+        }
+
+        // then any new fields go after the last field
+        let insert_at = last_field.unwrap_or(nodes.len());
+        let added: Vec<RawNode> = new_fields
+            .into_iter()
+            .flatten()
+            .map(|(text, _)| RawNode::Field(RawField::new(text, 0, 0)))
+            .collect();
+        if !added.is_empty() {
+            // the line before needs to end, e.g. at the end of a file
+            if let Some(RawNode::Field(f)) = insert_at.checked_sub(1).and_then(|i| nodes.get_mut(i))
+            {
+                if !f.text.ends_with('\n') {
+                    f.text.push_str(line_ending);
+                }
+            }
+        }
+        nodes.splice(insert_at..insert_at, added);
+        self.nodes = nodes;
+    }
+}
+
+/// Split refer text into the text of each field, keeping continuation
+/// lines and line endings.
+fn field_texts(input: &str) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+    for line in input.split_inclusive('\n') {
+        match fields.last_mut() {
+            Some(field) if !line.starts_with('%') => field.push_str(line),
+            _ => fields.push(line.to_string()),
+        }
+    }
+    fields
+}
+
+impl Display for RawNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RawNode::Field(field) => write!(f, "{}", field.text),
+            RawNode::Comment(text) | RawNode::Blank(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl Display for RawRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for node in &self.nodes {
+            write!(f, "{}", node)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Reader;
+
+    fn raw_records(data: &str) -> Vec<super::RawRecord> {
+        Reader::new(data.as_bytes())
+            .raw_records()
+            .collect::<crate::error::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trip_unchanged() {
+        let data = "\n\n# header comment\n%T  A title\r\n%A Brown, M.\n# note\n%X an annotation\n  which continues\n\n\n%B \n%J PNAS\n\n# trailing\n";
+        let raw = raw_records(data);
+        assert_eq!(raw.len(), 3);
+        assert!(!raw[2].has_fields());
+
+        let written: String = raw.iter().map(|r| r.to_string()).collect();
+        assert_eq!(written, data);
+    }
+
+    #[test]
+    fn update_changed_fields() {
+        let data =
+            "%T A title\n%A Brown, M.\n%A Twyford, A. D.\n%X an annotation\nwhich continues\n%V 12";
+        let mut raw = raw_records(data).remove(0);
+        let mut record = raw.record().unwrap();
+        assert_eq!(
+            record.annotation.as_deref(),
+            Some("an annotation which continues")
+        );
+
+        record.author.remove(0);
+        record.volume = Some("13".into());
+        record.journal = Some("PNAS".into());
+        raw.update(&record);
+
+        assert_eq!(
+            raw.to_string(),
+            "%T A title\n%A Twyford, A. D.\n%X an annotation\nwhich continues\n%V 13\n%J PNAS\n"
+        );
+        assert_eq!(raw.record().unwrap(), record);
+    }
+}
@@ -1,18 +1,20 @@
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_while},
-    character::is_alphabetic,
+    character::{complete::satisfy, is_alphabetic},
+    combinator::eof,
     multi::separated_list0,
     sequence::preceded,
     IResult,
 };
 
-use std::io::{self, Read};
-use std::path::Path;
+use std::io::{self, Seek};
+use std::path::{Path, PathBuf};
 use std::{fs::File, io::BufRead};
 
 use crate::{
-    error::{Error, ErrorKind, Result},
+    error::{Error, ErrorKind, Position, Result},
+    raw::{RawField, RawNode, RawRecord},
     record::{Author, Record},
     str_from_utf8,
 };
@@ -27,7 +29,7 @@ use crate::{
 ///
 /// ```
 /// use std::error::Error;
-/// use refer::{Reader, record::{Author, Record}};
+/// use refer::{Author, Reader, Record};
 ///
 /// # fn main() { example().unwrap(); }
 /// fn example() -> Result<(), Box<dyn Error>> {
@@ -38,7 +40,7 @@ use crate::{
 ///     if let Some(result) = rdr.records().next() {
 ///         let record = result?;
 ///         assert_eq!(record, Record {
-///             author: vec![Author { last: "Brown".into(), rest: "M.".into() }],
+///             author: vec![Author { last: "Brown".into(), rest: "M.".into(), ..Default::default() }],
 ///             journal: Some("PNAS".into()),
 ///             ..Default::default()
 ///         });
@@ -50,18 +52,35 @@ use crate::{
 ///
 /// ```
 ///
+/// Lines starting with `#` are comments, and are skipped. A line which
+/// does not start with a tag continues the field on the line before.
+///
 /// # Error handling
 ///
 /// Errors can arise in parsing, as records must conform to the refer specification.
 /// However, the specification is not exactly followed, and is a bit more relaxed. Most
 /// fields in a record will return `Option<String>`. For exact details on errors, please
-/// see [`Error`](enum.Error.html).
+/// see [`Error`](struct.Error.html). An error in a field has the
+/// [`Position`](struct.Position.html) of the problem, with the path of the file
+/// if the reader was made with `from_path`.
+///
+/// By default the first error in a record is returned in place of the record.
+/// A reader can instead recover from errors with
+/// [`set_recovering`](#method.set_recovering), keeping every valid field.
 ///
 pub struct Reader<R> {
     /// The underlying reader.
     rdr: io::BufReader<R>,
     /// The line number to keep track of errors if we find any.
     line: u64,
+    /// The number of bytes read so far.
+    byte: u64,
+    /// The path of the file, for errors.
+    path: Option<PathBuf>,
+    /// Whether to recover from errors in records.
+    recovering: bool,
+    /// The errors recovered from so far.
+    diagnostics: Vec<Error>,
 }
 
 impl Reader<File> {
@@ -85,7 +104,9 @@ impl Reader<File> {
     ///
     /// ```
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Reader<File>> {
-        Ok(Reader::new(File::open(path)?))
+        let mut rdr = Reader::new(File::open(path.as_ref())?);
+        rdr.path = Some(path.as_ref().to_path_buf());
+        Ok(rdr)
     }
 }
 
@@ -94,11 +115,59 @@ impl<R: io::Read> Reader<R> {
         Reader {
             rdr: io::BufReader::new(rdr),
             line: 0,
+            byte: 0,
+            path: None,
+            recovering: false,
+            diagnostics: Vec::new(),
         }
     }
 
+    /// Recover from errors in records, rather than returning them.
+    ///
+    /// Each record is read up to the blank line which ends it, so that
+    /// a bad field never affects the next record. When recovering, a
+    /// field which does not parse is skipped and the rest of its record
+    /// is kept, and invalid UTF-8 is replaced with `U+FFFD`. Every error
+    /// is kept in [`diagnostics`](#method.diagnostics). A record with no
+    /// valid fields is skipped. I/O errors are still returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use refer::Reader;
+    ///
+    /// let data = "%T One\n%A Brown,\n%D 2020\n\n%T Two\n";
+    /// let mut rdr = Reader::new(data.as_bytes());
+    /// rdr.set_recovering(true);
+    ///
+    /// let records: Vec<_> = rdr.records().collect::<Result<_, _>>().unwrap();
+    /// assert_eq!(records.len(), 2);
+    /// assert_eq!(records[0].date.as_deref(), Some("2020"));
+    ///
+    /// assert_eq!(rdr.diagnostics().len(), 1);
+    /// assert_eq!(rdr.diagnostics()[0].position().unwrap().line(), 2);
+    /// ```
+    pub fn set_recovering(&mut self, recovering: bool) {
+        self.recovering = recovering;
+    }
+
+    /// Whether the reader recovers from errors in records.
+    pub fn is_recovering(&self) -> bool {
+        self.recovering
+    }
+
+    /// The errors recovered from so far, in the order they were found.
+    pub fn diagnostics(&self) -> &[Error] {
+        &self.diagnostics
+    }
+
+    /// Take the errors recovered from so far, leaving none.
+    pub fn take_diagnostics(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
     /// A borrowed iterator over the records of a refer file.
-    pub fn records(&mut self) -> RecordsIter<R> {
+    pub fn records(&mut self) -> RecordsIter<'_, R> {
         RecordsIter::new(self)
     }
 
@@ -107,55 +176,163 @@ impl<R: io::Read> Reader<R> {
         RecordsIntoIter::new(self)
     }
 
+    /// A borrowed iterator over the raw records of a refer file, which
+    /// keep the original text of the file. See [`RawRecord`].
+    pub fn raw_records(&mut self) -> RawRecordsIter<'_, R> {
+        RawRecordsIter { rdr: self }
+    }
+
+    /// A borrowed iterator over the records of a refer file, with where
+    /// each record is in the file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use refer::Reader;
+    ///
+    /// let data = "# my papers\n%T A title\n%A Brown,\n  M.\n\n\n%T Another\n";
+    /// let mut rdr = Reader::new(data.as_bytes());
+    /// let positions: Vec<_> = rdr
+    ///     .records_with_position()
+    ///     .map(|r| r.map(|(position, _)| position))
+    ///     .collect::<Result<_, _>>()
+    ///     .unwrap();
+    ///
+    /// assert_eq!((positions[0].byte(), positions[0].line(), positions[0].end_line()), (12, 2, 4));
+    /// assert_eq!((positions[1].byte(), positions[1].line(), positions[1].end_line()), (40, 7, 7));
+    /// ```
+    pub fn records_with_position(&mut self) -> RecordsWithPositionIter<'_, R> {
+        RecordsWithPositionIter { rdr: self }
+    }
+
     /// Read a single record from an input reader.
     fn read_record(&mut self) -> Result<Option<Record>> {
-        // read the line
-        // if line is empty/only contains spaces, end read
-        // if line contains something,
-        let mut record = Record::default();
-        let reader = self.rdr.by_ref();
+        Ok(self.read_record_with_position()?.map(|(_, r)| r))
+    }
 
-        // might have to read each line separately
-        // using read_line()
-        let mut temp_buf = String::new();
+    /// Read a single record from an input reader, with where it is.
+    fn read_record_with_position(&mut self) -> Result<Option<(RecordPosition, Record)>> {
+        loop {
+            let raw = match self.read_raw_record()? {
+                Some(raw) => raw,
+                None => return Ok(None),
+            };
+            let position = match raw.position() {
+                Some(p) => p,
+                // only blank lines or comments at the end of the input
+                None => continue,
+            };
+            if self.recovering {
+                let (record, errors) = raw.record_recovering();
+                let path = self.path.as_deref();
+                self.diagnostics
+                    .extend(errors.into_iter().map(|e| e.with_path(path)));
+                if record != Record::default() {
+                    return Ok(Some((position, record)));
+                }
+            } else {
+                let record = raw
+                    .record()
+                    .map_err(|e| e.with_path(self.path.as_deref()))?;
+                return Ok(Some((position, record)));
+            }
+        }
+    }
+
+    /// Read a single raw record from an input reader. This is any blank
+    /// lines and comments before the record, the record itself, and the
+    /// blank line which ends it.
+    ///
+    /// The whole record is read even if a line is not valid UTF-8, so
+    /// that the next record is read from its start.
+    fn read_raw_record(&mut self) -> Result<Option<RawRecord>> {
+        let mut raw = RawRecord::default();
+        let mut utf8_errors = Vec::new();
 
         loop {
-            self.line += 1;
-            temp_buf.clear();
-            let bytes = reader.read_line(&mut temp_buf)?;
+            let mut line_buf = Vec::new();
+            let bytes = self.rdr.read_until(b'\n', &mut line_buf)?;
             if bytes == 0 {
                 // this is the EOF
-                if record == Record::default() {
-                    return Ok(None);
-                } else {
-                    return Ok(Some(record));
-                }
+                break;
             }
-            // we've cleared the buffer and read the line
+            self.line += 1;
+            let start = self.byte;
+            self.byte += bytes as u64;
+
+            let temp_buf = match String::from_utf8(line_buf) {
+                Ok(s) => s,
+                Err(e) => {
+                    let lossy = String::from_utf8_lossy(e.as_bytes()).into_owned();
+                    let valid = e.utf8_error().valid_up_to();
+                    let position = Position::in_field(&lossy, self.line, valid);
+                    let error = Error::new(ErrorKind::Utf8(e.utf8_error()))
+                        .with_position(position)
+                        .offset_by(start)
+                        .with_path(self.path.as_deref());
+                    utf8_errors.push(error);
+                    lossy
+                }
+            };
+
             if temp_buf.trim().is_empty() {
-                // if the record is empty AND we hit a newline,
-                // just keep going, otherwise we break becasue
-                // we already processed a record :)
-                if record == Record::default() {
-                    continue;
-                } else {
+                raw.push(RawNode::Blank(temp_buf.clone()));
+                // if we have not yet seen a field, just keep going,
+                // otherwise the blank line ends the record
+                if raw.has_fields() {
                     break;
                 }
+            } else if temp_buf.starts_with('#') {
+                raw.push(RawNode::Comment(temp_buf.clone()));
+            } else if temp_buf.starts_with('%') {
+                raw.push(RawNode::Field(RawField::new(
+                    temp_buf.clone(),
+                    self.line,
+                    start,
+                )));
+            } else if let Some(field) = raw.last_field_mut() {
+                // a line which is not a new field continues the last one
+                field.push_line(&temp_buf);
+            } else {
+                // this is not valid, and fails when the field is parsed
+                raw.push(RawNode::Field(RawField::new(
+                    temp_buf.clone(),
+                    self.line,
+                    start,
+                )));
             }
+        }
 
-            let parsed = parse_input_line(temp_buf.clone(), &mut record, self.line);
-
-            match parsed {
-                Ok(e) => match e {
-                    Some(_) => continue,
-                    None => break,
-                },
-                Err(e) => return Err(e),
+        if !utf8_errors.is_empty() {
+            if !self.recovering {
+                return Err(utf8_errors.remove(0));
             }
+            self.diagnostics.append(&mut utf8_errors);
+        }
+        if raw == RawRecord::default() {
+            return Ok(None);
         }
+        Ok(Some(raw))
+    }
+}
 
-        Ok(Some(record))
+/// Split refer text into its fields, joining any continuation lines
+/// on to the field they continue with a space. Comments are skipped.
+pub(crate) fn split_fields(input: &str) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+    for line in input
+        .lines()
+        .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+    {
+        match fields.last_mut() {
+            Some(field) if !line.starts_with('%') => {
+                field.push(' ');
+                field.push_str(line.trim());
+            }
+            _ => fields.push(line.trim_end().to_string()),
+        }
     }
+    fields
 }
 
 /// A borrowed iterator over the records of a refer file.
@@ -184,16 +361,130 @@ impl<'r, R: io::Read> Iterator for RecordsIter<'r, R> {
 
     fn next(&mut self) -> Option<Result<Record>> {
         match self.rdr.read_record() {
-            Ok(Some(r)) => {
-                self.rdr.line += 1;
-                Some(Ok(r))
-            }
+            Ok(Some(r)) => Some(Ok(r)),
             Ok(None) => None,
             Err(e) => Some(Err(e)),
         }
     }
 }
 
+impl<R: io::Read + io::Seek> Reader<R> {
+    /// Read the record which starts at the byte offset `offset`, as
+    /// given by [`Reader::records_with_position`] or an
+    /// [`Index`](index/struct.Index.html). Reading carries on from the
+    /// end of this record.
+    ///
+    /// The reader can't know which line the offset is on, so the lines of
+    /// errors and positions are counted from the offset, starting at 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use refer::Reader;
+    /// use std::io::Cursor;
+    ///
+    /// let data = "%T A title\n\n%T Another\n";
+    /// let mut rdr = Reader::new(Cursor::new(data));
+    ///
+    /// let record = rdr.read_record_at(12).unwrap().unwrap();
+    /// assert_eq!(record.title.as_deref(), Some("Another"));
+    /// let record = rdr.read_record_at(0).unwrap().unwrap();
+    /// assert_eq!(record.title.as_deref(), Some("A title"));
+    /// ```
+    pub fn read_record_at(&mut self, offset: u64) -> Result<Option<Record>> {
+        self.rdr.seek(io::SeekFrom::Start(offset))?;
+        self.line = 0;
+        self.byte = offset;
+        self.read_record()
+    }
+}
+
+/// Where a record is in its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecordPosition {
+    byte: u64,
+    line: u64,
+    end_line: u64,
+}
+
+impl RecordPosition {
+    pub(crate) fn new(byte: u64, line: u64, end_line: u64) -> Self {
+        RecordPosition {
+            byte,
+            line,
+            end_line,
+        }
+    }
+
+    /// The byte offset of the first field of the record.
+    pub fn byte(&self) -> u64 {
+        self.byte
+    }
+
+    /// The line of the first field of the record, starting from 1.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// The last line of the last field of the record.
+    pub fn end_line(&self) -> u64 {
+        self.end_line
+    }
+}
+
+/// A borrowed iterator over the records of a refer file, with their
+/// positions.
+pub struct RecordsWithPositionIter<'r, R: 'r> {
+    /// The underlying reader
+    rdr: &'r mut Reader<R>,
+}
+
+impl<'r, R: io::Read> RecordsWithPositionIter<'r, R> {
+    /// Return a reference to the underlying reader.
+    pub fn reader(&self) -> &Reader<R> {
+        self.rdr
+    }
+
+    /// Return a mutable reference to the underlying reader.
+    pub fn reader_mut(&mut self) -> &mut Reader<R> {
+        self.rdr
+    }
+}
+
+impl<'r, R: io::Read> Iterator for RecordsWithPositionIter<'r, R> {
+    type Item = Result<(RecordPosition, Record)>;
+
+    fn next(&mut self) -> Option<Result<(RecordPosition, Record)>> {
+        self.rdr.read_record_with_position().transpose()
+    }
+}
+
+/// A borrowed iterator over the raw records of a refer file.
+pub struct RawRecordsIter<'r, R: 'r> {
+    /// The underlying reader
+    rdr: &'r mut Reader<R>,
+}
+
+impl<'r, R: io::Read> RawRecordsIter<'r, R> {
+    /// Return a reference to the underlying reader.
+    pub fn reader(&self) -> &Reader<R> {
+        self.rdr
+    }
+
+    /// Return a mutable reference to the underlying reader.
+    pub fn reader_mut(&mut self) -> &mut Reader<R> {
+        self.rdr
+    }
+}
+
+impl<'r, R: io::Read> Iterator for RawRecordsIter<'r, R> {
+    type Item = Result<RawRecord>;
+
+    fn next(&mut self) -> Option<Result<RawRecord>> {
+        self.rdr.read_raw_record().transpose()
+    }
+}
+
 /// An owned iterator over the records of a refer file.
 pub struct RecordsIntoIter<R> {
     /// The underlying reader.
@@ -225,10 +516,7 @@ impl<R: io::Read> Iterator for RecordsIntoIter<R> {
 
     fn next(&mut self) -> Option<Result<Record>> {
         match self.rdr.read_record() {
-            Ok(Some(r)) => {
-                self.rdr.line += 1;
-                Some(Ok(r))
-            }
+            Ok(Some(r)) => Some(Ok(r)),
             Ok(None) => None,
             Err(e) => Some(Err(e)),
         }
@@ -292,30 +580,20 @@ pub(crate) fn parse_input_line(
     ))(bytes)
     {
         Ok(e) => e,
-        Err(e) => match e {
-            nom::Err::Incomplete(_) => {
-                return Err(Error::new(ErrorKind::NomError(format!(
-                    "At line: {}. In parsing the fields, an incomplete error was raised.",
-                    line_no
-                ))))
-            }
-            nom::Err::Error(e) => {
-                return Err(Error::new(ErrorKind::NomError(format!(
-                    "At line: {}. Parsing error with code ({}): {}",
-                    line_no,
-                    e.code.description(),
-                    str_from_utf8(e.input)?
-                ))))
-            }
-            nom::Err::Failure(e) => {
-                return Err(Error::new(ErrorKind::NomError(format!(
-                    "At line: {}. Parsing failure with code ({}): {}",
-                    line_no,
-                    e.code.description(),
-                    str_from_utf8(e.input)?
-                ))))
+        Err(e) => {
+            // a tag which is not in the specification is kept as an
+            // extension, but a known tag which failed to parse is an error
+            if let Ok((value, tag)) = parse_extension_line(bytes) {
+                if !KNOWN_TAGS.contains(tag) {
+                    let value = str_from_utf8(value)?.trim();
+                    if !value.is_empty() {
+                        record.extensions.push((tag, value.to_string()));
+                    }
+                    return Ok(Some(()));
+                }
             }
-        },
+            return Err(nom_error(e, bytes, line_no));
+        }
     };
 
     let tag = str_from_utf8(line_tag)?;
@@ -344,7 +622,10 @@ pub(crate) fn parse_input_line(
         "%V " => record.volume = Some(parsed),
         "%X " => record.annotation = Some(parsed),
         // should never get here
-        t => return Err(Error::new(ErrorKind::TagNotFound(t.to_string()))),
+        t => {
+            let position = Position::in_field(&input, line_no, 0);
+            return Err(Error::new(ErrorKind::TagNotFound(t.to_string())).with_position(position));
+        }
     }
     Ok(Some(()))
 }
@@ -354,79 +635,209 @@ fn parse_author_tag(i: &[u8]) -> IResult<&[u8], &[u8]> {
     tag("%A ")(i)
 }
 
-// Parse the author name.
-fn parse_author_name(i: &[u8]) -> IResult<&[u8], Vec<&[u8]>> {
-    let sep1 = tag(", ");
-    let sep2 = tag(" ");
-    separated_list0(
-        alt((sep1, sep2)),
-        // TODO: there are probably other edge cases here
-        take_while(|e| is_alphabetic(e) || e == b'-' || e == b'.'),
-    )(i)
+/// Words which mark a name in an author field as the name of an
+/// organisation rather than a person.
+const CORPORATE_WORDS: [&str; 24] = [
+    "organization",
+    "organisation",
+    "society",
+    "institute",
+    "institution",
+    "association",
+    "committee",
+    "council",
+    "group",
+    "consortium",
+    "foundation",
+    "university",
+    "agency",
+    "department",
+    "ministry",
+    "commission",
+    "board",
+    "centre",
+    "center",
+    "collaboration",
+    "network",
+    "bureau",
+    "corporation",
+    "office",
+];
+
+/// Suffixes which follow a name, e.g. `Smith, J., Jr.`.
+const NAME_SUFFIXES: [&str; 7] = ["jr", "sr", "ii", "iii", "iv", "2nd", "3rd"];
+
+fn is_suffix(word: &str) -> bool {
+    let word = word.trim_end_matches('.').to_lowercase();
+    NAME_SUFFIXES.contains(&word.as_str())
 }
 
-/// Parse the author line.
-fn parse_author_line(line: &[u8], line_no: u64) -> Result<Author> {
-    let (_, parsed) = match preceded(parse_author_tag, parse_author_name)(line) {
-        Ok(p) => p,
-        Err(e) => match e {
-            nom::Err::Incomplete(_) => {
-                return Err(Error::new(ErrorKind::NomError(format!(
-                    "At line: {}. In parsing the fields, an incomplete error was raised.",
-                    line_no
-                ))))
-            }
-            nom::Err::Error(e) => {
-                return Err(Error::new(ErrorKind::NomError(format!(
-                    "At line: {}. Parsing error with code ({}): {}",
-                    line_no,
-                    e.code.description(),
-                    str_from_utf8(e.input)?
-                ))))
-            }
-            nom::Err::Failure(e) => {
-                return Err(Error::new(ErrorKind::NomError(format!(
-                    "At line: {}. Parsing failure with code ({}): {}",
-                    line_no,
-                    e.code.description(),
-                    str_from_utf8(e.input)?
-                ))))
-            }
-        },
+/// Initials such as `M.`, `A.B.`, `J.-P.` or `AB`.
+fn is_initials(word: &str) -> bool {
+    let dotted = word
+        .split(['.', '-'])
+        .filter(|p| !p.is_empty())
+        .all(|p| p.chars().count() == 1 && p.chars().all(char::is_uppercase));
+    let run = word.chars().count() <= 3 && word.chars().all(char::is_uppercase);
+    word.contains(char::is_alphabetic) && (dotted || run)
+}
+
+/// Split a family name into its particle (the leading lower case words,
+/// e.g. `van der`) and the rest. The last word is never a particle.
+fn split_particle(family: &str) -> (Option<String>, String) {
+    let words: Vec<&str> = family.split_whitespace().collect();
+    let n = words
+        .iter()
+        .take(words.len().saturating_sub(1))
+        .take_while(|w| w.starts_with(char::is_lowercase))
+        .count();
+    let particle = (n > 0).then(|| words[..n].join(" "));
+    (particle, words[n..].join(" "))
+}
+
+/// Parse an author name. Names can be given as `Last, First` or
+/// `First Last` (as in groff refer), or as `Last I.`, with the initials
+/// after the family name. A suffix such as `Jr.` follows the name after a
+/// comma. Lower case words before the family name are a particle, e.g.
+/// `van der Berg, P.` or `Ludwig van Beethoven`. Names which contain
+/// words such as "Society" or "Organization" are corporate names.
+pub(crate) fn parse_author_name(name: &str) -> Result<Author> {
+    let author_error = |message: &str| {
+        Error::new(ErrorKind::Author(format!(
+            "Input error: {}. {}",
+            name, message
+        )))
     };
+    let parts: Vec<&str> = name.split(',').map(str::trim).collect();
+    if parts.iter().any(|p| p.is_empty()) {
+        return Err(author_error("Names separated by a comma can't be empty"));
+    }
 
-    match parsed.len() {
-        0..=1 => {
-            return Err(Error::new(ErrorKind::Author(format!(
-                "Input error: {}. `Number of names should be of length 2 or more, found 0 or 1",
-                str_from_utf8(line)?
-            ))))
-        }
-        2 => Ok(Author {
-            last: str_from_utf8(parsed[0])?.to_owned(),
-            rest: str_from_utf8(parsed[1])?.to_owned(),
-        }),
-        n @ 3.. => {
-            let mut rest = String::new();
-            for el in parsed.iter().take(n).skip(1) {
-                rest += str_from_utf8(el)?;
-                rest += " ";
+    // the family name with any particle, the given names, and any suffix
+    let (family, rest, suffix) = match parts[..] {
+        [whole] => {
+            let words: Vec<&str> = whole.split_whitespace().collect();
+            let is_corporate = words.iter().any(|w| {
+                let w = w
+                    .trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_lowercase();
+                CORPORATE_WORDS.contains(&w.as_str())
+            });
+            if is_corporate {
+                return Ok(Author {
+                    last: words.join(" "),
+                    corporate: true,
+                    ..Default::default()
+                });
             }
-            rest.pop();
-
-            Ok(Author {
-                last: str_from_utf8(parsed[0])?.to_owned(),
-                rest,
-            })
+            return first_last(&words, None).ok_or_else(|| author_error("A name can't be empty"));
         }
-        e => {
-            return Err(Error::new(ErrorKind::Author(format!(
-                "Input error: {}. `Number of names should be of length 2 or more, found {}",
-                str_from_utf8(line)?,
-                e
-            ))))
+        [first_last_form, suffix] if is_suffix(suffix) => {
+            let words: Vec<&str> = first_last_form.split_whitespace().collect();
+            return first_last(&words, Some(suffix.to_string()))
+                .ok_or_else(|| author_error("A name can't be empty"));
         }
+        [family, rest] => (family, rest, None),
+        [family, rest, suffix] if is_suffix(suffix) => (family, rest, Some(suffix)),
+        // as in BibTeX, `von Last, Jr, First`
+        [family, suffix, rest] if is_suffix(suffix) => (family, rest, Some(suffix)),
+        _ => return Err(author_error("Too many commas in the name")),
+    };
+
+    let (particle, last) = split_particle(family);
+    Ok(Author {
+        last,
+        rest: rest.split_whitespace().collect::<Vec<_>>().join(" "),
+        particle,
+        suffix: suffix.map(String::from),
+        corporate: false,
+    })
+}
+
+/// A name without commas: `First von Last`, `Last I.` where all but the
+/// first word are initials, or a single name such as `Smith`, which groff
+/// refer also allows. Returns `None` if there is no name.
+fn first_last(words: &[&str], mut suffix: Option<String>) -> Option<Author> {
+    let mut words = words.to_vec();
+    // a suffix without a comma, e.g. `John Smith Jr.`
+    if suffix.is_none() && words.len() > 2 && words.last().is_some_and(|w| is_suffix(w)) {
+        suffix = words.pop().map(String::from);
+    }
+    if words.len() < 2 {
+        return Some(Author {
+            last: words.first()?.to_string(),
+            suffix,
+            ..Default::default()
+        });
+    }
+    if words[1..].iter().all(|w| is_initials(w)) {
+        return Some(Author {
+            last: words[0].to_string(),
+            rest: words[1..].join(" "),
+            suffix,
+            ..Default::default()
+        });
     }
+
+    let (given, family) = words.split_at(words.len() - 1);
+    let n_given = given
+        .iter()
+        .take_while(|w| !w.starts_with(char::is_lowercase))
+        .count();
+    let particle = given[n_given..].join(" ");
+    Some(Author {
+        last: family[0].to_string(),
+        rest: given[..n_given].join(" "),
+        particle: (!particle.is_empty()).then_some(particle),
+        suffix,
+        corporate: false,
+    })
+}
+
+/// Parse the author line.
+fn parse_author_line(line: &[u8], line_no: u64) -> Result<Author> {
+    let (name, _) = parse_author_tag(line).map_err(|e| nom_error(e, line, line_no))?;
+
+    let name = str_from_utf8(name)?;
+    // the error is put at the start of the name
+    let offset = line.len() - name.trim_start().len();
+    parse_author_name(name.trim()).map_err(|e| {
+        let position = Position::in_field(&String::from_utf8_lossy(line), line_no, offset);
+        e.with_position(position)
+    })
+}
+
+/// An error from a nom parser of the field `field`, put where the
+/// parser stopped.
+fn nom_error(e: nom::Err<nom::error::Error<&[u8]>>, field: &[u8], line_no: u64) -> Error {
+    let (message, rest) = match e {
+        nom::Err::Incomplete(_) => (
+            "In parsing the fields, an incomplete error was raised.".to_string(),
+            field,
+        ),
+        nom::Err::Error(e) => (
+            format!(
+                "Parsing error with code ({}): {}",
+                e.code.description(),
+                String::from_utf8_lossy(e.input)
+            ),
+            e.input,
+        ),
+        nom::Err::Failure(e) => (
+            format!(
+                "Parsing failure with code ({}): {}",
+                e.code.description(),
+                String::from_utf8_lossy(e.input)
+            ),
+            e.input,
+        ),
+    };
+    let position = Position::in_field(
+        &String::from_utf8_lossy(field),
+        line_no,
+        field.len() - rest.len(),
+    );
+    Error::new(ErrorKind::NomError(message)).with_position(position)
 }
 
 /// Parse the book title.
@@ -553,6 +964,17 @@ fn parse_volume_line(i: &[u8]) -> IResult<&[u8], &[u8]> {
     tag("%V ")(i)
 }
 
+/// All the tags in the refer specification.
+const KNOWN_TAGS: &str = "ABCDEGIJKLNOPQRSTVX";
+
+/// Parse a line with any other tag, `%` followed by a single letter or
+/// digit, and then a space or the end of the line. Returns the tag.
+fn parse_extension_line(i: &[u8]) -> IResult<&[u8], char> {
+    let (i, t) = preceded(tag("%"), satisfy(|c| c.is_ascii_alphanumeric()))(i)?;
+    let (i, _) = alt((tag(" "), tag("\t"), tag("\n"), eof))(i)?;
+    Ok((i, t))
+}
+
 /// Parse annotation line.
 fn parse_annotation_line(i: &[u8]) -> IResult<&[u8], &[u8]> {
     tag("%X ")(i)
@@ -574,28 +996,86 @@ mod tests {
 
     #[test]
     fn test_parse_author_name() {
-        let author_string = b"Carter-Brown, M.";
-        let (_, y) = parse_author_name(author_string).unwrap();
-        let res: Vec<&str> = y.iter().map(|e| std::str::from_utf8(e).unwrap()).collect();
+        let author = parse_author_name("Carter-Brown, M.").unwrap();
 
-        assert_eq!(vec!["Carter-Brown", "M."], res)
+        assert_eq!(author.last, "Carter-Brown");
+        assert_eq!(author.rest, "M.");
     }
 
     #[test]
     fn test_parse_author_line() {
         let author_string = b"%A Carter-Brown, M.";
         let parsed = parse_author_line(author_string, 1).unwrap();
-
         assert_eq!(parsed.rest, "M.");
         assert_eq!(parsed.last, "Carter-Brown");
     }
 
+    #[test]
+    fn test_parse_author_names() {
+        let parse = |s| parse_author_name(s).unwrap();
+
+        let a = parse("Müller, J.");
+        assert_eq!((a.last.as_str(), a.rest.as_str()), ("Müller", "J."));
+
+        let a = parse("O'Brien, K.");
+        assert_eq!(a.last, "O'Brien");
+
+        let a = parse("van der Berg, P.");
+        assert_eq!(a.particle.as_deref(), Some("van der"));
+        assert_eq!(a.last, "Berg");
+        assert_eq!(a.family_name(), "van der Berg");
+
+        let a = parse("Smith, J., Jr.");
+        assert_eq!(a.suffix.as_deref(), Some("Jr."));
+        assert_eq!(a.rest, "J.");
+        assert_eq!(a, parse("Smith, Jr., J."));
+        assert_eq!(a, parse("J. Smith, Jr."));
+        assert_eq!(a, parse("J. Smith Jr."));
+
+        let a = parse("Brian W. Kernighan");
+        assert_eq!(
+            (a.last.as_str(), a.rest.as_str()),
+            ("Kernighan", "Brian W.")
+        );
+
+        let a = parse("Ludwig van Beethoven");
+        assert_eq!(a.particle.as_deref(), Some("van"));
+        assert_eq!(a.last, "Beethoven");
+
+        let a = parse("Brown AB");
+        assert_eq!((a.last.as_str(), a.rest.as_str()), ("Brown", "AB"));
+
+        let a = parse("World Health Organization");
+        assert!(a.corporate);
+        assert_eq!(a.last, "World Health Organization");
+
+        assert!(parse_author_name("Brown,").is_err());
+        assert!(parse_author_name("a, b, c").is_err());
+    }
+
+    #[test]
+    fn test_author_round_trip() {
+        for name in [
+            "%A van der Berg, P., Jr.",
+            "%A Müller, J.",
+            "%A World Health Organization",
+            "%A Smith",
+            "%A Smith, Jr.",
+            "%A van der Berg",
+        ] {
+            let mut record = Record::default();
+            parse_input_line(name.into(), &mut record, 1).unwrap();
+            assert_eq!(record.author[0].to_string(), name);
+        }
+    }
+
     #[test]
     fn test_parse_author_line_one() {
         let author_string = b"%A Carter-Brown";
-        let parsed = parse_author_line(author_string, 1);
+        let parsed = parse_author_line(author_string, 1).unwrap();
 
-        assert!(parsed.is_err())
+        assert_eq!(parsed.last, "Carter-Brown");
+        assert_eq!(parsed.rest, "");
     }
 
     #[test]
@@ -626,6 +1106,184 @@ mod tests {
         assert_eq!(expected_record, record);
     }
 
+    #[test]
+    fn test_continuation_lines() {
+        let input = "%T a title which\ncontinues\n  on to a third line\n%K one\ntwo\n\n%T next\n";
+        let records: Vec<Record> = Reader::new(input.as_bytes())
+            .records()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(
+            records[0].title.as_deref(),
+            Some("a title which continues on to a third line")
+        );
+        assert_eq!(records[0].keywords, Some(vec!["one".into(), "two".into()]));
+        assert_eq!(records[1].title.as_deref(), Some("next"));
+    }
+
+    #[test]
+    fn test_extension_tags() {
+        let input = "%A Brown, M.\n%U https://example.com\n%Z one\n%U http://other\n";
+        let record = Reader::new(input.as_bytes())
+            .records()
+            .next()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            record.extensions,
+            vec![
+                ('U', "https://example.com".to_string()),
+                ('Z', "one".to_string()),
+                ('U', "http://other".to_string())
+            ]
+        );
+        assert_eq!(record.extension('U'), Some("https://example.com"));
+        assert_eq!(record.extension('T'), None);
+        assert_eq!(
+            record.to_string(),
+            "%A Brown, M.\n%U https://example.com\n%Z one\n%U http://other\n"
+        );
+
+        assert!(parse_input_line("%? bad".into(), &mut Record::default(), 0).is_err());
+    }
+
+    #[test]
+    fn test_malformed_known_tags() {
+        // a known tag is never kept as an extension
+        for line in ["%T", "%Ttitle", "%T\ttitle", "%K\tone two", "%E\tBrown"] {
+            let mut record = Record::default();
+            let err = parse_input_line(line.into(), &mut record, 1).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::NomError(_)), "{:?}", line);
+            assert!(record.extensions.is_empty());
+        }
+        let input = "%A Brown, M.\n%T\tA title\n";
+        assert!(Reader::new(input.as_bytes())
+            .records()
+            .next()
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_error_position() {
+        // the line count must not drift over the records before the error
+        let input = "%T one\n%A Brown, M.\n\n# a comment\n%T two\n\n%T three\n%A  Carter-Brown,\n";
+        let errors: Vec<Error> = Reader::new(input.as_bytes())
+            .records()
+            .filter_map(|r| r.err())
+            .collect();
+        assert_eq!(errors.len(), 1);
+        let position = errors[0].position().unwrap();
+        assert_eq!(position.line(), 8);
+        assert_eq!(position.column(), 5);
+        assert_eq!(position.byte(), input.find("Carter").unwrap() as u64);
+        assert_eq!(position.tag(), Some('A'));
+        assert_eq!(position.source_line(), Some("%A  Carter-Brown,"));
+
+        // an error in a continuation line is put on that line
+        let input = "%T one\n%A\n  Carter-Brown,\n";
+        let err = Reader::new(input.as_bytes())
+            .records()
+            .next()
+            .unwrap()
+            .unwrap_err();
+        let position = err.position().unwrap();
+        assert_eq!((position.line(), position.column()), (3, 3));
+        assert_eq!(position.byte(), input.find("Carter").unwrap() as u64);
+        assert_eq!(position.source_line(), Some("  Carter-Brown,"));
+
+        let err = parse_input_line("%? bad".into(), &mut Record::default(), 3).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NomError(_)));
+        assert_eq!(err.position().map(|p| (p.line(), p.column())), Some((3, 1)));
+    }
+
+    #[test]
+    fn test_render_error() {
+        let path = std::env::temp_dir().join("refer_test_render_error.refer");
+        std::fs::write(&path, "%T one\n\n%T two\n%Z\n%-bad\n").unwrap();
+        let err = Reader::from_path(&path)
+            .unwrap()
+            .records()
+            .find_map(|r| r.err())
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let position = err.position().unwrap();
+        assert_eq!(position.path(), Some(path.as_path()));
+        assert_eq!(
+            err.render(),
+            format!(
+                "{}\n --> {}:5:1\n  |\n5 | %-bad\n  | ^^^^^\n",
+                "error[%-]: refer parsing error using nom: \"Parsing error with code (Tag): %-bad\"",
+                path.display()
+            )
+        );
+        assert!(err
+            .to_string()
+            .ends_with(&format!(" at {}:5:1", path.display())));
+    }
+
+    #[test]
+    fn test_recovering() {
+        let mut input = b"%T one\n%A Brown,\n%D 2020\n\n%-bad\n\n".to_vec();
+        input.extend_from_slice(b"%T tw\xffo\n%J PNAS\n\n%T three\n");
+
+        // without recovering, the rest of a bad record is skipped
+        let results: Vec<Result<Record>> = Reader::new(&input[..]).records().collect();
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_err() && results[1].is_err());
+        assert!(matches!(
+            results[2].as_ref().unwrap_err().kind(),
+            ErrorKind::Utf8(_)
+        ));
+        assert_eq!(results[3].as_ref().unwrap().title.as_deref(), Some("three"));
+
+        let mut rdr = Reader::new(&input[..]);
+        rdr.set_recovering(true);
+        let records: Vec<Record> = rdr.records().collect::<Result<_>>().unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].title.as_deref(), Some("one"));
+        assert_eq!(records[0].date.as_deref(), Some("2020"));
+        assert_eq!(records[1].title.as_deref(), Some("tw\u{fffd}o"));
+        assert_eq!(records[1].journal.as_deref(), Some("PNAS"));
+
+        let lines: Vec<(u64, u64)> = rdr
+            .diagnostics()
+            .iter()
+            .map(|e| e.position().map(|p| (p.line(), p.column())).unwrap())
+            .collect();
+        assert_eq!(lines, [(2, 4), (5, 1), (7, 6)]);
+        assert_eq!(rdr.take_diagnostics().len(), 3);
+        assert!(rdr.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_record_positions() {
+        let input =
+            "%T one\r\n%A Brown, M.\r\n\r\n# two\r\n%T two\r\ncontinued\r\n\r\n%T three\r\n";
+        let mut rdr = Reader::new(io::Cursor::new(input));
+        let positions: Vec<RecordPosition> = rdr
+            .records_with_position()
+            .map(|r| r.map(|(p, _)| p))
+            .collect::<Result<_>>()
+            .unwrap();
+        let expected = [
+            RecordPosition::new(0, 1, 2),
+            RecordPosition::new(input.find("%T two").unwrap() as u64, 5, 6),
+            RecordPosition::new(input.find("%T three").unwrap() as u64, 8, 8),
+        ];
+        assert_eq!(positions, expected);
+
+        // reading carries on after a record read at an offset
+        let record = rdr.read_record_at(positions[1].byte()).unwrap().unwrap();
+        assert_eq!(record.title.as_deref(), Some("two continued"));
+        let record = rdr.read_record().unwrap().unwrap();
+        assert_eq!(record.title.as_deref(), Some("three"));
+        assert!(rdr.read_record().unwrap().is_none());
+    }
+
     #[test]
     fn test_reader_1() {
         let mut reader = Reader::new("%A Brown, M.\n%T a title\n".as_bytes());
@@ -635,6 +1293,7 @@ mod tests {
             author: vec![Author {
                 last: "Brown".into(),
                 rest: "M.".into(),
+                ..Default::default()
             }],
             title: Some("a title".into()),
             ..Default::default()
@@ -1,87 +1,234 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
-use crate::error::{Error, ErrorKind, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-/// The type of the record, only books and journals are
-/// supported for formatting. See [`crate::style::StyleBuilder`].
-#[derive(Default, Debug, PartialEq, Clone)]
+use crate::{
+    date::Date,
+    error::{Error, Result},
+    pages::PageRange,
+    reader::parse_author_name,
+};
+
+/// The type of the record. Unless it is set on the record, the type is
+/// inferred from the fields, see [`Record::record_type`]. Every type can
+/// be formatted by every style, see [`crate::style::StyleBuilder`].
+///
+/// With the `serde` feature, the variants are encoded as kebab case
+/// strings, e.g. `"none"`, `"journal"` and `"article-in-book"`.
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 pub enum RecordType {
+    /// Not set, the type is inferred from the fields.
     #[default]
     None,
+    /// A book.
     Book,
+    /// An article in a journal.
     Journal,
+    /// An article (or chapter) in an edited book.
+    ArticleInBook,
+    /// A technical report.
+    Report,
+    /// A government document.
+    Government,
+    /// A thesis or dissertation.
+    Thesis,
+    /// A paper in the proceedings of a conference.
+    Conference,
+    /// Anything else, e.g. unpublished works.
+    Misc,
 }
 
+/// Words in %B or %J which mark a conference paper.
+const CONFERENCE_WORDS: [&str; 4] = ["conference", "symposium", "workshop", "proceedings"];
+
+/// Words in %R or %O which mark a thesis.
+const THESIS_WORDS: [&str; 2] = ["thesis", "dissertation"];
+
 /// A refer record.
 ///
 /// The record is exhaustive to the refer specification.
+///
+/// # Serde
+///
+/// With the `serde` feature, a record is encoded as a map using the field
+/// names below. Fields which are `None` or empty are left out, and are
+/// filled with their defaults when missing on decoding, so a record with
+/// only a title decodes from `{"title": "..."}`. `rec_type` is always
+/// written. Authors are maps of `last` and `rest`, along with `particle`,
+/// `suffix` and `corporate` when they are set, keywords and editors are
+/// lists of strings, `extensions` is a list of `[tag, value]` pairs, and
+/// `rec_type` is described in [`RecordType`]. As JSON:
+///
+/// ```json
+/// {
+///   "author": [{ "last": "Brown", "rest": "M." }],
+///   "journal": "PNAS",
+///   "keywords": ["plants", "parasites"],
+///   "rec_type": "journal",
+///   "extensions": [["U", "https://example.com"]]
+/// }
+/// ```
 #[derive(Default, Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Record {
     // TODO: this should probably be option<Vec<..>>
     /// The author list
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
     pub author: Vec<Author>,
     /// The name of the book
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub book: Option<String>,
     /// The place
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub place: Option<String>,
-    /// Date of publication
+    /// Date of publication, see [`Record::parsed_date`]
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub date: Option<String>,
     /// The editor
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
     pub editor: Vec<String>,
     /// US Government ordering number.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub government: Option<String>,
     /// The publisher (issuer)
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub issuer: Option<String>,
     /// For an article in a journal, the name of the journal.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub journal: Option<String>,
     /// Keywords to be used for searching.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub keywords: Option<Vec<String>>,
     /// Label.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub label: Option<String>,
     // Journal issue number
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub issue_number: Option<String>,
-    /// Page number. A range of pages can be specified as m-n.
-    // probably needs to be parsed fully e.g. 1-100
+    /// Page number. A range of pages can be specified as m-n, see
+    /// [`Record::parsed_pages`].
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub page_number: Option<String>,
     /// Other information. This is usually printed at the end of the reference.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub other: Option<String>,
     /// The name of the author, if the author is not a person. This will only be used if there are no %A fields. There can only be one %Q field.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub author_np: Option<String>,
     /// Technical report number.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub report: Option<String>,
     /// Series name.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub series: Option<String>,
     /// Title. For an article in a book or journal, this should be the title of the article.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub title: Option<String>,
     /// Volume number of the journal or book.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub volume: Option<String>,
     /// Annotation.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub annotation: Option<String>,
     /// The type of the record, the default is [`RecordType::None`].
     pub rec_type: RecordType,
+    /// Fields with tags which are not in the refer specification (e.g.
+    /// `%U` for a URL), as `(tag, value)` in the order they were read.
+    /// A tag can appear more than once.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub extensions: Vec<(char, String)>,
 }
 
 impl Record {
-    /// Return the record type of the record. At the moment, only
-    /// books and journal types are supported.
+    /// The value of the first field with a tag which is not in the
+    /// refer specification, e.g. `record.extension('U')` for `%U`.
+    pub fn extension(&self, tag: char) -> Option<&str> {
+        self.extensions(tag).next()
+    }
+
+    /// The values of all the fields with a tag which is not in the
+    /// refer specification, in the order they were read.
+    pub fn extensions(&self, tag: char) -> impl Iterator<Item = &str> {
+        self.extensions
+            .iter()
+            .filter(move |(t, _)| *t == tag)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The date of publication parsed from the %D field. A record with
+    /// no date has [`Date::NoDate`], and a date which can't be parsed is
+    /// an error.
+    pub fn parsed_date(&self) -> Result<Date> {
+        match &self.date {
+            Some(d) => d.parse(),
+            None => Ok(Date::NoDate),
+        }
+    }
+
+    /// The page range parsed from the %P field. A range which can't be
+    /// parsed, or which ends before it starts, is an error.
+    pub fn parsed_pages(&self) -> Result<Option<PageRange>> {
+        self.page_number.as_deref().map(str::parse).transpose()
+    }
+
+    /// Check the fields which have a structure, the date (%D) and the
+    /// pages (%P). A date or pages which can't be parsed, or a range
+    /// which ends before it starts, is an error. The styles write pages
+    /// which can't be parsed as they are, so this is how to find them.
+    pub fn validate(&self) -> Result<()> {
+        self.parsed_date()?;
+        self.parsed_pages()?;
+        Ok(())
+    }
+
+    /// Return the record type of the record. This is `rec_type` if it
+    /// is set, otherwise the type is inferred as groff refer does: %J is
+    /// a journal article, %B an article in a book, %R a technical report,
+    /// %G a government document, and %I a book. Anything else is
+    /// [`RecordType::Misc`].
     ///
-    /// Note that the %B field must be non empty (even a space will do)
-    /// for a record to be treated as a book. This is also the case for
-    /// %J, but usually in journal articles the journal is always present,
-    /// whereas a book may not always contain citable articles within.
-    pub fn record_type(&self) -> Result<RecordType> {
-        let book = self.book.is_some();
-        let journal = self.journal.is_some();
-
-        match (book, journal) {
-            (true, true) => Err(Error::new(ErrorKind::RecordType(
-                "Book and Journal both set".into(),
-            ))),
-            (true, false) => Ok(RecordType::Book),
-            (false, true) => Ok(RecordType::Journal),
-            (false, false) => Err(Error::new(ErrorKind::RecordType(
-                "Neither Book nor Journal are set".into(),
-            ))),
+    /// A %B which is empty (even a space will do), or which has no %T,
+    /// marks the record as a book rather than an article in one. A %R
+    /// or %O mentioning a thesis or dissertation marks a thesis, and a
+    /// %B or %J naming a conference, symposium or workshop (or a %B of
+    /// proceedings) marks a conference paper.
+    pub fn record_type(&self) -> RecordType {
+        if self.rec_type != RecordType::None {
+            return self.rec_type;
+        }
+        let mentions = |field: &Option<String>, words: &[&str]| {
+            field.as_deref().is_some_and(|f| {
+                let f = f.to_lowercase();
+                words.iter().any(|w| f.contains(w))
+            })
+        };
+
+        if mentions(&self.report, &THESIS_WORDS) || mentions(&self.other, &THESIS_WORDS) {
+            RecordType::Thesis
+        } else if self.journal.is_some() {
+            // "Proceedings of the National Academy of Sciences" is a journal
+            match mentions(&self.journal, &CONFERENCE_WORDS[..3]) {
+                true => RecordType::Conference,
+                false => RecordType::Journal,
+            }
+        } else if let Some(book) = &self.book {
+            match (book.trim().is_empty(), self.title.is_some()) {
+                (false, true) if mentions(&self.book, &CONFERENCE_WORDS) => RecordType::Conference,
+                (false, true) => RecordType::ArticleInBook,
+                _ => RecordType::Book,
+            }
+        } else if self.report.is_some() {
+            RecordType::Report
+        } else if self.government.is_some() {
+            RecordType::Government
+        } else if self.issuer.is_some() {
+            RecordType::Book
+        } else {
+            RecordType::Misc
         }
     }
 }
@@ -90,18 +237,110 @@ impl Record {
 /// multiple fields, and they are in a specific format.
 ///
 /// e.g. Brown, A. B.
-#[derive(Debug, PartialEq, Clone)]
+///
+/// Names can be given as `Last, First` or `First Last`, with particles
+/// (`van der Berg, P.`), suffixes (`Smith, J., Jr.`) and corporate names
+/// (`World Health Organization`). An author can be parsed from a string
+/// with [`str::parse`], and is always written in the `Last, First` form.
+///
+/// ```
+/// use refer::Author;
+///
+/// let author: Author = "Ludwig van Beethoven".parse().unwrap();
+/// assert_eq!(author.family_name(), "van Beethoven");
+/// assert_eq!(author.to_string(), "%A van Beethoven, Ludwig");
+/// ```
+#[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Author {
-    /// Last name
+    /// Last name, without any particle. For a corporate name this is
+    /// the whole name.
     pub last: String,
-    /// And the rest of the form: A. B. C.
+    /// And the rest of the form: A. B. C. These are the given names or
+    /// initials.
     pub rest: String,
+    /// A particle before the last name, e.g. `van der` or `de la`.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub particle: Option<String>,
+    /// A suffix after the name, e.g. `Jr.` or `III`.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub suffix: Option<String>,
+    /// Whether this is the name of an organisation rather than a person.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub corporate: bool,
+}
+
+impl Author {
+    /// The last name along with any particle, e.g. `van der Berg`.
+    pub fn family_name(&self) -> String {
+        match &self.particle {
+            Some(p) => format!("{} {}", p, self.last),
+            None => self.last.clone(),
+        }
+    }
+
+    /// The whole name as it is written in a field, e.g.
+    /// `van der Berg, P., Jr.`.
+    pub(crate) fn full_name(&self) -> String {
+        let mut name = self.family_name();
+        for part in std::iter::once(&self.rest).chain(&self.suffix) {
+            if !part.is_empty() {
+                name.push_str(", ");
+                name.push_str(part);
+            }
+        }
+        name
+    }
+}
+
+impl FromStr for Author {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_author_name(s.trim())
+    }
 }
 
 impl Display for Author {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "%A {}, {}", self.last, self.rest)
+        write!(f, "%A {}", self.full_name())
+    }
+}
+
+/// Values longer than this are wrapped on to continuation lines.
+const MAX_LINE_WIDTH: usize = 80;
+
+/// Write a field, wrapping long values on to continuation lines at word
+/// boundaries. A continuation line never starts with a `%` or a `#`, as
+/// it would be read back as a new field or a comment. The spaces between
+/// words are kept, other than where the line wraps.
+fn write_field(f: &mut std::fmt::Formatter, tag: &str, value: &str) -> std::fmt::Result {
+    let mut line = String::from(tag);
+    let mut rest = value.trim();
+    let mut space = " ";
+    while !rest.is_empty() {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..end];
+        // a line break in the value would start a new line
+        if space.contains(['\n', '\r']) {
+            space = " ";
+        }
+        if line.len() + space.len() + word.len() > MAX_LINE_WIDTH
+            && line != tag
+            && !word.starts_with(['%', '#'])
+        {
+            writeln!(f, "{}", line)?;
+            line.clear();
+        } else {
+            line.push_str(space);
+        }
+        line.push_str(word);
+        let next = rest[end..].trim_start();
+        space = &rest[end..rest.len() - next.len()];
+        rest = next;
     }
+    writeln!(f, "{}", line)
 }
 
 impl Display for Record {
@@ -121,45 +360,36 @@ impl Display for Record {
 
         // %B line
         match &self.book {
-            Some(b) => writeln!(f, "%B {}", b)?,
+            Some(b) => write_field(f, "%B", b)?,
             None => write!(f, "")?,
         };
         // %C line
         match &self.place {
-            Some(p) => writeln!(f, "%C {}", p)?,
+            Some(p) => write_field(f, "%C", p)?,
             None => write!(f, "")?,
         };
         // %D line
         match &self.date {
-            Some(d) => writeln!(f, "%D {}", d)?,
+            Some(d) => write_field(f, "%D", d)?,
             None => write!(f, "")?,
         };
         // %E line
-        match &self.editor.is_empty() {
-            true => write!(f, "")?,
-            false => {
-                let mut editor_list = String::new();
-                for el in &self.editor {
-                    editor_list += "%E ";
-                    editor_list += el;
-                    editor_list += "\n";
-                }
-                write!(f, "{}", editor_list)?
-            }
-        };
+        for el in &self.editor {
+            write_field(f, "%E", el)?;
+        }
         // %G line
         match &self.government {
-            Some(g) => writeln!(f, "%G {}", g)?,
+            Some(g) => write_field(f, "%G", g)?,
             None => write!(f, "")?,
         };
         // %I line
         match &self.issuer {
-            Some(i) => writeln!(f, "%I {}", i)?,
+            Some(i) => write_field(f, "%I", i)?,
             None => write!(f, "")?,
         };
         // %J line
         match &self.journal {
-            Some(j) => writeln!(f, "%J {}", j)?,
+            Some(j) => write_field(f, "%J", j)?,
             None => write!(f, "")?,
         };
         // %K line, all formatted with a space in between
@@ -171,61 +401,172 @@ impl Display for Record {
                     keywords += " ";
                 }
                 keywords.pop();
-                writeln!(f, "%K {}", keywords)?
+                write_field(f, "%K", &keywords)?
             }
             None => write!(f, "")?,
         };
         // %L line
         match &self.label {
-            Some(l) => writeln!(f, "%L {}", l)?,
+            Some(l) => write_field(f, "%L", l)?,
             None => write!(f, "")?,
         };
         // %N line
         match &self.issue_number {
-            Some(i) => writeln!(f, "%N {}", i)?,
+            Some(i) => write_field(f, "%N", i)?,
             None => write!(f, "")?,
         };
         // %O line
         match &self.other {
-            Some(o) => writeln!(f, "%O {}", o)?,
+            Some(o) => write_field(f, "%O", o)?,
             None => write!(f, "")?,
         };
         // %P line
         match &self.page_number {
-            Some(p) => writeln!(f, "%P {}", p)?,
+            Some(p) => write_field(f, "%P", p)?,
             None => write!(f, "")?,
         };
         // %Q line
         match &self.author_np {
-            Some(q) => writeln!(f, "%Q {}", q)?,
+            Some(q) => write_field(f, "%Q", q)?,
             None => write!(f, "")?,
         };
         // %R line
         match &self.report {
-            Some(r) => writeln!(f, "%R {}", r)?,
+            Some(r) => write_field(f, "%R", r)?,
             None => write!(f, "")?,
         };
         // %S line
         match &self.series {
-            Some(s) => writeln!(f, "%S {}", s)?,
+            Some(s) => write_field(f, "%S", s)?,
             None => write!(f, "")?,
         };
         // %T line
         match &self.title {
-            Some(t) => writeln!(f, "%T {}", t)?,
+            Some(t) => write_field(f, "%T", t)?,
             None => write!(f, "")?,
         };
         // %V line
         match &self.volume {
-            Some(v) => writeln!(f, "%V {}", v)?,
+            Some(v) => write_field(f, "%V", v)?,
             None => write!(f, "")?,
         };
         // %X line
         match &self.annotation {
-            Some(x) => writeln!(f, "%X {}", x)?,
+            Some(x) => write_field(f, "%X", x)?,
             None => write!(f, "")?,
         };
+        // any other tags, in the order they were read
+        for (tag, value) in &self.extensions {
+            write_field(f, &format!("%{}", tag), value)?;
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infer_record_type() {
+        let record = |fields: &[(char, &str)]| {
+            let mut record = Record::default();
+            for (tag, value) in fields {
+                let value = Some(value.to_string());
+                match tag {
+                    'B' => record.book = value,
+                    'G' => record.government = value,
+                    'I' => record.issuer = value,
+                    'J' => record.journal = value,
+                    'R' => record.report = value,
+                    'T' => record.title = value,
+                    _ => unreachable!(),
+                }
+            }
+            record.record_type()
+        };
+
+        assert_eq!(
+            record(&[('J', "PNAS"), ('B', "A book")]),
+            RecordType::Journal
+        );
+        assert_eq!(
+            record(&[('J', "Proceedings of the National Academy of Sciences")]),
+            RecordType::Journal
+        );
+        assert_eq!(
+            record(&[('B', "A book"), ('T', "A chapter")]),
+            RecordType::ArticleInBook
+        );
+        assert_eq!(record(&[('B', " "), ('T', "A book")]), RecordType::Book);
+        assert_eq!(
+            record(&[('B', "Proceedings of RECOMB"), ('T', "A paper")]),
+            RecordType::Conference
+        );
+        assert_eq!(
+            record(&[('R', "TR-12"), ('G', "AD-123")]),
+            RecordType::Report
+        );
+        assert_eq!(record(&[('G', "AD-123")]), RecordType::Government);
+        assert_eq!(
+            record(&[('R', "PhD thesis"), ('I', "MIT")]),
+            RecordType::Thesis
+        );
+        assert_eq!(record(&[('I', "Penguin")]), RecordType::Book);
+        assert_eq!(record(&[('T', "Notes")]), RecordType::Misc);
+
+        let set = Record {
+            journal: Some("PNAS".into()),
+            rec_type: RecordType::Misc,
+            ..Default::default()
+        };
+        assert_eq!(set.record_type(), RecordType::Misc);
+    }
+
+    #[test]
+    fn validate() {
+        let mut record = Record {
+            date: Some("March 2020".into()),
+            page_number: Some("12-15".into()),
+            ..Default::default()
+        };
+        assert!(record.validate().is_ok());
+        for pages in ["198-02", "pp. 12-15"] {
+            record.page_number = Some(pages.into());
+            assert!(matches!(
+                record.validate().unwrap_err().kind(),
+                crate::ErrorKind::Pages(_)
+            ));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let record = Record {
+            author: vec![Author {
+                last: "Brown".into(),
+                rest: "M.".into(),
+                ..Default::default()
+            }],
+            journal: Some("PNAS".into()),
+            keywords: Some(vec!["plants".into()]),
+            rec_type: RecordType::Journal,
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            json,
+            r#"{"author":[{"last":"Brown","rest":"M."}],"journal":"PNAS","keywords":["plants"],"rec_type":"journal"}"#
+        );
+        assert_eq!(serde_json::from_str::<Record>(&json).unwrap(), record);
+        assert_eq!(
+            serde_json::from_str::<Record>(r#"{"title":"A title"}"#).unwrap(),
+            Record {
+                title: Some("A title".into()),
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,669 @@
+/*!
+A reader and writer for the RIS format, which publisher websites and
+most databases export citations as.
+
+Each RIS record starts with a `TY  - ` line giving its type, and ends
+with an `ER  - ` line. The [`Reader`] and [`Writer`] here mirror the
+refer [`crate::Reader`] and [`crate::Writer`], and produce and consume
+[`Record`]s.
+
+| refer | RIS                           |
+|-------|-------------------------------|
+| %A    | AU, A1                        |
+| %B    | BT, T2 (for CHAP)             |
+| %C    | CY                            |
+| %D    | PY, Y1, DA                    |
+| %E    | A2, ED                        |
+| %I    | PB                            |
+| %J    | JO, JF, JA, T2 (for JOUR)     |
+| %K    | KW                            |
+| %L    | ID                            |
+| %N    | IS                            |
+| %O    | N1, DO (as `doi:...`), UR     |
+| %P    | SP and EP                     |
+| %R    | M1 (for RPRT)                 |
+| %S    | T3                            |
+| %T    | TI, T1                        |
+| %V    | VL                            |
+| %X    | AB, N2                        |
+
+# Example
+
+```
+use refer::ris::{Reader, Writer};
+
+let data = "TY  - JOUR\nAU  - Brown, M.\nTI  - A title\nSP  - 1\nEP  - 10\nER  - \n";
+let mut rdr = Reader::new(data.as_bytes());
+let record = rdr.records().next().unwrap().unwrap();
+assert_eq!(record.page_number.as_deref(), Some("1-10"));
+
+let mut wtr = Writer::new(vec![]);
+wtr.write_record(&record).unwrap();
+let written = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+assert_eq!(written, data.replace("ER  - \n", "ER  - \n\n"));
+```
+*/
+
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, IntoInnerError, Write};
+use std::path::Path;
+use std::result::Result as StdResult;
+
+use crate::{
+    date::{Date, DatePart},
+    error::{Error, ErrorKind, Result},
+    record::{Author, Record, RecordType},
+};
+
+/// An RIS reader.
+pub struct Reader<R> {
+    /// The underlying reader.
+    rdr: io::BufReader<R>,
+    /// The line number to keep track of errors if we find any.
+    line: u64,
+}
+
+impl Reader<File> {
+    /// Create a reader from a given file path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Reader<File>> {
+        Ok(Reader::new(File::open(path)?))
+    }
+}
+
+impl<R: io::Read> Reader<R> {
+    pub fn new(rdr: R) -> Reader<R> {
+        Reader {
+            rdr: io::BufReader::new(rdr),
+            line: 0,
+        }
+    }
+
+    /// A borrowed iterator over the records of an RIS file.
+    pub fn records(&mut self) -> RecordsIter<'_, R> {
+        RecordsIter { rdr: self }
+    }
+
+    /// An owned iterator over the records of an RIS file.
+    pub fn into_records(self) -> RecordsIntoIter<R> {
+        RecordsIntoIter { rdr: self }
+    }
+
+    /// Read a single record, from the `TY` line to the `ER` line.
+    fn read_record(&mut self) -> Result<Option<Record>> {
+        let mut record: Option<Record> = None;
+        let mut start_page = None;
+        let mut end_page = None;
+        let mut typ = String::new();
+        // the last field, which continuation lines are added to
+        let mut pending: Option<(String, String)> = None;
+
+        let mut temp_buf = String::new();
+        loop {
+            self.line += 1;
+            temp_buf.clear();
+            if self.rdr.read_line(&mut temp_buf)? == 0 {
+                return match record {
+                    None => Ok(None),
+                    Some(_) => Err(ris_error(self.line, "end of input before an ER line")),
+                };
+            }
+            // a byte order mark can start the file
+            let line = temp_buf.trim_start_matches('\u{feff}').trim_end();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (tag, value) = match split_line(line) {
+                Some(t) => t,
+                None => {
+                    // a line without a tag continues the last field, text
+                    // before the first record is ignored
+                    if let Some((_, value)) = pending.as_mut() {
+                        if !value.is_empty() {
+                            value.push(' ');
+                        }
+                        value.push_str(line.trim());
+                    }
+                    continue;
+                }
+            };
+            // the last field is complete
+            if let (Some(r), Some((tag, value))) = (record.as_mut(), pending.take()) {
+                add_field(r, &typ, &tag, value, &mut start_page, &mut end_page);
+            }
+
+            match (tag, record.as_mut()) {
+                ("TY", None) => {
+                    typ = value.to_string();
+                    record = Some(Record {
+                        rec_type: match value {
+                            "JOUR" | "JFULL" | "MGZN" | "NEWS" | "EJOUR" => RecordType::Journal,
+                            "BOOK" | "EBOOK" => RecordType::Book,
+                            "CHAP" | "ECHAP" => RecordType::ArticleInBook,
+                            "RPRT" => RecordType::Report,
+                            "GOVDOC" => RecordType::Government,
+                            "THES" => RecordType::Thesis,
+                            "CONF" | "CPAPER" => RecordType::Conference,
+                            "GEN" | "UNPB" => RecordType::Misc,
+                            _ => RecordType::None,
+                        },
+                        ..Default::default()
+                    });
+                }
+                ("TY", Some(_)) => {
+                    return Err(ris_error(
+                        self.line,
+                        "TY line before the ER of the last record",
+                    ))
+                }
+                (_, None) => return Err(ris_error(self.line, "expected a TY line")),
+                ("ER", Some(r)) => {
+                    r.page_number = match (start_page.take(), end_page.take()) {
+                        (Some(s), Some(e)) => Some(format!("{}-{}", s, e)),
+                        (Some(s), None) => Some(s),
+                        (None, Some(e)) => Some(e),
+                        (None, None) => None,
+                    };
+                    return Ok(record);
+                }
+                (tag, Some(_)) => pending = Some((tag.to_string(), value.to_string())),
+            }
+        }
+    }
+}
+
+/// Split an RIS line into its tag and value. The tag is two capital
+/// letters or digits, followed by two spaces and a hyphen. Some exporters
+/// drop the trailing space on empty values.
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    let tag = line.get(..2)?;
+    let rest = line.get(2..)?;
+    let value = rest.strip_prefix("  -")?;
+    if !tag
+        .chars()
+        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+    {
+        return None;
+    }
+    Some((tag, value.trim()))
+}
+
+/// Add the value of an RIS field, along with any continuation lines, to
+/// a record.
+fn add_field(
+    record: &mut Record,
+    typ: &str,
+    tag: &str,
+    value: String,
+    start_page: &mut Option<String>,
+    end_page: &mut Option<String>,
+) {
+    if value.is_empty() {
+        return;
+    }
+    let set = |field: &mut Option<String>| *field = Some(value.clone());
+
+    match tag {
+        "AU" | "A1" => record.author.push(to_author(&value)),
+        "A2" | "ED" => record.editor.push(value),
+        // each KW line is one keyword, which may have spaces
+        "KW" => record.keywords.get_or_insert_with(Vec::new).push(value),
+        "TI" | "T1" => set(&mut record.title),
+        "T2" if typ == "JOUR" => set(&mut record.journal),
+        "T2" | "BT" => set(&mut record.book),
+        // abbreviated journal names are only used if there is no full name
+        "JO" | "JF" => set(&mut record.journal),
+        "JA" | "J2" if record.journal.is_none() => set(&mut record.journal),
+        "T3" => set(&mut record.series),
+        "VL" => set(&mut record.volume),
+        "IS" => set(&mut record.issue_number),
+        "SP" => {
+            // some exporters put the whole range in SP
+            match value.split_once('-') {
+                Some((s, e)) => {
+                    *start_page = Some(s.trim().to_string());
+                    *end_page = Some(e.trim().to_string());
+                }
+                None => *start_page = Some(value),
+            }
+        }
+        "EP" => *end_page = Some(value),
+        "PY" | "Y1" | "DA" => {
+            // dates are written YYYY/MM/DD/other. Only the year of PY
+            // and Y1 is kept, and the full date of DA is kept over it.
+            let date = match ris_date(&value) {
+                Some(d) if tag == "DA" => d.to_string(),
+                Some(d) => d.year.to_string(),
+                None => value,
+            };
+            let full = |d: &str| {
+                d.parse::<Date>()
+                    .is_ok_and(|d| d.start().is_some_and(|s| s.month.is_some()))
+            };
+            if record
+                .date
+                .as_deref()
+                .is_none_or(|d| !full(d) || full(&date))
+            {
+                record.date = Some(date);
+            }
+        }
+        "PB" => set(&mut record.issuer),
+        "CY" => set(&mut record.place),
+        "M1" if typ == "RPRT" => set(&mut record.report),
+        "DO" => record.other = Some(format!("doi:{}", value)),
+        "UR" => {
+            record.other.get_or_insert(value);
+        }
+        "N1" => set(&mut record.other),
+        "AB" | "N2" => set(&mut record.annotation),
+        "ID" => set(&mut record.label),
+        _ => (),
+    }
+}
+
+/// A date written as `YYYY/MM/DD/other`, where the parts after the year
+/// may be empty.
+fn ris_date(value: &str) -> Option<DatePart> {
+    let mut parts = value.split('/').map(str::trim);
+    let year = parts.next()?.parse().ok()?;
+    let month = parts
+        .next()
+        .and_then(|m| m.parse().ok())
+        .filter(|m| (1..=12).contains(m));
+    let day = parts
+        .next()
+        .and_then(|d| d.parse().ok())
+        .filter(|d| month.is_some() && (1..=31).contains(d));
+    Some(DatePart {
+        year,
+        month,
+        day,
+        season: None,
+    })
+}
+
+fn to_author(name: &str) -> Author {
+    name.parse().unwrap_or_else(|_| Author {
+        last: name.trim().to_string(),
+        ..Default::default()
+    })
+}
+
+/// A borrowed iterator over the records of an RIS file.
+pub struct RecordsIter<'r, R: 'r> {
+    /// The underlying reader
+    rdr: &'r mut Reader<R>,
+}
+
+impl<'r, R: io::Read> RecordsIter<'r, R> {
+    /// Return a reference to the underlying reader.
+    pub fn reader(&self) -> &Reader<R> {
+        self.rdr
+    }
+
+    /// Return a mutable reference to the underlying reader.
+    pub fn reader_mut(&mut self) -> &mut Reader<R> {
+        self.rdr
+    }
+}
+
+impl<'r, R: io::Read> Iterator for RecordsIter<'r, R> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Result<Record>> {
+        self.rdr.read_record().transpose()
+    }
+}
+
+/// An owned iterator over the records of an RIS file.
+pub struct RecordsIntoIter<R> {
+    /// The underlying reader.
+    rdr: Reader<R>,
+}
+
+impl<R: io::Read> RecordsIntoIter<R> {
+    /// Return a reference to the underlying reader.
+    pub fn reader(&self) -> &Reader<R> {
+        &self.rdr
+    }
+
+    /// Return a mutable reference to the underlying reader.
+    pub fn reader_mut(&mut self) -> &mut Reader<R> {
+        &mut self.rdr
+    }
+
+    /// Drop this iterator and return the underlying reader.
+    pub fn into_reader(self) -> Reader<R> {
+        self.rdr
+    }
+}
+
+impl<R: io::Read> Iterator for RecordsIntoIter<R> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Result<Record>> {
+        self.rdr.read_record().transpose()
+    }
+}
+
+/// An RIS writer.
+pub struct Writer<W: io::Write> {
+    wtr: io::BufWriter<W>,
+}
+
+impl Writer<File> {
+    /// Build an RIS writer that writes data to the given file path.
+    /// *The file is truncated if it already exists*.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Writer<File>> {
+        Ok(Writer::new(File::create(path)?))
+    }
+}
+
+impl<W: io::Write> Writer<W> {
+    /// Create a new RIS writer.
+    pub fn new(wtr: W) -> Writer<W> {
+        Writer {
+            wtr: io::BufWriter::new(wtr),
+        }
+    }
+
+    /// Write a record, followed by a blank line.
+    pub fn write_record(&mut self, record: &Record) -> Result<()> {
+        let typ = ris_type(record);
+        let mut field = |tag: &str, value: &str| writeln!(self.wtr, "{}  - {}", tag, value);
+
+        field("TY", typ)?;
+        for a in &record.author {
+            field("AU", &a.full_name())?;
+        }
+        if record.author.is_empty() {
+            if let Some(q) = &record.author_np {
+                field("AU", q)?;
+            }
+        }
+        for e in &record.editor {
+            field("A2", e)?;
+        }
+        if let Some(t) = &record.title {
+            field("TI", t)?;
+        }
+        if let Some(j) = &record.journal {
+            field("JO", j)?;
+        }
+        if let Some(b) = &record.book {
+            match typ {
+                "CHAP" => field("T2", b)?,
+                _ => field("BT", b)?,
+            }
+        }
+        if let Some(s) = &record.series {
+            field("T3", s)?;
+        }
+        if let Some(v) = &record.volume {
+            field("VL", v)?;
+        }
+        if let Some(n) = &record.issue_number {
+            field("IS", n)?;
+        }
+        match record.parsed_pages() {
+            // abbreviated ranges are written in full
+            Ok(Some(p)) => {
+                field("SP", &p.start.to_string())?;
+                if let Some(e) = &p.end {
+                    field("EP", &e.to_string())?;
+                }
+            }
+            Ok(None) => (),
+            Err(_) => {
+                let p = record.page_number.as_deref().unwrap_or_default();
+                match p.split_once('-') {
+                    Some((s, e)) => {
+                        field("SP", s.trim())?;
+                        field("EP", e.trim_start_matches('-').trim())?;
+                    }
+                    None => field("SP", p)?,
+                }
+            }
+        }
+        // PY is only the year, and DA the full date
+        match record.parsed_date().ok().as_ref().and_then(Date::start) {
+            Some(d) => {
+                field("PY", &d.year.to_string())?;
+                if let Some(m) = d.month {
+                    let day = d.day.map(|d| format!("{:02}", d)).unwrap_or_default();
+                    field("DA", &format!("{:04}/{:02}/{}/", d.year, m, day))?;
+                }
+            }
+            None => {
+                if let Some(d) = &record.date {
+                    field("PY", d)?;
+                }
+            }
+        }
+        if let Some(i) = &record.issuer {
+            field("PB", i)?;
+        }
+        if let Some(c) = &record.place {
+            field("CY", c)?;
+        }
+        if let Some(r) = &record.report {
+            field("M1", r)?;
+        }
+        if let Some(k) = &record.keywords {
+            for kw in k {
+                field("KW", kw)?;
+            }
+        }
+        if let Some(o) = &record.other {
+            match o.strip_prefix("doi:") {
+                Some(doi) => field("DO", doi.trim())?,
+                None => field("N1", o)?,
+            }
+        }
+        if let Some(x) = &record.annotation {
+            field("AB", x)?;
+        }
+        if let Some(l) = &record.label {
+            field("ID", l)?;
+        }
+        // ER has a trailing space, and records are separated by a blank line
+        self.wtr.write_all(b"ER  - \n\n")?;
+        Ok(())
+    }
+
+    /// Flush the contents of the current buffer and return the underlying writer.
+    pub fn into_inner(self) -> StdResult<W, IntoInnerError<BufWriter<W>>> {
+        self.wtr.into_inner()
+    }
+
+    /// Flush the writer at the end of usage.
+    pub fn flush(&mut self) -> StdResult<(), io::Error> {
+        self.wtr.flush()
+    }
+}
+
+/// The RIS type for a record.
+fn ris_type(record: &Record) -> &'static str {
+    match record.record_type() {
+        RecordType::Journal => "JOUR",
+        RecordType::Book => "BOOK",
+        RecordType::ArticleInBook => "CHAP",
+        RecordType::Report => "RPRT",
+        RecordType::Government => "GOVDOC",
+        RecordType::Thesis => "THES",
+        RecordType::Conference => "CPAPER",
+        RecordType::Misc | RecordType::None => "GEN",
+    }
+}
+
+fn ris_error(line: u64, message: &str) -> Error {
+    Error::new(ErrorKind::Ris(format!("At line: {}. {}", line, message)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_records() {
+        let data = "\u{feff}TY  - CHAP\n\
+                    AU  - Brown, Max\n\
+                    AU  - Twyford, A. D.\n\
+                    ED  - Smith, J.\n\
+                    TI  - A chapter\n\
+                    T2  - The book\n\
+                    PY  - 2020/03/01/\n\
+                    SP  - 45\n\
+                    EP  - 67\n\
+                    KW  - plants\n\
+                    KW  - parasites\n\
+                    DO  - 10.1000/xyz\n\
+                    AB  - A long abstract\n\
+                    which continues\n\
+                    ER  - \n\
+                    \n\
+                    TY  - JOUR\n\
+                    T2  - PNAS\n\
+                    ER  -\n";
+        let records: Vec<Record> = Reader::new(data.as_bytes())
+            .into_records()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 2);
+        let chapter = &records[0];
+        assert_eq!(chapter.author.len(), 2);
+        assert_eq!(chapter.author[0].rest, "Max");
+        assert_eq!(chapter.editor, vec!["Smith, J.".to_string()]);
+        assert_eq!(chapter.book.as_deref(), Some("The book"));
+        assert_eq!(chapter.date.as_deref(), Some("2020"));
+        assert_eq!(chapter.page_number.as_deref(), Some("45-67"));
+        assert_eq!(
+            chapter.keywords,
+            Some(vec!["plants".into(), "parasites".into()])
+        );
+        assert_eq!(chapter.other.as_deref(), Some("doi:10.1000/xyz"));
+        assert_eq!(
+            chapter.annotation.as_deref(),
+            Some("A long abstract which continues")
+        );
+
+        assert_eq!(records[1].journal.as_deref(), Some("PNAS"));
+        assert_eq!(records[1].rec_type, RecordType::Journal);
+    }
+
+    #[test]
+    fn test_keywords_continuations_and_dates() {
+        let data = "TY  - JOUR\n\
+                    AU  - van der Berg,\n  Pieter\n\
+                    AU  - Brown, M.\n\
+                    KW  - parasitic plants\n\
+                    KW  - host\nrange\n\
+                    PY  - 1999\n\
+                    DA  - 1999/05/03/\n\
+                    ER  - \n";
+        let record = Reader::new(data.as_bytes())
+            .into_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.author.len(), 2);
+        assert_eq!(record.author[0].family_name(), "van der Berg");
+        assert_eq!(record.author[0].rest, "Pieter");
+        assert_eq!(
+            record.keywords,
+            Some(vec!["parasitic plants".into(), "host range".into()])
+        );
+        assert_eq!(record.date.as_deref(), Some("3 May 1999"));
+
+        // the year goes in PY and the full date in DA
+        let mut wtr = Writer::new(vec![]);
+        wtr.write_record(&Record {
+            date: Some("May 1999".into()),
+            ..record.clone()
+        })
+        .unwrap();
+        let written = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        assert!(written.contains("PY  - 1999\nDA  - 1999/05//\n"));
+        assert!(written.contains("KW  - parasitic plants\nKW  - host range\n"));
+        let read = Reader::new(written.as_bytes())
+            .into_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.date.as_deref(), Some("May 1999"));
+        assert_eq!(read.keywords, record.keywords);
+    }
+
+    #[test]
+    fn test_one_word_author_to_refer() {
+        let data = "TY  - GEN\nAU  - Google\nTI  - Search\nER  - \n";
+        let record = Reader::new(data.as_bytes())
+            .into_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        let read = crate::Reader::new(record.to_string().as_bytes())
+            .records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.author, record.author);
+        assert_eq!(read.author[0].last, "Google");
+    }
+
+    #[test]
+    fn test_missing_end() {
+        let mut rdr = Reader::new("TY  - JOUR\nTI  - A title\n".as_bytes());
+        assert!(rdr.records().next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_write_record() {
+        let record = Record {
+            author: vec![Author {
+                last: "Brown".into(),
+                rest: "M.".into(),
+                ..Default::default()
+            }],
+            title: Some("A report".into()),
+            report: Some("TR-1".into()),
+            issuer: Some("Kew".into()),
+            ..Default::default()
+        };
+        let mut wtr = Writer::new(vec![]);
+        wtr.write_record(&record).unwrap();
+
+        assert_eq!(
+            String::from_utf8(wtr.into_inner().unwrap()).unwrap(),
+            "TY  - RPRT\nAU  - Brown, M.\nTI  - A report\nPB  - Kew\nM1  - TR-1\nER  - \n\n"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_particle_and_suffix() {
+        let record = Record {
+            author: vec![Author {
+                last: "Berg".into(),
+                rest: "P.".into(),
+                particle: Some("van der".into()),
+                suffix: Some("Jr.".into()),
+                ..Default::default()
+            }],
+            title: Some("A book".into()),
+            ..Default::default()
+        };
+        let mut wtr = Writer::new(vec![]);
+        wtr.write_record(&record).unwrap();
+        let written = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        assert!(written.contains("AU  - van der Berg, P., Jr.\n"));
+
+        let read = Reader::new(written.as_bytes())
+            .into_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.author, record.author);
+    }
+}
@@ -1,8 +1,11 @@
 // Very much a work in progress, please bear with! Or help me?
 
 use crate::{
-    error::{Error, ErrorKind, Result},
+    date::{Date, DatePart},
+    error::Result,
+    pages::{PageFormat, PageRange},
     record::{Author, Record, RecordType},
+    text::{bold, italic, link, Markup, Span, Text},
 };
 
 /// Create a styled `String` from refer record data.
@@ -12,6 +15,9 @@ pub struct StyleBuilder {
     inner: Record,
     /// The styling to use, default is Harvard.
     typ: Style,
+    /// How emphasised parts (e.g. journal names) are marked up,
+    /// default is plain text.
+    markup: Markup,
     /// Whether to use the other (%O) field. If true, the other field
     /// is used in journal formatting for 'Available at: DOI/URL'
     use_other_field: bool,
@@ -23,6 +29,7 @@ impl StyleBuilder {
         StyleBuilder {
             inner: rec,
             typ: Style::default(),
+            markup: Markup::default(),
             use_other_field: true,
         }
     }
@@ -33,107 +40,970 @@ impl StyleBuilder {
         self.typ = style;
     }
 
+    /// Set the markup used by [`StyleBuilder::format`]. See [`Markup`].
+    pub fn set_markup(&mut self, markup: Markup) {
+        self.markup = markup;
+    }
+
     /// Use the '%O' (other) field when printing references
     /// in a specified format.
     pub fn use_other(&mut self) {
         self.use_other_field = true;
     }
 
-    /// Formats a [Record] into a string which can fail if the record type
-    /// is not defined on the underlying record.
+    /// Formats a [Record] into a string, with the markup set. Every
+    /// [`RecordType`] can be formatted, this fails only if the date
+    /// can't be parsed. Pages which can't be parsed are written as they
+    /// are.
     pub fn format(&self) -> Result<String> {
+        Ok(self.format_text()?.render(self.markup))
+    }
+
+    /// Formats a [Record] into [`Text`], which can be rendered with any
+    /// [`Markup`].
+    pub fn format_text(&self) -> Result<Text> {
+        let typ = match self.inner.record_type() {
+            // a conference paper published in a journal is cited as one
+            RecordType::Conference if self.inner.journal.is_some() => RecordType::Journal,
+            typ => typ,
+        };
         match self.typ {
-            Style::Apa => todo!(),
-            // very rough implementations
-            Style::Harvard => match self.inner.record_type()? {
-                RecordType::None => Err(Error::new(ErrorKind::RecordType(
-                    "Calling format on a record which does not have a record type.".into(),
-                ))),
-                // for harvard - how does the book title and normal title work?
-                // see https://libguides.ucd.ie/harvardstyle/harvardchapterineditedbook#:~:text=Reference%3A%20Chapter%20Author(s),publication%3A%20Publisher%2C%20page%20range.
-                RecordType::Book => {
-                    let record = &self.inner;
-                    // <authors> <(date)> <title> <place>: <publisher> <series> <volume number>
-                    // add the authors
-                    let mut a = harvard_author_string(record);
-                    // add the date if there is one
-                    harvard_date_string(record, &mut a);
-
-                    // add the name of the book
-                    if let Some(t) = &record.title {
-                        a.push_str(t);
-                        a.push_str(". ");
-                    }
+            Style::Apa => self.apa(typ),
+            Style::Harvard => self.harvard(typ),
+            Style::ChicagoAuthorDate => self.chicago(typ, true),
+            Style::ChicagoNotes => self.chicago(typ, false),
+            Style::Vancouver => self.vancouver(typ),
+            Style::Ieee => self.ieee(typ),
+            Style::Mla => self.mla(typ),
+        }
+    }
 
-                    // TODO: if we have a book title & editor name
-                    // if let (Some(b), e) = (&record.book, &record.editor) {
+    /// Formats a [Record] as a footnote, in the Chicago notes style
+    /// whichever style is set. See [`Note`].
+    pub fn format_note(&self, note: Note) -> Result<String> {
+        Ok(self.format_note_text(note)?.render(self.markup))
+    }
+
+    /// Formats a [Record] as a footnote into [`Text`].
+    pub fn format_note_text(&self, note: Note) -> Result<Text> {
+        let typ = match self.inner.record_type() {
+            RecordType::Conference if self.inner.journal.is_some() => RecordType::Journal,
+            typ => typ,
+        };
+        match note {
+            Note::Long => self.chicago_long_note(typ),
+            Note::Short => Ok(self.chicago_short_note(typ)),
+        }
+    }
 
-                    // }
+    fn apa(&self, typ: RecordType) -> Result<Text> {
+        let record = &self.inner;
+        let mut a = apa_author_string(record);
+        apa_date_string(record, &mut a)?;
 
-                    // add place
-                    if let Some(p) = &record.place {
-                        a.push_str(p);
+        match typ {
+            RecordType::Journal => {
+                // <authors> <(date)> <title>. <*journal*>, <*volume*>(<issue>), <pages>. <doi>
+                if let Some(t) = &record.title {
+                    a.push_str(&sentence_case(t));
+                    push_terminated(&mut a, ". ");
+                }
+                // the source is built separately so the separators
+                // only appear between the parts that are present
+                let mut source = Vec::new();
+                if let Some(j) = &record.journal {
+                    source.push(italic(j));
+                }
+                match (&record.volume, &record.issue_number) {
+                    (Some(v), Some(i)) => {
+                        let mut volume = italic(v);
+                        volume.push_str(&format!("({})", i));
+                        source.push(volume);
                     }
-                    // add publisher
-                    if let Some(p) = &record.issuer {
-                        if record.place.is_some() {
-                            a.push_str(": ");
-                        }
-                        a.push_str(p);
-                        a.push_str(". ");
+                    (Some(v), None) => source.push(italic(v)),
+                    (None, Some(i)) => source.push(format!("({})", i).into()),
+                    (None, None) => (),
+                }
+                if let Some(p) = Pages::of(record) {
+                    // APA gives ranges in full, with an en dash
+                    source.push(p.format(PageFormat::Expanded, "\u{2013}").into());
+                }
+                if !source.is_empty() {
+                    a.append(Text::join(source, ", "));
+                    a.push_str(". ");
+                }
+            }
+            RecordType::ArticleInBook | RecordType::Conference => {
+                // <title>. In <editors> (Eds.), <*book*> (<pp. pages>). <publisher>. <doi>
+                if let Some(t) = &record.title {
+                    a.push_str(&sentence_case(t));
+                    push_terminated(&mut a, ". ");
+                }
+                a.push_str("In ");
+                if let Some(e) = apa_editor_string(record) {
+                    a.push_str(&e);
+                    a.push_str(", ");
+                }
+                if let Some(b) = &record.book {
+                    a.append(italic(sentence_case(b.trim())));
+                }
+                let mut details = Vec::new();
+                if let Some(v) = &record.volume {
+                    details.push(format!("Vol. {}", v));
+                }
+                if let Some(p) = Pages::of(record) {
+                    let pp = if p.is_range() { "pp." } else { "p." };
+                    details.push(format!(
+                        "{} {}",
+                        pp,
+                        p.format(PageFormat::Expanded, "\u{2013}")
+                    ));
+                }
+                if !details.is_empty() {
+                    a.push_str(&format!(" ({})", details.join(", ")));
+                }
+                push_terminated(&mut a, ". ");
+                if let Some(p) = &record.issuer {
+                    a.push_str(p);
+                    push_terminated(&mut a, ". ");
+                }
+            }
+            RecordType::Book
+            | RecordType::Report
+            | RecordType::Government
+            | RecordType::Thesis
+            | RecordType::Misc
+            | RecordType::None => {
+                // <authors> <(date)> <*title*> <(series, Vol. volume)> <[description]>. <publisher>. <doi>
+                let title = record
+                    .title
+                    .as_deref()
+                    .or(record.book.as_deref().filter(|b| !b.trim().is_empty()));
+                if let Some(t) = title {
+                    a.append(italic(sentence_case(t)));
+                }
+                let mut details = Vec::new();
+                if let Some(s) = &record.series {
+                    details.push(s.clone());
+                }
+                if let Some(v) = &record.volume {
+                    details.push(format!("Vol. {}", v));
+                }
+                match typ {
+                    RecordType::Report => {
+                        details.extend(record.report.iter().map(|r| format!("Report No. {}", r)))
                     }
-                    // add series
-                    if let Some(s) = &record.series {
-                        a.push_str(s);
-                        if record.volume.is_some() {
-                            a.push_str(", ");
-                        } else {
-                            a.push('.');
+                    RecordType::Government => details.extend(
+                        record
+                            .government
+                            .iter()
+                            .map(|g| format!("Publication No. {}", g)),
+                    ),
+                    _ => (),
+                }
+                if !details.is_empty() {
+                    a.push_str(&format!(" ({})", details.join(", ")));
+                }
+                // a thesis is described in brackets, along with where it
+                // was submitted, which takes the place of the publisher
+                let publisher = match typ {
+                    RecordType::Thesis => {
+                        let kind = record.report.as_deref().unwrap_or("Thesis");
+                        match &record.issuer {
+                            Some(i) => a.push_str(&format!(" [{}, {}]", kind, i)),
+                            None => a.push_str(&format!(" [{}]", kind)),
                         }
+                        None
                     }
-                    // volume
-                    if let Some(v) = &record.volume {
-                        a.push_str(v);
+                    _ => record.issuer.as_ref(),
+                };
+                if title.is_some() || !details.is_empty() || typ == RecordType::Thesis {
+                    push_terminated(&mut a, ". ");
+                }
+                // APA 7 drops the place of publication
+                if let Some(p) = publisher {
+                    a.push_str(p);
+                    push_terminated(&mut a, ". ");
+                }
+            }
+        }
+        self.apa_other_string(&mut a);
+        a.trim_end();
+
+        Ok(a)
+    }
+
+    // very rough implementations
+    fn harvard(&self, typ: RecordType) -> Result<Text> {
+        let record = &self.inner;
+        // add the authors
+        let mut a = Text::from(harvard_author_string(record));
+        // add the date if there is one
+        harvard_date_string(record, &mut a)?;
+
+        match typ {
+            RecordType::Journal => {
+                // title
+                if let Some(t) = &record.title {
+                    a.push_str(t);
+                    a.push_str(". ");
+                }
+                // journal
+                if let Some(j) = &record.journal {
+                    a.push_str(j);
+                    a.push_str(", ");
+                }
+                // volume
+                if let Some(v) = &record.volume {
+                    a.append(bold(v));
+                    // a.push(' ');
+                }
+                // issue
+                if let Some(i) = &record.issue_number {
+                    a.push('(');
+                    a.push_str(i);
+                    a.push_str(") ");
+                }
+                // page
+                if let Some(p) = Pages::of(record) {
+                    // after the journal, volume or issue
+                    a.trim_end_matches(&[' ', ',']);
+                    a.push_str(if p.is_range() { ", pp. " } else { ", p. " });
+                    a.push_str(&p.format(PageFormat::Expanded, "\u{2013}"));
+                    a.push('.');
+                }
+            }
+            // see https://libguides.ucd.ie/harvardstyle/harvardchapterineditedbook#:~:text=Reference%3A%20Chapter%20Author(s),publication%3A%20Publisher%2C%20page%20range.
+            RecordType::ArticleInBook | RecordType::Conference => {
+                // <authors> <(date)> <title>. In: <editors> (eds.) <book>. <place>: <publisher>, <pp. pages>.
+                if let Some(t) = &record.title {
+                    a.push_str(t);
+                    a.push_str(". ");
+                }
+                a.push_str("In: ");
+                if let Some(e) = harvard_editor_string(record) {
+                    a.push_str(&e);
+                    a.push(' ');
+                }
+                if let Some(b) = &record.book {
+                    a.push_str(b.trim());
+                    a.push_str(". ");
+                }
+                harvard_publisher_string(record, &mut a);
+                if let Some(p) = Pages::of(record) {
+                    a.trim_end_matches(&[' ', '.']);
+                    a.push_str(if p.is_range() { ", pp. " } else { ", p. " });
+                    a.push_str(&p.format(PageFormat::Expanded, "\u{2013}"));
+                    a.push('.');
+                }
+            }
+            RecordType::Report | RecordType::Government | RecordType::Thesis => {
+                // <authors> <(date)> <title>. <report number>. <place>: <publisher>.
+                if let Some(t) = &record.title {
+                    a.push_str(t);
+                    a.push_str(". ");
+                }
+                let number = match typ {
+                    RecordType::Report => record.report.as_ref().map(|r| format!("Report {}", r)),
+                    RecordType::Government => record.government.clone(),
+                    _ => Some(record.report.clone().unwrap_or_else(|| "Thesis".into())),
+                };
+                if let Some(n) = number {
+                    a.push_str(&n);
+                    a.push_str(". ");
+                }
+                harvard_publisher_string(record, &mut a);
+            }
+            RecordType::Book | RecordType::Misc | RecordType::None => {
+                // <authors> <(date)> <title> <place>: <publisher> <series> <volume number>
+                // add the name of the book
+                let title = record
+                    .title
+                    .as_deref()
+                    .or(record.book.as_deref().filter(|b| !b.trim().is_empty()));
+                if let Some(t) = title {
+                    a.push_str(t);
+                    a.push_str(". ");
+                }
+
+                // TODO: if we have a book title & editor name
+                // if let (Some(b), e) = (&record.book, &record.editor) {
+
+                // }
+
+                harvard_publisher_string(record, &mut a);
+                // add series
+                if let Some(s) = &record.series {
+                    a.push_str(s);
+                    if record.volume.is_some() {
+                        a.push_str(", ");
+                    } else {
                         a.push('.');
                     }
-
-                    Ok(a)
                 }
-                RecordType::Journal => {
-                    let record = &self.inner;
-                    let mut a = harvard_author_string(record);
-                    harvard_date_string(record, &mut a);
+                // volume
+                if let Some(v) = &record.volume {
+                    a.push_str(v);
+                    a.push('.');
+                }
+            }
+        }
 
-                    // title
-                    if let Some(t) = &record.title {
-                        a.push_str(t);
-                        a.push_str(". ");
+        Ok(a)
+    }
+
+    /// Chicago (17th edition) bibliography entries, in the author-date
+    /// or the notes and bibliography system. The two differ only in
+    /// where the year goes.
+    fn chicago(&self, typ: RecordType, author_date: bool) -> Result<Text> {
+        let record = &self.inner;
+        let mut a = chicago_author_string(record);
+        let year = record.parsed_date()?.years("ca. ", "\u{2013}");
+        if author_date {
+            a.push_str(&year);
+            push_terminated(&mut a, ". ");
+        }
+        // in notes and bibliography, the year ends the publication details
+        let published = |details: Option<String>| match (author_date, details) {
+            (true, d) => d,
+            (false, Some(d)) => Some(format!("{}, {}", d, year)),
+            (false, None) => Some(year.clone()),
+        };
+
+        match typ {
+            RecordType::Journal => {
+                // <title>. <*journal*> <volume>, no. <issue> (<year>): <pages>.
+                // <title>. <*journal*> <volume> (<issue>): <pages>.
+                if let Some(t) = &record.title {
+                    a.push_str(&quoted(t, "."));
+                    a.push(' ');
+                }
+                if let Some(j) = &record.journal {
+                    a.append(italic(j));
+                }
+                if let Some(v) = &record.volume {
+                    a.push(' ');
+                    a.push_str(v);
+                }
+                match (&record.issue_number, author_date) {
+                    (Some(i), true) => a.push_str(&format!(" ({})", i)),
+                    (Some(i), false) => a.push_str(&format!(", no. {}", i)),
+                    (None, _) => (),
+                }
+                if !author_date {
+                    a.push_str(&format!(" ({})", year));
+                }
+                if let Some(p) = Pages::of(record) {
+                    a.push_str(": ");
+                    a.push_str(&p.format(PageFormat::Chicago, "\u{2013}"));
+                }
+                push_terminated(&mut a, ". ");
+            }
+            RecordType::ArticleInBook | RecordType::Conference => {
+                // <title>. In <*book*>, edited by <editors>, <pages>. <place>: <publisher>.
+                if let Some(t) = &record.title {
+                    a.push_str(&quoted(t, "."));
+                    a.push(' ');
+                }
+                let mut book = vec![Text::plain("In ")];
+                book[0].append(italic(record.book.as_deref().unwrap_or_default().trim()));
+                if let Some(e) = chicago_editor_names(record) {
+                    book.push(format!("edited by {}", e).into());
+                }
+                if let Some(v) = &record.volume {
+                    book.push(format!("vol. {}", v).into());
+                }
+                if let Some(p) = Pages::of(record) {
+                    book.push(p.format(PageFormat::Chicago, "\u{2013}").into());
+                }
+                a.append(Text::join(book, ", "));
+                push_terminated(&mut a, ". ");
+                if let Some(p) = published(chicago_publisher(record)) {
+                    a.push_str(&p);
+                    push_terminated(&mut a, ". ");
+                }
+            }
+            RecordType::Book
+            | RecordType::Report
+            | RecordType::Government
+            | RecordType::Thesis
+            | RecordType::Misc
+            | RecordType::None => {
+                // <*title*>. <series>. <report number>. <place>: <publisher>.
+                // <title>. <thesis kind>, <university>.
+                if let Some(t) = book_title(record) {
+                    match typ {
+                        RecordType::Thesis => a.push_str(&quoted(t, ".")),
+                        _ => {
+                            a.append(italic(t));
+                            push_terminated(&mut a, ".");
+                        }
                     }
-                    // journal
-                    if let Some(j) = &record.journal {
-                        a.push_str(j);
-                        a.push_str(", ");
+                    a.push(' ');
+                }
+                for part in chicago_details(record, typ) {
+                    a.push_str(&part);
+                    push_terminated(&mut a, ". ");
+                }
+                let details = match typ {
+                    RecordType::Thesis => {
+                        let kind = record.report.as_deref().unwrap_or("Thesis");
+                        Some(match &record.issuer {
+                            Some(i) => format!("{}, {}", kind, i),
+                            None => kind.to_string(),
+                        })
                     }
-                    // volume
-                    if let Some(v) = &record.volume {
-                        a.push_str(v);
-                        // a.push(' ');
+                    _ => chicago_publisher(record),
+                };
+                if let Some(p) = published(details) {
+                    a.push_str(&p);
+                    push_terminated(&mut a, ". ");
+                }
+            }
+        }
+        if let Some(o) = self.other_string() {
+            a.append(linked(&o));
+            push_terminated(&mut a, ".");
+        }
+        a.trim_end();
+
+        Ok(a)
+    }
+
+    /// A full Chicago note, for the first time a work is cited.
+    fn chicago_long_note(&self, typ: RecordType) -> Result<Text> {
+        let record = &self.inner;
+        let year = record.parsed_date()?.years("ca. ", "\u{2013}");
+        let mut parts = Vec::new();
+        if let Some(names) = chicago_note_names(record) {
+            parts.push(Text::from(names));
+        }
+
+        let mut a = match typ {
+            RecordType::Journal => {
+                // <authors>, <title>, <*journal*> <volume>, no. <issue> (<year>): <pages>
+                if let Some(t) = &record.title {
+                    parts.push(quoted(t, "").into());
+                }
+                let mut journal = italic(record.journal.as_deref().unwrap_or_default());
+                if let Some(v) = &record.volume {
+                    journal.push(' ');
+                    journal.push_str(v);
+                }
+                parts.push(journal);
+                let mut a = join_note_parts(&parts);
+                if let Some(i) = &record.issue_number {
+                    a.push_str(&format!(", no. {}", i));
+                }
+                a.push_str(&format!(" ({})", year));
+                if let Some(p) = Pages::of(record) {
+                    a.push_str(": ");
+                    a.push_str(&p.format(PageFormat::Chicago, "\u{2013}"));
+                }
+                a
+            }
+            RecordType::ArticleInBook | RecordType::Conference => {
+                // <authors>, <title>, in <*book*>, ed. <editors> (<place>: <publisher>, <year>), <pages>
+                if let Some(t) = &record.title {
+                    parts.push(quoted(t, "").into());
+                }
+                let mut book = Text::plain("in ");
+                book.append(italic(record.book.as_deref().unwrap_or_default().trim()));
+                parts.push(book);
+                if let Some(e) = chicago_editor_names(record) {
+                    parts.push(format!("ed. {}", e).into());
+                }
+                if let Some(v) = &record.volume {
+                    parts.push(format!("vol. {}", v).into());
+                }
+                let mut a = join_note_parts(&parts);
+                a.push_str(&publication_brackets(chicago_publisher(record), &year));
+                if let Some(p) = Pages::of(record) {
+                    a.push_str(", ");
+                    a.push_str(&p.format(PageFormat::Chicago, "\u{2013}"));
+                }
+                a
+            }
+            _ => {
+                // <authors>, <*title*>, <report number> (<place>: <publisher>, <year>)
+                // <authors>, <title> (<thesis kind>, <university>, <year>)
+                if let Some(t) = book_title(record) {
+                    parts.push(match typ {
+                        RecordType::Thesis => quoted(t, "").into(),
+                        _ => italic(t),
+                    });
+                }
+                parts.extend(chicago_details(record, typ).into_iter().map(Text::from));
+                let details = match typ {
+                    RecordType::Thesis => {
+                        let kind = record.report.as_deref().unwrap_or("Thesis");
+                        Some(match &record.issuer {
+                            Some(i) => format!("{}, {}", kind, i),
+                            None => kind.to_string(),
+                        })
                     }
-                    // issue
-                    if let Some(i) = &record.issue_number {
-                        a.push('(');
-                        a.push_str(i);
-                        a.push_str(") ");
+                    _ => chicago_publisher(record),
+                };
+                let mut a = join_note_parts(&parts);
+                a.push_str(&publication_brackets(details, &year));
+                a
+            }
+        };
+        if let Some(o) = self.other_string() {
+            a.push_str(", ");
+            a.append(linked(&o));
+        }
+        push_terminated(&mut a, ".");
+        Ok(a)
+    }
+
+    /// A short Chicago note, for later citations of a work: the family
+    /// names and a short title.
+    fn chicago_short_note(&self, typ: RecordType) -> Text {
+        let record = &self.inner;
+        let mut parts = Vec::new();
+        if let Some(names) = chicago_short_names(record) {
+            parts.push(Text::from(names));
+        }
+        let quote = matches!(
+            typ,
+            RecordType::Journal
+                | RecordType::ArticleInBook
+                | RecordType::Conference
+                | RecordType::Thesis
+        );
+        let title = book_title(record).map(short_title);
+        let mut a = join_note_parts(&parts);
+        match title {
+            Some(t) if quote => {
+                if !a.is_empty() {
+                    a.push_str(", ");
+                }
+                a.push_str(&quoted(&t, "."));
+            }
+            Some(t) => {
+                if !a.is_empty() {
+                    a.push_str(", ");
+                }
+                a.append(italic(t));
+                push_terminated(&mut a, ".");
+            }
+            None => push_terminated(&mut a, "."),
+        }
+        a
+    }
+
+    /// Vancouver, as in the NLM's Citing Medicine.
+    fn vancouver(&self, typ: RecordType) -> Result<Text> {
+        let record = &self.inner;
+        let mut a = vancouver_author_string(record);
+        let year = record.parsed_date()?.years("", "-");
+        // <place>: <publisher>; <year>.
+        let published = |details: Option<String>| {
+            let mut p = Text::from(match details {
+                Some(d) => format!("{}; {}", d, year),
+                None => year.clone(),
+            });
+            push_terminated(&mut p, ". ");
+            p
+        };
+
+        match typ {
+            RecordType::Journal => {
+                // <title>. <journal abbreviation>. <year>;<volume>(<issue>):<pages>.
+                if let Some(t) = &record.title {
+                    a.push_str(t.trim());
+                    push_terminated(&mut a, ". ");
+                }
+                if let Some(j) = &record.journal {
+                    a.push_str(&journal_abbreviation(j));
+                    a.push_str(". ");
+                }
+                a.push_str(&year);
+                if record.volume.is_some() || record.issue_number.is_some() {
+                    a.push(';');
+                }
+                if let Some(v) = &record.volume {
+                    a.push_str(v);
+                }
+                if let Some(i) = &record.issue_number {
+                    a.push_str(&format!("({})", i));
+                }
+                if let Some(p) = Pages::of(record) {
+                    a.push(':');
+                    a.push_str(&p.format(PageFormat::Minimal, "-"));
+                }
+                push_terminated(&mut a, ". ");
+            }
+            RecordType::ArticleInBook | RecordType::Conference => {
+                // <title>. In: <editors>, editors. <book>. <place>: <publisher>; <year>. p. <pages>.
+                if let Some(t) = &record.title {
+                    a.push_str(t.trim());
+                    push_terminated(&mut a, ". ");
+                }
+                a.push_str("In: ");
+                if let Some(e) = vancouver_editor_string(record) {
+                    a.push_str(&e);
+                }
+                if let Some(b) = record.book.as_deref().filter(|b| !b.trim().is_empty()) {
+                    a.push_str(b.trim());
+                    push_terminated(&mut a, ". ");
+                }
+                if let Some(v) = &record.volume {
+                    a.push_str(&format!("Vol. {}. ", v));
+                }
+                a.append(published(chicago_publisher(record)));
+                if let Some(p) = Pages::of(record) {
+                    a.push_str("p. ");
+                    a.push_str(&p.format(PageFormat::Minimal, "-"));
+                    a.push_str(". ");
+                }
+            }
+            RecordType::Book
+            | RecordType::Report
+            | RecordType::Government
+            | RecordType::Thesis
+            | RecordType::Misc
+            | RecordType::None => {
+                // <title>. <place>: <publisher>; <year>. Report No.: <number>.
+                // <title> [<thesis kind>]. <place>: <university>; <year>.
+                if record.author.is_empty() && record.author_np.is_none() {
+                    if let Some(e) = vancouver_editor_string(record) {
+                        a.push_str(&e);
                     }
-                    // page
-                    if let Some(p) = &record.page_number {
-                        a.push_str(p);
-                        a.push('.');
+                }
+                if let Some(t) = book_title(record) {
+                    a.push_str(t.trim());
+                    if typ == RecordType::Thesis {
+                        let kind = record.report.as_deref().unwrap_or("dissertation");
+                        a.push_str(&format!(" [{}]", kind.to_lowercase()));
                     }
+                    push_terminated(&mut a, ". ");
+                }
+                match (&record.series, &record.volume) {
+                    (Some(s), Some(v)) => a.push_str(&format!("Vol. {}, {}. ", v, s)),
+                    (Some(s), None) => a.push_str(&format!("({}). ", s)),
+                    (None, Some(v)) => a.push_str(&format!("Vol. {}. ", v)),
+                    (None, None) => (),
+                }
+                a.append(published(chicago_publisher(record)));
+                let number = match typ {
+                    RecordType::Report => record.report.as_ref(),
+                    RecordType::Government => record.government.as_ref(),
+                    _ => None,
+                };
+                if let Some(n) = number {
+                    a.push_str(&format!("Report No.: {}. ", n));
+                }
+            }
+        }
+        // NLM writes the DOI bare, rather than as a link
+        if let Some(o) = self.other_string() {
+            match o.strip_prefix("https://doi.org/") {
+                Some(doi) => a.append(link(&o, &format!("doi:{}", doi))),
+                None if o.contains("://") => {
+                    a.push_str("Available from: ");
+                    a.append(linked(&o));
+                }
+                None => {
+                    a.push_str(&o);
+                    push_terminated(&mut a, ".");
+                }
+            }
+        }
+        a.trim_end();
+
+        Ok(a)
+    }
 
-                    Ok(a)
+    /// IEEE, as in the IEEE Reference Guide.
+    fn ieee(&self, typ: RecordType) -> Result<Text> {
+        let record = &self.inner;
+        let date = abbreviated_date(&record.parsed_date()?, &IEEE_MONTHS, false);
+        let mut parts = Vec::new();
+        if let Some(names) = ieee_author_string(record) {
+            parts.push(Text::from(names));
+        }
+
+        let mut a = match typ {
+            RecordType::Journal => {
+                // <authors>, <title>, <*journal*>, vol. <volume>, no. <issue>, pp. <pages>, <date>
+                if let Some(t) = &record.title {
+                    parts.push(quoted(t, "").into());
                 }
-            },
+                if let Some(j) = &record.journal {
+                    parts.push(italic(j));
+                }
+                parts.extend(record.volume.iter().map(|v| format!("vol. {}", v).into()));
+                parts.extend(
+                    record
+                        .issue_number
+                        .iter()
+                        .map(|i| format!("no. {}", i).into()),
+                );
+                if let Some(p) = Pages::of(record) {
+                    parts.push(ieee_pages(&p).into());
+                }
+                parts.push(date.into());
+                join_note_parts(&parts)
+            }
+            RecordType::ArticleInBook => {
+                // <authors>, <title>, in <*book*>, <editors>, Eds. <place>: <publisher>, <year>, pp. <pages>
+                if let Some(t) = &record.title {
+                    parts.push(quoted(t, "").into());
+                }
+                let mut book = Text::plain("in ");
+                book.append(italic(record.book.as_deref().unwrap_or_default().trim()));
+                if let Some(v) = &record.volume {
+                    book.push_str(&format!(", vol. {}", v));
+                }
+                if let Some(e) = ieee_editor_string(record) {
+                    book.push_str(&format!(", {}", e));
+                }
+                parts.push(book);
+                let mut a = join_note_parts(&parts);
+                push_terminated(&mut a, ". ");
+                let mut published: Vec<String> = chicago_publisher(record).into_iter().collect();
+                published.push(date);
+                if let Some(p) = Pages::of(record) {
+                    published.push(ieee_pages(&p));
+                }
+                a.push_str(&published.join(", "));
+                a
+            }
+            RecordType::Conference => {
+                // <authors>, <title>, in <*proceedings*>, <place>, <date>, pp. <pages>
+                if let Some(t) = &record.title {
+                    parts.push(quoted(t, "").into());
+                }
+                if let Some(b) = record.book.as_deref().filter(|b| !b.trim().is_empty()) {
+                    let mut book = Text::plain("in ");
+                    book.append(italic(b.trim()));
+                    parts.push(book);
+                }
+                parts.extend(record.place.clone().map(Text::from));
+                parts.push(date.into());
+                if let Some(p) = Pages::of(record) {
+                    parts.push(ieee_pages(&p).into());
+                }
+                join_note_parts(&parts)
+            }
+            RecordType::Report | RecordType::Government | RecordType::Thesis => {
+                // <authors>, <title>, <issuer>, <place>, Rep. <number>, <date>
+                // <authors>, <title>, <thesis kind>, <university>, <place>, <date>
+                if let Some(t) = book_title(record) {
+                    parts.push(quoted(t, "").into());
+                }
+                if typ == RecordType::Thesis {
+                    parts.push(
+                        record
+                            .report
+                            .as_deref()
+                            .unwrap_or("Ph.D. dissertation")
+                            .into(),
+                    );
+                }
+                parts.extend(record.issuer.clone().map(Text::from));
+                parts.extend(record.place.clone().map(Text::from));
+                let number = match typ {
+                    RecordType::Report => record.report.as_ref(),
+                    RecordType::Government => record.government.as_ref(),
+                    _ => None,
+                };
+                parts.extend(number.map(|n| format!("Rep. {}", n).into()));
+                parts.push(date.into());
+                join_note_parts(&parts)
+            }
+            RecordType::Book | RecordType::Misc | RecordType::None => {
+                // <authors>, <*title*>, <editors>, Eds. <place>: <publisher>, <year>
+                // with only editors, they are written in place of the authors
+                let editors = ieee_editor_string(record);
+                let has_authors = !parts.is_empty();
+                if !has_authors {
+                    parts.extend(editors.clone().map(Text::from));
+                }
+                if let Some(t) = book_title(record) {
+                    parts.push(italic(t.trim()));
+                }
+                if let Some(v) = &record.volume {
+                    parts.push(format!("vol. {}", v).into());
+                }
+                if has_authors {
+                    parts.extend(editors.map(Text::from));
+                }
+                let mut a = join_note_parts(&parts);
+                if !a.is_empty() {
+                    push_terminated(&mut a, ". ");
+                }
+                let mut published: Vec<String> = chicago_publisher(record).into_iter().collect();
+                published.push(date);
+                a.push_str(&published.join(", "));
+                a
+            }
+        };
+        let other = self.other_string();
+        if let Some((o, doi)) = other
+            .as_deref()
+            .and_then(|o| Some((o, o.strip_prefix("https://doi.org/")?)))
+        {
+            a.push_str(", doi: ");
+            a.append(link(o, doi));
+        }
+        push_terminated(&mut a, ".");
+        match other {
+            Some(o) if o.starts_with("https://doi.org/") => (),
+            // a link isn't followed by a full stop
+            Some(o) if o.contains("://") => {
+                a.push_str(" [Online]. Available: ");
+                a.append(linked(&o));
+            }
+            Some(o) => {
+                a.push(' ');
+                a.push_str(&o);
+                push_terminated(&mut a, ".");
+            }
+            None => (),
+        }
+
+        Ok(a)
+    }
+
+    /// MLA (9th edition) works cited entries. After the author and the
+    /// title, the container (the journal or the book) is followed by its
+    /// elements, separated by commas.
+    fn mla(&self, typ: RecordType) -> Result<Text> {
+        let record = &self.inner;
+        let mut a = mla_author_string(record);
+        let date = record.parsed_date()?;
+        let date = (date != Date::NoDate).then(|| abbreviated_date(&date, &MLA_MONTHS, true));
+        let pages = match Pages::of(record) {
+            Some(p) if p.is_range() => {
+                Some(format!("pp. {}", p.format(PageFormat::Mla, "\u{2013}")))
+            }
+            Some(p) => Some(format!("p. {}", p.format(PageFormat::Mla, "\u{2013}"))),
+            None => None,
+        };
+
+        let mut container = Vec::new();
+        match typ {
+            RecordType::Journal | RecordType::ArticleInBook | RecordType::Conference => {
+                // <title>. <*container*>, edited by <editors>, vol. <volume>, no. <issue>, <publisher>, <date>, pp. <pages>.
+                if let Some(t) = &record.title {
+                    a.push_str(&quoted(t, "."));
+                    a.push(' ');
+                }
+                let title = match typ {
+                    RecordType::Journal => record.journal.as_deref(),
+                    _ => record.book.as_deref(),
+                };
+                if let Some(t) = title.map(str::trim).filter(|t| !t.is_empty()) {
+                    container.push(italic(t));
+                }
+                container
+                    .extend(mla_editor_string(record).map(|e| format!("edited by {}", e).into()));
+                container.extend(record.volume.iter().map(|v| format!("vol. {}", v).into()));
+                container.extend(
+                    record
+                        .issue_number
+                        .iter()
+                        .map(|n| format!("no. {}", n).into()),
+                );
+                if typ != RecordType::Journal {
+                    container.extend(record.issuer.clone().map(Text::from));
+                }
+                container.extend(date.map(Text::from));
+                container.extend(pages.map(Text::from));
+            }
+            RecordType::Book
+            | RecordType::Report
+            | RecordType::Government
+            | RecordType::Thesis
+            | RecordType::Misc
+            | RecordType::None => {
+                // <*title*>. Edited by <editors>, vol. <volume>, <publisher>, <date>.
+                // <*title*>. <date>. <university>, <thesis kind>.
+                if let Some(t) = book_title(record) {
+                    a.append(italic(t.trim()));
+                    push_terminated(&mut a, ". ");
+                }
+                if let Some(e) = mla_editor_string(record) {
+                    container.push(format!("Edited by {}", e).into());
+                }
+                container.extend(record.volume.iter().map(|v| format!("vol. {}", v).into()));
+                container.extend(record.series.clone().map(Text::from));
+                match typ {
+                    RecordType::Thesis => {
+                        // the date comes before the university
+                        if let Some(d) = date {
+                            a.push_str(&d);
+                            a.push_str(". ");
+                        }
+                        container.extend(record.issuer.clone().map(Text::from));
+                        container.push(
+                            record
+                                .report
+                                .as_deref()
+                                .unwrap_or("PhD dissertation")
+                                .into(),
+                        );
+                    }
+                    _ => {
+                        let number = match typ {
+                            RecordType::Report => record.report.as_ref(),
+                            RecordType::Government => record.government.as_ref(),
+                            _ => None,
+                        };
+                        container.extend(number.map(|n| format!("Report {}", n).into()));
+                        container.extend(record.issuer.clone().map(Text::from));
+                        container.extend(date.map(Text::from));
+                    }
+                }
+            }
+        }
+        // a DOI or URL is the location, the last element of the container
+        let (location, other): (Vec<_>, Vec<_>) = self
+            .other_string()
+            .into_iter()
+            .partition(|o| o.contains("://"));
+        container.extend(location.iter().map(|o| linked(o)));
+        if !container.is_empty() {
+            a.append(Text::join(container, ", "));
+            push_terminated(&mut a, ". ");
+        }
+        for o in other {
+            a.push_str(&o);
+            push_terminated(&mut a, ".");
+        }
+        a.trim_end();
+
+        Ok(a)
+    }
+
+    /// The other (%O) field, with a DOI written as a link, if it is
+    /// used.
+    fn other_string(&self) -> Option<String> {
+        if !self.use_other_field {
+            return None;
+        }
+        let o = self.inner.other.as_deref()?.trim();
+        let lower = o.to_lowercase();
+        if lower.starts_with("http://") || lower.starts_with("https://") {
+            Some(o.to_string())
+        } else if let Some(doi) = doi_from(o) {
+            Some(format!("https://doi.org/{}", doi))
+        } else {
+            Some(o.to_string())
+        }
+    }
+
+    /// APA puts the DOI (or URL) last, written as a link.
+    fn apa_other_string(&self, a: &mut Text) {
+        if let Some(o) = self.other_string() {
+            a.append(linked(&o));
+            // a link isn't followed by a full stop
+            if !o.contains("://") {
+                push_terminated(a, ".");
+            }
         }
     }
 }
@@ -141,37 +1011,1358 @@ impl StyleBuilder {
 /// The styles which are currently supported.
 #[derive(Default, Debug)]
 pub enum Style {
+    /// APA, 7th edition.
     Apa,
     #[default]
     Harvard,
+    /// Chicago, 17th edition, author-date reference list entries.
+    ChicagoAuthorDate,
+    /// Chicago, 17th edition, notes and bibliography. The notes are
+    /// made with [`StyleBuilder::format_note`].
+    ChicagoNotes,
+    /// Vancouver (ICMJE and NLM), the numeric style of the biomedical
+    /// journals.
+    Vancouver,
+    /// IEEE, the numeric style of engineering and computing.
+    Ieee,
+    /// MLA, 9th edition, as in the MLA Handbook.
+    Mla,
+}
+
+/// The form of a Chicago footnote.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Note {
+    /// The full note, for the first time a work is cited.
+    #[default]
+    Long,
+    /// The short note, for later citations, with the family names of
+    /// the authors and a title of up to four words.
+    Short,
 }
 
 fn harvard_author_string(record: &Record) -> String {
+    if record.author.is_empty() {
+        return match &record.author_np {
+            Some(q) => format!("{} ", q.trim()),
+            None => String::new(),
+        };
+    }
     if record.author.len() > 4 {
         // we are guaranteed to have a first element here.
         let first = record.author.first().unwrap();
-        format!("{} {} et al., ", first.last, first.rest)
+        format!("{} et al., ", harvard_name(first))
     } else {
-        let authors = record
-            .author
-            .iter()
-            .fold(String::new(), |mut a, Author { last, rest }| {
-                a.push_str(last);
-                a.push(' ');
-                a.push_str(rest);
-                a.push_str(" and ");
-                a
-            });
+        let authors = record.author.iter().fold(String::new(), |mut a, author| {
+            a.push_str(&harvard_name(author));
+            a.push_str(" and ");
+            a
+        });
         // as we always add "and "
         // I guess we allocate here again which is annoying
         authors.strip_suffix("and ").unwrap().to_string()
     }
 }
 
-fn harvard_date_string(record: &Record, a: &mut String) {
-    if let Some(d) = &record.date {
+/// `von Last First Jr.`, or the whole of a corporate name.
+fn harvard_name(author: &Author) -> String {
+    let mut name = author.family_name();
+    for part in std::iter::once(&author.rest).chain(&author.suffix) {
+        if !part.is_empty() {
+            name.push(' ');
+            name.push_str(part);
+        }
+    }
+    name
+}
+
+/// `Last, First, First Last, and First Last. ` The first author is
+/// inverted. Over ten authors, the first seven are listed with `et al.`
+fn chicago_author_string(record: &Record) -> Text {
+    let mut names: Vec<String> = record
+        .author
+        .iter()
+        .enumerate()
+        .map(|(i, author)| match i {
+            0 => inverted_name(author),
+            _ => natural_name(author),
+        })
+        .collect();
+    let mut a = Text::from(match names.len() {
+        0 => match &record.author_np {
+            Some(q) => q.clone(),
+            None => return Text::default(),
+        },
+        // the inverted name has a comma, so two names need one too
+        2 => format!("{}, and {}", names[0], names[1]),
+        n if n > 10 => {
+            names.truncate(7);
+            format!("{}, et al", names.join(", "))
+        }
+        _ => and_list(&names),
+    });
+    push_terminated(&mut a, ". ");
+    a
+}
+
+/// The authors of a note: `First Last and First Last`, or the first
+/// author with `et al.` for four or more.
+fn chicago_note_names(record: &Record) -> Option<String> {
+    let names: Vec<String> = record.author.iter().map(natural_name).collect();
+    match names.len() {
+        0 => record.author_np.clone(),
+        1..=3 => Some(and_list(&names)),
+        _ => Some(format!("{} et al.", names[0])),
+    }
+}
+
+/// The authors of a short note, by family name.
+fn chicago_short_names(record: &Record) -> Option<String> {
+    let names: Vec<String> = record.author.iter().map(|a| a.family_name()).collect();
+    match names.len() {
+        0 => record.author_np.clone(),
+        1..=3 => Some(and_list(&names)),
+        _ => Some(format!("{} et al.", names[0])),
+    }
+}
+
+/// `K. Jones and P. R. Smith`
+fn chicago_editor_names(record: &Record) -> Option<String> {
+    let names: Vec<String> = record
+        .editor
+        .iter()
+        .map(|e| match e.parse::<Author>() {
+            Ok(author) => natural_name(&author),
+            Err(_) => e.clone(),
+        })
+        .collect();
+    (!names.is_empty()).then(|| and_list(&names))
+}
+
+/// `<place>: <publisher>`
+fn chicago_publisher(record: &Record) -> Option<String> {
+    match (&record.place, &record.issuer) {
+        (Some(p), Some(i)) => Some(format!("{}: {}", p, i)),
+        (Some(p), None) => Some(p.clone()),
+        (None, Some(i)) => Some(i.clone()),
+        (None, None) => None,
+    }
+}
+
+/// The series, volume and number of a book or report.
+fn chicago_details(record: &Record, typ: RecordType) -> Vec<String> {
+    let mut details = Vec::new();
+    match (&record.series, &record.volume) {
+        (Some(s), Some(v)) => details.push(format!("{} {}", s, v)),
+        (Some(s), None) => details.push(s.clone()),
+        (None, Some(v)) => details.push(format!("Vol. {}", v)),
+        (None, None) => (),
+    }
+    match typ {
+        RecordType::Report => details.extend(record.report.iter().map(|r| format!("Report {}", r))),
+        RecordType::Government => details.extend(record.government.clone()),
+        _ => (),
+    }
+    details
+}
+
+/// `(<place>: <publisher>, <year>)` in a note.
+fn publication_brackets(details: Option<String>, year: &str) -> String {
+    match details {
+        Some(d) => format!(" ({}, {})", d, year),
+        None => format!(" ({})", year),
+    }
+}
+
+/// The parts of a note joined with commas, which go inside the closing
+/// quote of a quoted title.
+fn join_note_parts(parts: &[Text]) -> Text {
+    let mut a = Text::default();
+    for part in parts {
+        if !a.is_empty() {
+            let quoted = match a.spans().last() {
+                Some(Span::Plain(p)) => p.strip_suffix('\u{201d}'),
+                _ => None,
+            };
+            match quoted {
+                Some(q) if q.ends_with(['?', '!']) => (),
+                Some(_) => {
+                    a.trim_end_matches(&['\u{201d}']);
+                    a.push_str(",\u{201d}");
+                }
+                None => a.push(','),
+            }
+            a.push(' ');
+        }
+        a.append(part.clone());
+    }
+    a
+}
+
+/// A title in double quotes, with `punctuation` inside the closing
+/// quote unless the title already ends a sentence.
+fn quoted(title: &str, punctuation: &str) -> String {
+    let title = title.trim();
+    let punctuation = match title.ends_with(['.', '?', '!']) {
+        true => "",
+        false => punctuation,
+    };
+    format!("\u{201c}{}{}\u{201d}", title, punctuation)
+}
+
+/// A title of up to four words, without any subtitle, and not ending
+/// on a small word.
+fn short_title(title: &str) -> String {
+    const SMALL: [&str; 12] = [
+        "a", "an", "and", "as", "for", "in", "of", "on", "the", "their", "to", "with",
+    ];
+    let main = title.split(':').next().unwrap_or(title);
+    let mut words: Vec<&str> = main.split_whitespace().take(4).collect();
+    while words.len() > 1 && SMALL.contains(&words[words.len() - 1].to_lowercase().as_str()) {
+        words.pop();
+    }
+    words.join(" ").trim_end_matches([',', '.']).to_string()
+}
+
+/// The pages of a record. Pages which can't be parsed are written as
+/// they are, see [`Record::validate`].
+enum Pages<'a> {
+    Parsed(PageRange),
+    Raw(&'a str),
+}
+
+impl Pages<'_> {
+    fn of(record: &Record) -> Option<Pages<'_>> {
+        let raw = record.page_number.as_deref()?.trim();
+        match raw.parse() {
+            Ok(p) => Some(Pages::Parsed(p)),
+            Err(_) if raw.is_empty() => None,
+            // the styles add their own `pp.`
+            Err(_) => {
+                let lower = raw.to_lowercase();
+                let raw = match ["pp.", "p."].iter().find(|p| lower.starts_with(*p)) {
+                    Some(p) => raw[p.len()..].trim_start(),
+                    None => raw,
+                };
+                Some(Pages::Raw(raw))
+            }
+        }
+    }
+
+    fn is_range(&self) -> bool {
+        match self {
+            Pages::Parsed(p) => p.is_range(),
+            Pages::Raw(r) => r.contains(['-', '\u{2013}', ',']),
+        }
+    }
+
+    fn format(&self, format: PageFormat, dash: &str) -> String {
+        match self {
+            Pages::Parsed(p) => p.format(format, dash),
+            Pages::Raw(r) => r.to_string(),
+        }
+    }
+}
+
+/// The title of a book, or the book (%B) field of a record with no
+/// title.
+fn book_title(record: &Record) -> Option<&str> {
+    record
+        .title
+        .as_deref()
+        .or(record.book.as_deref().filter(|b| !b.trim().is_empty()))
+}
+
+/// `Last, First, Jr.`, or the whole of a corporate name.
+fn inverted_name(author: &Author) -> String {
+    let mut name = author.family_name();
+    for part in std::iter::once(&author.rest).chain(&author.suffix) {
+        if !part.is_empty() {
+            name.push_str(", ");
+            name.push_str(part);
+        }
+    }
+    name
+}
+
+/// `First Last Jr.`, or the whole of a corporate name.
+fn natural_name(author: &Author) -> String {
+    [author.rest.clone(), author.family_name()]
+        .into_iter()
+        .chain(author.suffix.clone())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `A`, `A and B`, or `A, B, and C`.
+fn and_list(names: &[String]) -> String {
+    match names {
+        [] => String::new(),
+        [one] => one.clone(),
+        [a, b] => format!("{} and {}", a, b),
+        [rest @ .., last] => format!("{}, and {}", rest.join(", "), last),
+    }
+}
+
+/// `Brown MJ, Twyford AD. ` Up to six authors are listed, then
+/// `et al.`
+fn vancouver_author_string(record: &Record) -> Text {
+    let mut names: Vec<String> = record.author.iter().map(vancouver_name).collect();
+    if names.is_empty() {
+        return match &record.author_np {
+            Some(q) => {
+                let mut a = Text::plain(q);
+                push_terminated(&mut a, ". ");
+                a
+            }
+            None => Text::default(),
+        };
+    }
+    if names.len() > 6 {
+        names.truncate(6);
+        names.push("et al".to_string());
+    }
+    let mut a = Text::from(names.join(", "));
+    push_terminated(&mut a, ". ");
+    a
+}
+
+/// `Jones K, Smith PR, editors. `
+fn vancouver_editor_string(record: &Record) -> Option<String> {
+    let names: Vec<String> = record
+        .editor
+        .iter()
+        .map(|e| match e.parse::<Author>() {
+            Ok(author) => vancouver_name(&author),
+            Err(_) => e.clone(),
+        })
+        .collect();
+    match names.len() {
+        0 => None,
+        1 => Some(format!("{}, editor. ", names[0])),
+        _ => Some(format!("{}, editors. ", names.join(", "))),
+    }
+}
+
+/// `Brown MJ`, with the initials run together.
+fn vancouver_name(author: &Author) -> String {
+    let initials: String = author
+        .rest
+        .split([' ', '.', '-'])
+        .filter_map(|name| name.chars().next())
+        .flat_map(char::to_uppercase)
+        .collect();
+    [author.family_name(), initials]
+        .into_iter()
+        .chain(author.suffix.clone())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `A. B. Surname, C. Other, and D. Third`, or the first author with
+/// `et al.` for more than six.
+fn ieee_author_string(record: &Record) -> Option<String> {
+    let names: Vec<String> = record.author.iter().map(ieee_name).collect();
+    match names.len() {
+        0 => record.author_np.clone(),
+        1..=6 => Some(and_list(&names)),
+        _ => Some(format!("{} et al.", names[0])),
+    }
+}
+
+/// `K. Jones and P. R. Smith, Eds.`
+fn ieee_editor_string(record: &Record) -> Option<String> {
+    let names: Vec<String> = record
+        .editor
+        .iter()
+        .map(|e| match e.parse::<Author>() {
+            Ok(author) => ieee_name(&author),
+            Err(_) => e.clone(),
+        })
+        .collect();
+    match names.len() {
+        0 => None,
+        1 => Some(format!("{}, Ed.", names[0])),
+        _ => Some(format!("{}, Eds.", and_list(&names))),
+    }
+}
+
+/// `A. B. Surname`, with the given names as initials.
+fn ieee_name(author: &Author) -> String {
+    [initials(&author.rest), author.family_name()]
+        .into_iter()
+        .chain(author.suffix.clone())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `p. 5` or `pp. 45–67`.
+fn ieee_pages(pages: &Pages) -> String {
+    match pages.is_range() {
+        true => format!("pp. {}", pages.format(PageFormat::Expanded, "\u{2013}")),
+        false => format!("p. {}", pages.format(PageFormat::Expanded, "\u{2013}")),
+    }
+}
+
+/// `Brown, Max James, and A. D. Twyford. ` The first author is
+/// inverted, and three or more are written with `et al.`
+fn mla_author_string(record: &Record) -> Text {
+    let mut a = Text::from(match record.author.as_slice() {
+        [] => match &record.author_np {
+            Some(q) => q.clone(),
+            None => return Text::default(),
+        },
+        [one] => inverted_name(one),
+        [first, second] => format!("{}, and {}", inverted_name(first), natural_name(second)),
+        [first, ..] => format!("{}, et al", inverted_name(first)),
+    });
+    push_terminated(&mut a, ". ");
+    a
+}
+
+/// `K. Jones and P. R. Smith`, or the first editor with `et al.` for
+/// three or more.
+fn mla_editor_string(record: &Record) -> Option<String> {
+    let names: Vec<String> = record
+        .editor
+        .iter()
+        .map(|e| match e.parse::<Author>() {
+            Ok(author) => natural_name(&author),
+            Err(_) => e.clone(),
+        })
+        .collect();
+    match names.len() {
+        0 => None,
+        1 | 2 => Some(and_list(&names)),
+        _ => Some(format!("{} et al.", names[0])),
+    }
+}
+
+/// The months as abbreviated by IEEE.
+const IEEE_MONTHS: [&str; 12] = [
+    "Jan.", "Feb.", "Mar.", "Apr.", "May", "Jun.", "Jul.", "Aug.", "Sept.", "Oct.", "Nov.", "Dec.",
+];
+
+/// The months as abbreviated by the MLA, which keeps names of four
+/// letters or fewer.
+const MLA_MONTHS: [&str; 12] = [
+    "Jan.", "Feb.", "Mar.", "Apr.", "May", "June", "July", "Aug.", "Sept.", "Oct.", "Nov.", "Dec.",
+];
+
+/// The date with its month abbreviated, e.g. `Mar. 2023`,
+/// `Mar.–Apr. 2023` or `2019–2020`, and the day too if `with_day`,
+/// e.g. `1 Mar. 2023`. Seasons are written in full, e.g. `Spring 2023`.
+fn abbreviated_date(date: &Date, months: &[&str; 12], with_day: bool) -> String {
+    let month = |part: &DatePart| {
+        part.month
+            .map(|m| months[m as usize - 1])
+            .or(part.season_name())
+    };
+    let (start, end, circa, suffix) = match date {
+        Date::Dated {
+            start,
+            end,
+            circa,
+            suffix,
+        } => (start, end, *circa, *suffix),
+        other => return other.to_string(),
+    };
+    let part = |p: &DatePart| match (month(p), p.day.filter(|_| with_day)) {
+        (Some(m), Some(d)) => format!("{} {} {}", d, m, p.year),
+        (Some(m), None) => format!("{} {}", m, p.year),
+        (None, _) => p.year.to_string(),
+    };
+    let mut s = String::new();
+    if circa {
+        s.push_str("c. ");
+    }
+    match end {
+        Some(e) if e.year == start.year => match (month(start), month(e)) {
+            _ if with_day && (start.day.is_some() || e.day.is_some()) => {
+                s.push_str(&format!("{}\u{2013}{}", part(start), part(e)))
+            }
+            (Some(m), Some(n)) if m != n => s.push_str(&format!("{}\u{2013}{} {}", m, n, e.year)),
+            _ => s.push_str(&part(start)),
+        },
+        Some(e) => s.push_str(&format!("{}\u{2013}{}", part(start), part(e))),
+        None => s.push_str(&part(start)),
+    }
+    s.extend(suffix);
+    s
+}
+
+/// Common words in journal titles with their ISO 4 abbreviations,
+/// sorted for a binary search.
+const JOURNAL_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("academy", "Acad"),
+    ("american", "Am"),
+    ("annals", "Ann"),
+    ("annual", "Annu"),
+    ("applied", "Appl"),
+    ("archives", "Arch"),
+    ("association", "Assoc"),
+    ("biochemistry", "Biochem"),
+    ("biological", "Biol"),
+    ("biology", "Biol"),
+    ("botanical", "Bot"),
+    ("botany", "Bot"),
+    ("british", "Br"),
+    ("bulletin", "Bull"),
+    ("cardiology", "Cardiol"),
+    ("chemistry", "Chem"),
+    ("clinical", "Clin"),
+    ("communications", "Commun"),
+    ("disease", "Dis"),
+    ("diseases", "Dis"),
+    ("ecology", "Ecol"),
+    ("engineering", "Eng"),
+    ("england", "Engl"),
+    ("environmental", "Environ"),
+    ("epidemiology", "Epidemiol"),
+    ("european", "Eur"),
+    ("evolution", "Evol"),
+    ("experimental", "Exp"),
+    ("general", "Gen"),
+    ("genetics", "Genet"),
+    ("immunology", "Immunol"),
+    ("infectious", "Infect"),
+    ("international", "Int"),
+    ("journal", "J"),
+    ("letters", "Lett"),
+    ("medical", "Med"),
+    ("medicine", "Med"),
+    ("microbiology", "Microbiol"),
+    ("molecular", "Mol"),
+    ("national", "Natl"),
+    ("neurology", "Neurol"),
+    ("neuroscience", "Neurosci"),
+    ("nursing", "Nurs"),
+    ("oncology", "Oncol"),
+    ("pediatrics", "Pediatr"),
+    ("pharmacology", "Pharmacol"),
+    ("physics", "Phys"),
+    ("physiology", "Physiol"),
+    ("phytologist", "Phytol"),
+    ("proceedings", "Proc"),
+    ("psychiatry", "Psychiatry"),
+    ("psychology", "Psychol"),
+    ("quarterly", "Q"),
+    ("research", "Res"),
+    ("review", "Rev"),
+    ("reviews", "Rev"),
+    ("science", "Sci"),
+    ("sciences", "Sci"),
+    ("society", "Soc"),
+    ("statistics", "Stat"),
+    ("surgery", "Surg"),
+    ("systematic", "Syst"),
+    ("technology", "Technol"),
+    ("transactions", "Trans"),
+    ("university", "Univ"),
+];
+
+/// Words dropped from abbreviated journal titles.
+const JOURNAL_STOP_WORDS: &[&str] = &["a", "an", "and", "&", "for", "in", "of", "on", "the"];
+
+/// Abbreviate a journal title as in the NLM catalogue, without full
+/// stops, e.g. `Journal of the American Medical Association` to
+/// `J Am Med Assoc`.
+/// Titles of one word are kept, as are words without a known
+/// abbreviation.
+fn journal_abbreviation(journal: &str) -> String {
+    let words: Vec<&str> = journal
+        .split(|c: char| c.is_whitespace() || c == '.')
+        .filter(|w| !w.is_empty())
+        .collect();
+    if words.len() == 1 {
+        return words[0].to_string();
+    }
+    words
+        .iter()
+        .filter(|w| !JOURNAL_STOP_WORDS.contains(&w.to_lowercase().as_str()))
+        .map(|w| {
+            let lower = w.to_lowercase();
+            match JOURNAL_ABBREVIATIONS.binary_search_by_key(&lower.as_str(), |(word, _)| word) {
+                Ok(i) => JOURNAL_ABBREVIATIONS[i].1,
+                Err(_) => w,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `<place>: <publisher>. `
+fn harvard_publisher_string(record: &Record, a: &mut Text) {
+    // add place
+    if let Some(p) = &record.place {
+        a.push_str(p);
+    }
+    // add publisher
+    if let Some(p) = &record.issuer {
+        if record.place.is_some() {
+            a.push_str(": ");
+        }
+        a.push_str(p);
+        a.push_str(". ");
+    } else if record.place.is_some() {
+        a.push_str(". ");
+    }
+}
+
+/// `Jones K. and Smith P. (eds.)`
+fn harvard_editor_string(record: &Record) -> Option<String> {
+    let names: Vec<String> = record
+        .editor
+        .iter()
+        .map(|e| match e.parse::<Author>() {
+            Ok(author) => harvard_name(&author),
+            Err(_) => e.clone(),
+        })
+        .collect();
+    let ed = if names.len() > 1 { "eds." } else { "ed." };
+    (!names.is_empty()).then(|| format!("{} ({})", names.join(" and "), ed))
+}
+
+fn harvard_date_string(record: &Record, a: &mut Text) -> Result<()> {
+    if record.date.is_some() {
         a.push('(');
-        a.push_str(d);
+        a.push_str(&record.parsed_date()?.years("c. ", "\u{2013}"));
         a.push_str(") ");
     }
+    Ok(())
+}
+
+/// APA author list: `Last, I. I., Last, I., & Last, I.`. Up to 20 authors
+/// are listed, beyond that the first 19, an ellipsis and the final author.
+fn apa_author_string(record: &Record) -> Text {
+    let names: Vec<String> = record
+        .author
+        .iter()
+        .map(|author| {
+            let initials = initials(&author.rest);
+            let mut name = author.family_name();
+            if !initials.is_empty() {
+                name.push_str(", ");
+                name.push_str(&initials);
+            }
+            if let Some(s) = &author.suffix {
+                name.push_str(", ");
+                name.push_str(s);
+            }
+            name
+        })
+        .collect();
+
+    let mut a = Text::from(match names.len() {
+        // fall back on the non-person author
+        0 => match &record.author_np {
+            Some(q) => q.clone(),
+            None => return Text::default(),
+        },
+        1 => names[0].clone(),
+        2 => format!("{}, & {}", names[0], names[1]),
+        3..=20 => {
+            let (last, rest) = names.split_last().unwrap();
+            format!("{}, & {}", rest.join(", "), last)
+        }
+        _ => format!(
+            "{}, . . . {}",
+            names[..19].join(", "),
+            names.last().unwrap()
+        ),
+    });
+    push_terminated(&mut a, ". ");
+    a
+}
+
+/// The year, `(n.d.)` if there is no date, `(in press)`, or e.g.
+/// `(ca. 1850)` and `(2019–2020)`.
+fn apa_date_string(record: &Record, a: &mut Text) -> Result<()> {
+    a.push('(');
+    a.push_str(&record.parsed_date()?.years("ca. ", "\u{2013}"));
+    a.push_str("). ");
+    Ok(())
+}
+
+/// Editors as in a chapter: `K. Jones & P. Smith (Eds.)`.
+fn apa_editor_string(record: &Record) -> Option<String> {
+    let names: Vec<String> = record
+        .editor
+        .iter()
+        .map(|e| match e.parse::<Author>() {
+            Ok(author) if !author.corporate => {
+                let initials = initials(&author.rest);
+                let name = [initials, author.family_name()].join(" ");
+                match &author.suffix {
+                    Some(s) => format!("{}, {}", name.trim(), s),
+                    None => name.trim().to_string(),
+                }
+            }
+            _ => e.clone(),
+        })
+        .collect();
+    let names = match names.len() {
+        0 => return None,
+        1 => names[0].clone(),
+        2 => format!("{} & {}", names[0], names[1]),
+        _ => {
+            let (last, rest) = names.split_last().unwrap();
+            format!("{}, & {}", rest.join(", "), last)
+        }
+    };
+    let ed = if record.editor.len() > 1 {
+        "Eds."
+    } else {
+        "Ed."
+    };
+    Some(format!("{} ({})", names, ed))
+}
+
+/// Turn given names into initials, e.g. `Max James` -> `M. J.`,
+/// `Jean-Paul` -> `J.-P.`. Names which are already initials are kept.
+fn initials(rest: &str) -> String {
+    rest.split_whitespace()
+        .map(|name| {
+            name.split('-')
+                .filter_map(|part| part.chars().next())
+                .map(|c| format!("{}.", c.to_uppercase()))
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Sentence case a title: lower case all words apart from the first word,
+/// the first word after a colon, and any word which already has a capital
+/// letter after its first character (acronyms such as DNA, or names such as
+/// McDonald).
+fn sentence_case(title: &str) -> String {
+    let mut capitalise = true;
+    let mut words = Vec::new();
+    for word in title.split(' ') {
+        let has_inner_capital = word.chars().skip(1).any(char::is_uppercase);
+        let w = if has_inner_capital {
+            word.to_string()
+        } else if capitalise {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(c) => c
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect(),
+                None => String::new(),
+            }
+        } else {
+            word.to_lowercase()
+        };
+        if !word.is_empty() {
+            capitalise = word.ends_with(':') || word.ends_with('?') || word.ends_with('!');
+        }
+        words.push(w);
+    }
+    words.join(" ")
+}
+
+/// Extract a DOI (`10.xxxx/...`) from a string such as `doi:10.1/abc`
+/// or `https://doi.org/10.1/abc`.
+fn doi_from(s: &str) -> Option<&str> {
+    let start = s.find("10.")?;
+    let doi = s[start..].split_whitespace().next()?;
+    doi.contains('/').then_some(doi.trim_end_matches('.'))
+}
+
+/// A URL written as a link, or other text as it is.
+fn linked(o: &str) -> Text {
+    match o.contains("://") {
+        true => link(o, o),
+        false => Text::plain(o),
+    }
+}
+
+/// Push the separator unless the text already ends in terminal
+/// punctuation, in which case only the trailing part of the separator
+/// (usually a space) is pushed. Avoids `Title?.`.
+fn push_terminated(a: &mut Text, sep: &str) {
+    if a.ends_with(&['.', '?', '!']) {
+        a.push_str(sep.trim_start_matches(['.', ',']));
+    } else {
+        a.push_str(sep);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    fn journal_record() -> Record {
+        Record {
+            author: vec![
+                Author {
+                    last: "Brown".into(),
+                    rest: "Max James".into(),
+                    ..Default::default()
+                },
+                Author {
+                    last: "Twyford".into(),
+                    rest: "A. D.".into(),
+                    ..Default::default()
+                },
+            ],
+            date: Some("2023".into()),
+            title: Some("Parasitic Plants and Their Hosts".into()),
+            journal: Some("New Phytologist".into()),
+            volume: Some("12".into()),
+            issue_number: Some("3".into()),
+            page_number: Some("45-67".into()),
+            other: Some("doi:10.1111/nph.12345".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn apa_journal() {
+        let mut builder = StyleBuilder::new(journal_record());
+        builder.set_style(Style::Apa);
+
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown, M. J., & Twyford, A. D. (2023). Parasitic plants and their hosts. \
+             New Phytologist, 12(3), 45\u{2013}67. https://doi.org/10.1111/nph.12345"
+        );
+    }
+
+    #[test]
+    fn apa_journal_markdown() {
+        let mut builder = StyleBuilder::new(journal_record());
+        builder.set_style(Style::Apa);
+        builder.set_markup(Markup::Markdown);
+
+        assert!(builder
+            .format()
+            .unwrap()
+            .contains("*New Phytologist*, *12*(3)"));
+    }
+
+    #[test]
+    fn apa_book() {
+        let record = Record {
+            author_np: Some("World Health Organization".into()),
+            title: Some("Global Report on DNA: A Summary".into()),
+            book: Some(" ".into()),
+            issuer: Some("WHO Press".into()),
+            ..Default::default()
+        };
+        let mut builder = StyleBuilder::new(record);
+        builder.set_style(Style::Apa);
+
+        assert_eq!(
+            builder.format().unwrap(),
+            "World Health Organization. (n.d.). Global report on DNA: A summary. WHO Press."
+        );
+    }
+
+    #[test]
+    fn harvard_journal() {
+        let record = Record {
+            author: vec![Author {
+                last: "Brown".into(),
+                rest: "M.".into(),
+                ..Default::default()
+            }],
+            date: Some("2023".into()),
+            title: Some("A title".into()),
+            journal: Some("PNAS".into()),
+            volume: Some("12".into()),
+            page_number: Some("123-9".into()),
+            ..Default::default()
+        };
+        let mut builder = StyleBuilder::new(record);
+
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown M. (2023) A title. PNAS, 12, pp. 123\u{2013}129."
+        );
+        // pages which can't be parsed are written as they are
+        for (pages, written) in [
+            ("pp. 12-15", "pp. 12-15"),
+            ("12, 15-18", "pp. 12, 15-18"),
+            ("198-02", "pp. 198-02"),
+            ("C", "p. C"),
+        ] {
+            let builder = StyleBuilder::new(Record {
+                page_number: Some(pages.into()),
+                ..builder.inner.clone()
+            });
+            assert_eq!(
+                builder.format().unwrap(),
+                format!("Brown M. (2023) A title. PNAS, 12, {}.", written)
+            );
+        }
+
+        // the volume is bold
+        builder.set_markup(Markup::Html);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown M. (2023) A title. PNAS, <b>12</b>, pp. 123\u{2013}129."
+        );
+    }
+
+    #[test]
+    fn values_are_text() {
+        // the values of a record are kept as they are, and can't add
+        // markup of their own
+        let mut builder = StyleBuilder::new(Record {
+            author_np: Some("WHO".into()),
+            title: Some("A <i>sneaky</i> \u{e000}title".into()),
+            journal: Some("J".into()),
+            date: Some("2020".into()),
+            other: Some("https://example.com/\u{e005}x".into()),
+            ..Default::default()
+        });
+        builder.set_style(Style::Apa);
+        builder.set_markup(Markup::Html);
+        assert_eq!(
+            builder.format().unwrap(),
+            "WHO. (2020). A &lt;i&gt;sneaky&lt;/i&gt; \u{e000}title. <i>J</i>. \
+             <a href=\"https://example.com/\u{e005}x\">https://example.com/\u{e005}x</a>"
+        );
+    }
+
+    #[test]
+    fn apa_dates() {
+        let mut record = Record {
+            author_np: Some("WHO".into()),
+            title: Some("Report".into()),
+            book: Some(" ".into()),
+            date: Some("In press".into()),
+            ..Default::default()
+        };
+        let format = |record: &Record| {
+            let mut builder = StyleBuilder::new(record.clone());
+            builder.set_style(Style::Apa);
+            builder.format()
+        };
+        assert_eq!(format(&record).unwrap(), "WHO. (in press). Report.");
+
+        record.date = Some("c. 1850".into());
+        assert_eq!(format(&record).unwrap(), "WHO. (ca. 1850). Report.");
+
+        // a letter after the year, and a season
+        record.date = Some("1999a".into());
+        assert_eq!(format(&record).unwrap(), "WHO. (1999a). Report.");
+        record.date = Some("Spring 2020".into());
+        assert_eq!(format(&record).unwrap(), "WHO. (2020). Report.");
+
+        record.date = Some("sometime".into());
+        let err = format(&record).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Date(_)));
+    }
+
+    fn chapter_record() -> Record {
+        Record {
+            author: vec![Author {
+                last: "Brown".into(),
+                rest: "Max".into(),
+                ..Default::default()
+            }],
+            date: Some("2020".into()),
+            title: Some("Parasitic Plants".into()),
+            book: Some("The Biology of Plants".into()),
+            editor: vec!["Jones, K.".into(), "Smith, P. R.".into()],
+            issuer: Some("OUP".into()),
+            place: Some("Oxford".into()),
+            page_number: Some("45-67".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn apa_chapter() {
+        let mut builder = StyleBuilder::new(chapter_record());
+        builder.set_style(Style::Apa);
+
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown, M. (2020). Parasitic plants. In K. Jones & P. R. Smith (Eds.), \
+             The biology of plants (pp. 45\u{2013}67). OUP."
+        );
+    }
+
+    #[test]
+    fn harvard_chapter() {
+        let builder = StyleBuilder::new(chapter_record());
+
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown Max (2020) Parasitic Plants. In: Jones K. and Smith P. R. (eds.) \
+             The Biology of Plants. Oxford: OUP, pp. 45\u{2013}67."
+        );
+    }
+
+    #[test]
+    fn apa_report_and_thesis() {
+        let mut record = Record {
+            author_np: Some("NASA".into()),
+            date: Some("1999".into()),
+            title: Some("Mars Climate Orbiter".into()),
+            report: Some("TR-12".into()),
+            issuer: Some("JPL".into()),
+            ..Default::default()
+        };
+        let format = |record: &Record| {
+            let mut builder = StyleBuilder::new(record.clone());
+            builder.set_style(Style::Apa);
+            builder.format().unwrap()
+        };
+        assert_eq!(
+            format(&record),
+            "NASA. (1999). Mars climate orbiter (Report No. TR-12). JPL."
+        );
+
+        record.report = Some("Doctoral dissertation".into());
+        assert_eq!(
+            format(&record),
+            "NASA. (1999). Mars climate orbiter [Doctoral dissertation, JPL]."
+        );
+    }
+
+    #[test]
+    fn chicago_journal() {
+        let mut builder = StyleBuilder::new(journal_record());
+        builder.set_style(Style::ChicagoNotes);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown, Max James, and A. D. Twyford. \u{201c}Parasitic Plants and Their Hosts.\u{201d} \
+             New Phytologist 12, no. 3 (2023): 45\u{2013}67. https://doi.org/10.1111/nph.12345."
+        );
+
+        builder.set_style(Style::ChicagoAuthorDate);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown, Max James, and A. D. Twyford. 2023. \u{201c}Parasitic Plants and Their Hosts.\u{201d} \
+             New Phytologist 12 (3): 45\u{2013}67. https://doi.org/10.1111/nph.12345."
+        );
+
+        assert_eq!(
+            builder.format_note(Note::Long).unwrap(),
+            "Max James Brown and A. D. Twyford, \u{201c}Parasitic Plants and Their Hosts,\u{201d} \
+             New Phytologist 12, no. 3 (2023): 45\u{2013}67, https://doi.org/10.1111/nph.12345."
+        );
+        assert_eq!(
+            builder.format_note(Note::Short).unwrap(),
+            "Brown and Twyford, \u{201c}Parasitic Plants.\u{201d}"
+        );
+    }
+
+    #[test]
+    fn chicago_chapter() {
+        let mut builder = StyleBuilder::new(chapter_record());
+        builder.set_style(Style::ChicagoNotes);
+        builder.set_markup(Markup::Markdown);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown, Max. \u{201c}Parasitic Plants.\u{201d} In *The Biology of Plants*, edited by \
+             K. Jones and P. R. Smith, 45\u{2013}67. Oxford: OUP, 2020."
+        );
+
+        builder.set_style(Style::ChicagoAuthorDate);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown, Max. 2020. \u{201c}Parasitic Plants.\u{201d} In *The Biology of Plants*, \
+             edited by K. Jones and P. R. Smith, 45\u{2013}67. Oxford: OUP."
+        );
+
+        assert_eq!(
+            builder.format_note(Note::Long).unwrap(),
+            "Max Brown, \u{201c}Parasitic Plants,\u{201d} in *The Biology of Plants*, \
+             ed. K. Jones and P. R. Smith (Oxford: OUP, 2020), 45\u{2013}67."
+        );
+        assert_eq!(
+            builder.format_note(Note::Short).unwrap(),
+            "Brown, \u{201c}Parasitic Plants.\u{201d}"
+        );
+    }
+
+    #[test]
+    fn chicago_report() {
+        let record = Record {
+            author_np: Some("NASA".into()),
+            date: Some("1999".into()),
+            title: Some("Mars Climate Orbiter: Mishap Investigation".into()),
+            report: Some("TR-12".into()),
+            issuer: Some("JPL".into()),
+            place: Some("Pasadena".into()),
+            rec_type: RecordType::Report,
+            ..Default::default()
+        };
+        let mut builder = StyleBuilder::new(record);
+        builder.set_style(Style::ChicagoNotes);
+        assert_eq!(
+            builder.format().unwrap(),
+            "NASA. Mars Climate Orbiter: Mishap Investigation. Report TR-12. Pasadena: JPL, 1999."
+        );
+
+        builder.set_style(Style::ChicagoAuthorDate);
+        assert_eq!(
+            builder.format().unwrap(),
+            "NASA. 1999. Mars Climate Orbiter: Mishap Investigation. Report TR-12. Pasadena: JPL."
+        );
+
+        assert_eq!(
+            builder.format_note(Note::Long).unwrap(),
+            "NASA, Mars Climate Orbiter: Mishap Investigation, Report TR-12 (Pasadena: JPL, 1999)."
+        );
+        assert_eq!(
+            builder.format_note(Note::Short).unwrap(),
+            "NASA, Mars Climate Orbiter."
+        );
+    }
+
+    #[test]
+    fn vancouver() {
+        let mut builder = StyleBuilder::new(journal_record());
+        builder.set_style(Style::Vancouver);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown MJ, Twyford AD. Parasitic Plants and Their Hosts. New Phytol. \
+             2023;12(3):45-67. doi:10.1111/nph.12345"
+        );
+
+        let mut builder = StyleBuilder::new(chapter_record());
+        builder.set_style(Style::Vancouver);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown M. Parasitic Plants. In: Jones K, Smith PR, editors. \
+             The Biology of Plants. Oxford: OUP; 2020. p. 45-67."
+        );
+
+        // at most six authors, and only the differing digits of the pages
+        let author = Author {
+            last: "Smith".into(),
+            rest: "Jo-Anne B.".into(),
+            ..Default::default()
+        };
+        let mut builder = StyleBuilder::new(Record {
+            author: vec![author; 7],
+            journal: Some("Journal of the American Medical Association".into()),
+            page_number: Some("321-328".into()),
+            other: None,
+            ..journal_record()
+        });
+        builder.set_style(Style::Vancouver);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Smith JAB, Smith JAB, Smith JAB, Smith JAB, Smith JAB, Smith JAB, et al. \
+             Parasitic Plants and Their Hosts. J Am Med Assoc. 2023;12(3):321-8."
+        );
+
+        // undated, without a doubled period after n.d.
+        let mut builder = StyleBuilder::new(Record {
+            author_np: Some("World Health Organization".into()),
+            title: Some("Malaria Report".into()),
+            report: Some("12".into()),
+            rec_type: RecordType::Report,
+            ..Default::default()
+        });
+        builder.set_style(Style::Vancouver);
+        assert_eq!(
+            builder.format().unwrap(),
+            "World Health Organization. Malaria Report. n.d. Report No.: 12."
+        );
+
+        let mut builder = StyleBuilder::new(Record {
+            date: None,
+            volume: None,
+            issue_number: None,
+            page_number: None,
+            other: None,
+            ..journal_record()
+        });
+        builder.set_style(Style::Vancouver);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown MJ, Twyford AD. Parasitic Plants and Their Hosts. New Phytol. n.d."
+        );
+    }
+
+    #[test]
+    fn ieee() {
+        let mut builder = StyleBuilder::new(Record {
+            date: Some("March 2023".into()),
+            ..journal_record()
+        });
+        builder.set_style(Style::Ieee);
+        builder.set_markup(Markup::Markdown);
+        assert_eq!(
+            builder.format().unwrap(),
+            "M. J. Brown and A. D. Twyford, \u{201c}Parasitic Plants and Their Hosts,\u{201d} \
+             *New Phytologist*, vol. 12, no. 3, pp. 45\u{2013}67, Mar. 2023, \
+             doi: [10.1111/nph.12345](https://doi.org/10.1111/nph.12345)."
+        );
+
+        let mut builder = StyleBuilder::new(chapter_record());
+        builder.set_style(Style::Ieee);
+        assert_eq!(
+            builder.format().unwrap(),
+            "M. Brown, \u{201c}Parasitic Plants,\u{201d} in The Biology of Plants, \
+             K. Jones and P. R. Smith, Eds. Oxford: OUP, 2020, pp. 45\u{2013}67."
+        );
+
+        let mut builder = StyleBuilder::new(Record {
+            author_np: Some("NASA".into()),
+            date: Some("1999".into()),
+            title: Some("Mars Climate Orbiter".into()),
+            report: Some("TR-12".into()),
+            issuer: Some("JPL".into()),
+            place: Some("Pasadena, CA, USA".into()),
+            rec_type: RecordType::Report,
+            ..Default::default()
+        });
+        builder.set_style(Style::Ieee);
+        assert_eq!(
+            builder.format().unwrap(),
+            "NASA, \u{201c}Mars Climate Orbiter,\u{201d} JPL, Pasadena, CA, USA, Rep. TR-12, 1999."
+        );
+
+        let mut builder = StyleBuilder::new(Record {
+            author: chapter_record().author,
+            date: Some("2020".into()),
+            title: Some("The Biology of Plants".into()),
+            issuer: Some("OUP".into()),
+            place: Some("Oxford, U.K.".into()),
+            ..Default::default()
+        });
+        builder.set_style(Style::Ieee);
+        assert_eq!(
+            builder.format().unwrap(),
+            "M. Brown, The Biology of Plants. Oxford, U.K.: OUP, 2020."
+        );
+
+        let date = |d: &str| abbreviated_date(&d.parse().unwrap(), &IEEE_MONTHS, false);
+        assert_eq!(date("May-June 2020"), "May\u{2013}Jun. 2020");
+        assert_eq!(date("September 2019-2020"), "Sept. 2019\u{2013}2020");
+        assert_eq!(date("Spring 2020"), "Spring 2020");
+        assert_eq!(date("Spring-Summer 2020b"), "Spring\u{2013}Summer 2020b");
+    }
+
+    #[test]
+    fn mla() {
+        let mut builder = StyleBuilder::new(journal_record());
+        builder.set_style(Style::Mla);
+        builder.set_markup(Markup::Markdown);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown, Max James, and A. D. Twyford. \u{201c}Parasitic Plants and Their Hosts.\u{201d} \
+             *New Phytologist*, vol. 12, no. 3, 2023, pp. 45\u{2013}67, \
+             <https://doi.org/10.1111/nph.12345>."
+        );
+
+        // three authors or more are shortened
+        let mut author = chapter_record().author;
+        author.extend(journal_record().author);
+        let mut builder = StyleBuilder::new(Record {
+            author,
+            date: Some("1 March 2020".into()),
+            page_number: Some("103-104".into()),
+            ..chapter_record()
+        });
+        builder.set_style(Style::Mla);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown, Max, et al. \u{201c}Parasitic Plants.\u{201d} The Biology of Plants, \
+             edited by K. Jones and P. R. Smith, OUP, 1 Mar. 2020, pp. 103\u{2013}04."
+        );
+
+        let mut builder = StyleBuilder::new(Record {
+            rec_type: RecordType::Thesis,
+            report: Some("PhD dissertation".into()),
+            issuer: Some("U of Edinburgh".into()),
+            book: None,
+            editor: Vec::new(),
+            page_number: None,
+            ..chapter_record()
+        });
+        builder.set_style(Style::Mla);
+        assert_eq!(
+            builder.format().unwrap(),
+            "Brown, Max. Parasitic Plants. 2020. U of Edinburgh, PhD dissertation."
+        );
+    }
+
+    #[test]
+    fn every_record_type() {
+        let types = [
+            RecordType::None,
+            RecordType::Book,
+            RecordType::Journal,
+            RecordType::ArticleInBook,
+            RecordType::Report,
+            RecordType::Government,
+            RecordType::Thesis,
+            RecordType::Conference,
+            RecordType::Misc,
+        ];
+        let styles = || {
+            [
+                Style::Apa,
+                Style::Harvard,
+                Style::ChicagoAuthorDate,
+                Style::ChicagoNotes,
+                Style::Vancouver,
+                Style::Ieee,
+                Style::Mla,
+            ]
+        };
+        // with authors, with only a %Q, and with neither
+        let records = [
+            chapter_record(),
+            Record {
+                author: Vec::new(),
+                author_np: Some("Royal Botanic Gardens".into()),
+                ..chapter_record()
+            },
+            Record {
+                author: Vec::new(),
+                ..chapter_record()
+            },
+        ];
+        for rec_type in types {
+            for record in &records {
+                for style in styles() {
+                    let mut builder = StyleBuilder::new(Record {
+                        rec_type,
+                        ..record.clone()
+                    });
+                    builder.set_style(style);
+                    assert!(builder.format().is_ok());
+                    assert!(builder.format_note(Note::Long).is_ok());
+                    assert!(builder.format_note(Note::Short).is_ok());
+                }
+            }
+        }
+
+        // an empty record is a misc work
+        for style in styles() {
+            let mut builder = StyleBuilder::new(Record::default());
+            builder.set_style(style);
+            assert!(builder.format().is_ok());
+            assert!(builder.format_note(Note::Long).is_ok());
+            assert!(builder.format_note(Note::Short).is_ok());
+        }
+        let mut builder = StyleBuilder::new(Record::default());
+        builder.set_style(Style::Apa);
+        assert_eq!(builder.format().unwrap(), "(n.d.).");
+
+        // a %Q takes the place of the authors
+        let mut builder = StyleBuilder::new(records[1].clone());
+        builder.set_style(Style::Harvard);
+        assert!(builder
+            .format()
+            .unwrap()
+            .starts_with("Royal Botanic Gardens (2020) "));
+    }
 }
@@ -0,0 +1,547 @@
+use std::fmt;
+
+/// Italic text, or nothing if the text is empty.
+pub(crate) fn italic(text: impl Into<Text>) -> Text {
+    let text = text.into();
+    match text.is_empty() {
+        true => Text::default(),
+        false => Text(vec![Span::Italic(text)]),
+    }
+}
+
+/// Bold text, or nothing if the text is empty.
+pub(crate) fn bold(text: impl Into<Text>) -> Text {
+    let text = text.into();
+    match text.is_empty() {
+        true => Text::default(),
+        false => Text(vec![Span::Bold(text)]),
+    }
+}
+
+/// A link to a URL.
+pub(crate) fn link(url: &str, text: &str) -> Text {
+    Text(vec![Span::Link {
+        url: url.to_string(),
+        text: Text::plain(text),
+    }])
+}
+
+/// How formatted text is marked up. Plain text has no way to show
+/// italics, so by default they are left unmarked.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    #[default]
+    Plain,
+    /// Italics in `*`, bold in `**` and links in `<>` or `[]()`.
+    Markdown,
+    /// `<i>`, `<b>` and `<a href>`, with `&`, `<`, `>` and `"` escaped.
+    Html,
+    /// `\textit`, `\textbf` and `\href` (or `\url`) from the hyperref
+    /// package, with the special characters escaped.
+    Latex,
+    /// Font changes such as `\fI…\fP`, for groff and troff documents.
+    Troff,
+    /// `{\i …}`, `{\b …}` and `HYPERLINK` fields, to be put in an RTF
+    /// document, e.g. for a word processor.
+    Rtf,
+}
+
+/// A span of formatted text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Span {
+    /// Text without any formatting.
+    Plain(String),
+    /// Italic text, e.g. the title of a journal or a book.
+    Italic(Text),
+    /// Bold text.
+    Bold(Text),
+    /// A link, e.g. to a DOI.
+    Link {
+        /// Where the link goes.
+        url: String,
+        /// The text of the link.
+        text: Text,
+    },
+}
+
+/// Formatted text, such as a reference made by a
+/// [`StyleBuilder`](crate::StyleBuilder), as a tree of [`Span`]s.
+///
+/// It is written as plain text with `Display`, and with any [`Markup`]
+/// by [`Text::render`].
+///
+/// # Example
+///
+/// ```
+/// use refer::{Author, Markup, Record, Style, StyleBuilder};
+///
+/// let record = Record {
+///     author: vec![Author { last: "Brown".into(), rest: "Max".into(), ..Default::default() }],
+///     title: Some("Parasitic plants".into()),
+///     journal: Some("Plant & Cell".into()),
+///     date: Some("2023".into()),
+///     ..Default::default()
+/// };
+/// let mut builder = StyleBuilder::new(record);
+/// builder.set_style(Style::Apa);
+/// let text = builder.format_text().unwrap();
+///
+/// assert_eq!(text.to_string(), "Brown, M. (2023). Parasitic plants. Plant & Cell.");
+/// assert_eq!(
+///     text.render(Markup::Html),
+///     "Brown, M. (2023). Parasitic plants. <i>Plant &amp; Cell</i>."
+/// );
+/// assert_eq!(
+///     text.render(Markup::Latex),
+///     "Brown, M. (2023). Parasitic plants. \\textit{Plant \\& Cell}."
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Text(Vec<Span>);
+
+impl Text {
+    /// Text of one plain span.
+    pub fn plain(s: &str) -> Text {
+        match s.is_empty() {
+            true => Text::default(),
+            false => Text(vec![Span::Plain(s.to_string())]),
+        }
+    }
+
+    /// The spans of the text.
+    pub fn spans(&self) -> &[Span] {
+        &self.0
+    }
+
+    /// Whether there is no text.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Push plain text onto the end.
+    pub(crate) fn push_str(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        match self.0.last_mut() {
+            Some(Span::Plain(p)) => p.push_str(s),
+            _ => self.0.push(Span::Plain(s.to_string())),
+        }
+    }
+
+    /// Push a plain character onto the end.
+    pub(crate) fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Push text onto the end, joining plain spans which meet.
+    pub(crate) fn append(&mut self, text: Text) {
+        for span in text.0 {
+            match span {
+                Span::Plain(s) => self.push_str(&s),
+                span => self.0.push(span),
+            }
+        }
+    }
+
+    /// Join texts with a plain separator.
+    pub(crate) fn join(texts: impl IntoIterator<Item = Text>, separator: &str) -> Text {
+        let mut joined = Text::default();
+        for (i, text) in texts.into_iter().enumerate() {
+            if i > 0 {
+                joined.push_str(separator);
+            }
+            joined.append(text);
+        }
+        joined
+    }
+
+    /// The characters of the text, from the end, including those inside
+    /// spans.
+    pub(crate) fn rev_chars(&self) -> Box<dyn Iterator<Item = char> + '_> {
+        Box::new(self.0.iter().rev().flat_map(|span| match span {
+            Span::Plain(s) => Box::new(s.chars().rev()),
+            Span::Italic(t) | Span::Bold(t) | Span::Link { text: t, .. } => t.rev_chars(),
+        }))
+    }
+
+    /// Whether the text ends with one of `chars`, inside a span or not.
+    pub(crate) fn ends_with(&self, chars: &[char]) -> bool {
+        self.rev_chars().next().is_some_and(|c| chars.contains(&c))
+    }
+
+    /// Remove `chars` from the end of the plain text at the end. Text
+    /// inside a span is kept.
+    pub(crate) fn trim_end_matches(&mut self, chars: &[char]) {
+        while let Some(Span::Plain(p)) = self.0.last_mut() {
+            p.truncate(p.trim_end_matches(chars).len());
+            match p.is_empty() {
+                true => self.0.pop(),
+                false => break,
+            };
+        }
+    }
+
+    /// Remove white space from the end of the plain text at the end.
+    pub(crate) fn trim_end(&mut self) {
+        while let Some(Span::Plain(p)) = self.0.last_mut() {
+            p.truncate(p.trim_end().len());
+            match p.is_empty() {
+                true => self.0.pop(),
+                false => break,
+            };
+        }
+    }
+
+    /// Remove `c` from the start of the text, if it starts with it
+    /// outside a span.
+    #[cfg(feature = "csl")]
+    pub(crate) fn strip_prefix(&mut self, c: char) {
+        if let Some(Span::Plain(p)) = self.0.first_mut() {
+            if p.starts_with(c) {
+                p.remove(0);
+                if p.is_empty() {
+                    self.0.remove(0);
+                }
+            }
+        }
+    }
+
+    /// Change the plain text of every span, in order, with `f`.
+    #[cfg(feature = "csl")]
+    pub(crate) fn map_plain(&mut self, f: &mut impl FnMut(&str) -> String) {
+        for span in &mut self.0 {
+            match span {
+                Span::Plain(s) => *s = f(s),
+                Span::Italic(t) | Span::Bold(t) | Span::Link { text: t, .. } => t.map_plain(f),
+            }
+        }
+        self.0.retain(|span| match span {
+            Span::Plain(s) => !s.is_empty(),
+            Span::Italic(t) | Span::Bold(t) => !t.is_empty(),
+            Span::Link { .. } => true,
+        });
+    }
+
+    /// Render the text with a markup.
+    pub fn render(&self, markup: Markup) -> String {
+        let mut out = String::new();
+        match markup {
+            Markup::Troff => self.troff(&mut out, Font::default()),
+            _ => self.write(&mut out, markup),
+        }
+        out
+    }
+
+    fn write(&self, out: &mut String, markup: Markup) {
+        for span in &self.0 {
+            match span {
+                Span::Plain(s) => escape(out, s, markup),
+                Span::Italic(t) => {
+                    let (open, close) = match markup {
+                        Markup::Plain | Markup::Troff => ("", ""),
+                        Markup::Markdown => ("*", "*"),
+                        Markup::Html => ("<i>", "</i>"),
+                        Markup::Latex => ("\\textit{", "}"),
+                        Markup::Rtf => ("{\\i ", "}"),
+                    };
+                    out.push_str(open);
+                    t.write(out, markup);
+                    out.push_str(close);
+                }
+                Span::Bold(t) => {
+                    let (open, close) = match markup {
+                        Markup::Plain | Markup::Troff => ("", ""),
+                        Markup::Markdown => ("**", "**"),
+                        Markup::Html => ("<b>", "</b>"),
+                        Markup::Latex => ("\\textbf{", "}"),
+                        Markup::Rtf => ("{\\b ", "}"),
+                    };
+                    out.push_str(open);
+                    t.write(out, markup);
+                    out.push_str(close);
+                }
+                Span::Link { url, text } => {
+                    let bare = text.to_string() == *url;
+                    match markup {
+                        Markup::Plain | Markup::Troff => text.write(out, markup),
+                        Markup::Markdown if bare => out.push_str(&format!("<{}>", url)),
+                        Markup::Markdown => {
+                            out.push('[');
+                            text.write(out, markup);
+                            out.push_str(&format!("]({})", url.replace(')', "%29")));
+                        }
+                        Markup::Html => {
+                            out.push_str("<a href=\"");
+                            escape(out, url, markup);
+                            out.push_str("\">");
+                            text.write(out, markup);
+                            out.push_str("</a>");
+                        }
+                        Markup::Latex if bare => {
+                            out.push_str(&format!("\\url{{{}}}", url_latex(url)))
+                        }
+                        Markup::Latex => {
+                            out.push_str(&format!("\\href{{{}}}{{", url_latex(url)));
+                            text.write(out, markup);
+                            out.push('}');
+                        }
+                        Markup::Rtf => {
+                            out.push_str("{\\field{\\*\\fldinst{HYPERLINK \"");
+                            escape(out, url, markup);
+                            out.push_str("\"}}{\\fldrslt{");
+                            text.write(out, markup);
+                            out.push_str("}}}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Troff font changes don't nest: `\fP` goes back to the previous
+    /// font only, so the font outside a nested span is set again.
+    fn troff(&self, out: &mut String, font: Font) {
+        for span in &self.0 {
+            let (text, inner) = match span {
+                Span::Plain(s) => {
+                    escape_troff(out, s);
+                    continue;
+                }
+                Span::Link { text, .. } => (text, font),
+                Span::Italic(text) => (
+                    text,
+                    Font {
+                        italic: true,
+                        ..font
+                    },
+                ),
+                Span::Bold(text) => (text, Font { bold: true, ..font }),
+            };
+            if inner == font {
+                text.troff(out, font);
+                continue;
+            }
+            out.push_str(inner.escape());
+            text.troff(out, inner);
+            match font == Font::default() && !text.changes_font(inner) {
+                true => out.push_str("\\fP"),
+                false => out.push_str(font.escape()),
+            }
+        }
+    }
+
+    /// Whether any span inside changes from the font.
+    fn changes_font(&self, font: Font) -> bool {
+        self.0.iter().any(|span| match span {
+            Span::Plain(_) => false,
+            Span::Italic(_) if !font.italic => true,
+            Span::Bold(_) if !font.bold => true,
+            Span::Italic(t) | Span::Bold(t) | Span::Link { text: t, .. } => t.changes_font(font),
+        })
+    }
+}
+
+/// The text without any markup.
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.render(Markup::Plain))
+    }
+}
+
+impl From<&str> for Text {
+    fn from(s: &str) -> Self {
+        Text::plain(s)
+    }
+}
+
+impl From<&String> for Text {
+    fn from(s: &String) -> Self {
+        Text::plain(s)
+    }
+}
+
+impl From<String> for Text {
+    fn from(s: String) -> Self {
+        match s.is_empty() {
+            true => Text::default(),
+            false => Text(vec![Span::Plain(s)]),
+        }
+    }
+}
+
+/// A troff font.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Font {
+    italic: bool,
+    bold: bool,
+}
+
+impl Font {
+    fn escape(self) -> &'static str {
+        match (self.italic, self.bold) {
+            (false, false) => "\\fR",
+            (true, false) => "\\fI",
+            (false, true) => "\\fB",
+            (true, true) => "\\f(BI",
+        }
+    }
+}
+
+fn escape(out: &mut String, s: &str, markup: Markup) {
+    for c in s.chars() {
+        match (markup, c) {
+            (Markup::Html, '&') => out.push_str("&amp;"),
+            (Markup::Html, '<') => out.push_str("&lt;"),
+            (Markup::Html, '>') => out.push_str("&gt;"),
+            (Markup::Html, '"') => out.push_str("&quot;"),
+            (Markup::Markdown, '\\' | '*' | '_' | '[' | ']' | '`' | '<') => {
+                out.push('\\');
+                out.push(c);
+            }
+            (Markup::Latex, '&' | '%' | '$' | '#' | '_' | '{' | '}') => {
+                out.push('\\');
+                out.push(c);
+            }
+            (Markup::Latex, '~') => out.push_str("\\textasciitilde{}"),
+            (Markup::Latex, '^') => out.push_str("\\textasciicircum{}"),
+            (Markup::Latex, '\\') => out.push_str("\\textbackslash{}"),
+            (Markup::Rtf, '\\' | '{' | '}') => {
+                out.push('\\');
+                out.push(c);
+            }
+            // RTF is 7-bit, with other characters as signed UTF-16
+            // code units and a `?` for readers without Unicode
+            (Markup::Rtf, c) if !c.is_ascii() => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    out.push_str(&format!("\\u{}?", *unit as i16));
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+}
+
+/// In a URL, `%` and `#` must be escaped, and `\` and braces can't be.
+fn url_latex(url: &str) -> String {
+    url.replace('%', "\\%").replace('#', "\\#")
+}
+
+/// Escape the backslash, the typographic characters which have troff
+/// names, and control characters at the start of a line.
+fn escape_troff(out: &mut String, s: &str) {
+    for c in s.chars() {
+        if matches!(c, '.' | '\'') && (out.is_empty() || out.ends_with('\n')) {
+            out.push_str("\\&");
+        }
+        match c {
+            '\\' => out.push_str("\\e"),
+            '\u{2013}' => out.push_str("\\(en"),
+            '\u{2014}' => out.push_str("\\(em"),
+            '\u{201c}' => out.push_str("\\(lq"),
+            '\u{201d}' => out.push_str("\\(rq"),
+            '\u{2018}' => out.push_str("\\(oq"),
+            '\u{2019}' => out.push_str("\\(cq"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Text {
+        let mut title = Text::plain("A ");
+        title.append(bold("bold"));
+        title.push_str(" <title>");
+        let mut text = Text::plain("Brown & ");
+        text.append(italic(title));
+        text.push_str(". ");
+        text.append(bold("12"));
+        text.push_str(", ");
+        text.append(link("https://doi.org/10.1/a_b", "https://doi.org/10.1/a_b"));
+        text
+    }
+
+    #[test]
+    fn tree() {
+        let text = sample();
+        assert_eq!(text.spans().len(), 6);
+        assert_eq!(text.spans()[0], Span::Plain("Brown & ".into()));
+        assert!(matches!(&text.spans()[1], Span::Italic(t) if t.spans().len() == 3));
+        assert_eq!(
+            text.to_string(),
+            "Brown & A bold <title>. 12, https://doi.org/10.1/a_b"
+        );
+        // empty spans are left out, and plain text is joined
+        let mut text = Text::plain("a");
+        text.append(italic(""));
+        text.push('b');
+        assert_eq!(text, Text::plain("ab"));
+    }
+
+    #[test]
+    fn edit() {
+        let mut text = Text::plain(".a ");
+        text.append(italic("b."));
+        assert!(text.ends_with(&['.']));
+        text.push_str(", ");
+        text.trim_end_matches(&[',', ' ']);
+        assert!(matches!(text.spans().last(), Some(Span::Italic(_))));
+        assert_eq!(text.render(Markup::Markdown), ".a *b.*");
+        text.trim_end();
+        assert_eq!(text.to_string(), ".a b.");
+        assert_eq!(
+            Text::join([Text::plain("a"), italic("b")], ", ").render(Markup::Html),
+            "a, <i>b</i>"
+        );
+    }
+
+    #[test]
+    fn renderers() {
+        let text = sample();
+        assert_eq!(
+            text.render(Markup::Markdown),
+            "Brown & *A **bold** \\<title>*. **12**, <https://doi.org/10.1/a_b>"
+        );
+        assert_eq!(
+            text.render(Markup::Html),
+            "Brown &amp; <i>A <b>bold</b> &lt;title&gt;</i>. <b>12</b>, \
+             <a href=\"https://doi.org/10.1/a_b\">https://doi.org/10.1/a_b</a>"
+        );
+        assert_eq!(
+            text.render(Markup::Latex),
+            "Brown \\& \\textit{A \\textbf{bold} <title>}. \\textbf{12}, \
+             \\url{https://doi.org/10.1/a_b}"
+        );
+        assert_eq!(
+            text.render(Markup::Troff),
+            "Brown & \\fIA \\f(BIbold\\fI <title>\\fR. \\fB12\\fP, https://doi.org/10.1/a_b"
+        );
+        assert_eq!(
+            text.render(Markup::Rtf),
+            "Brown & {\\i A {\\b bold} <title>}. {\\b 12}, \
+             {\\field{\\*\\fldinst{HYPERLINK \"https://doi.org/10.1/a_b\"}}\
+             {\\fldrslt{https://doi.org/10.1/a_b}}}"
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let text = Text::plain(".In \u{201c}C\\D\u{201d} 1\u{2013}2 {50%} caf\u{e9} \u{1f600}");
+        assert_eq!(
+            text.render(Markup::Troff),
+            "\\&.In \\(lqC\\eD\\(rq 1\\(en2 {50%} caf\u{e9} \u{1f600}"
+        );
+        assert_eq!(
+            text.render(Markup::Latex),
+            ".In \u{201c}C\\textbackslash{}D\u{201d} 1\u{2013}2 \\{50\\%\\} caf\u{e9} \u{1f600}"
+        );
+        assert_eq!(
+            text.render(Markup::Rtf),
+            ".In \\u8220?C\\\\D\\u8221? 1\\u8211?2 \\{50%\\} caf\\u233? \\u-10179?\\u-8704?"
+        );
+    }
+}
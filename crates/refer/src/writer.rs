@@ -1,15 +1,22 @@
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::{self, BufWriter, IntoInnerError, Write};
 use std::path::Path;
 use std::result::Result as StdResult;
 
-use crate::{error::Result, reader::parse_input_line, record::Record};
+use crate::{
+    error::Result,
+    raw::RawRecord,
+    reader::{parse_input_line, split_fields},
+    record::Record,
+    str_from_utf8,
+};
 
 /// A writer for a refer file.
 ///
 /// It's a simple wrapper of a [io::BufWriter], along
 /// with a line number tracker to help track errors when
-/// parsing the input.
+/// parsing the input. Long fields are wrapped on to
+/// continuation lines, as in the `Display` of a [`Record`].
 pub struct Writer<W: io::Write> {
     pub wtr: io::BufWriter<W>,
     line_no: u64,
@@ -40,6 +47,32 @@ impl Writer<File> {
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Writer<File>> {
         Ok(Writer::new(File::create(path)?))
     }
+
+    /// Build a refer writer that adds records to the end of the file at
+    /// the given path. The file is created if it does not exist.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::error::Error;
+    /// use refer::{Record, Writer};
+    ///
+    /// # fn main() { example().unwrap(); }
+    /// fn example() -> Result<(), Box<dyn Error>> {
+    ///     let record = Record {
+    ///         title: Some("A title".into()),
+    ///         ..Default::default()
+    ///     };
+    ///     let mut wtr = Writer::from_path_append("foo.refer")?;
+    ///     wtr.write(&record)?;
+    ///     wtr.flush()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn from_path_append<P: AsRef<Path>>(path: P) -> Result<Writer<File>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Writer::new(file))
+    }
 }
 
 impl<W: io::Write> Writer<W> {
@@ -75,23 +108,50 @@ impl<W: io::Write> Writer<W> {
         Ok(())
     }
 
-    /// The main function used to write a record to an output buffer.
+    /// Write a record from its fields, e.g. `["%A Brown, M", "%J PNAS"]`.
+    /// Each field is parsed and checked before the record is written,
+    /// as in [`Writer::write`].
     pub fn write_record<I, T>(&mut self, record: I) -> Result<()>
     where
         I: IntoIterator<Item = T>,
-        T: AsRef<[u8]> + std::convert::Into<Vec<u8>>,
+        T: AsRef<[u8]>,
     {
         let mut record_holder = Record::default();
         for field in record {
-            self.line_no += 1;
-            let field_string = String::from_utf8(field.into()).unwrap();
-            self.check_field(field_string, &mut record_holder)?;
+            let field_string = str_from_utf8(field.as_ref())?;
+            // a field can be given along with its continuation lines
+            for f in split_fields(field_string) {
+                self.line_no += 1;
+                self.check_field(f, &mut record_holder)?;
+            }
         }
-        self.wtr.write_all(record_holder.to_string().as_bytes())?;
+        self.write(&record_holder)
+    }
+
+    /// The main function used to write a record to an output buffer.
+    /// The fields are written as in the `Display` of [`Record`], followed
+    /// by a blank line to end the record. A record which could not be
+    /// read back, e.g. with an author with no name, is an error and
+    /// nothing is written.
+    pub fn write(&mut self, record: &Record) -> Result<()> {
+        let text = record.to_string();
+        let mut checked = Record::default();
+        for field in split_fields(&text) {
+            self.check_field(field, &mut checked)?;
+        }
+        self.wtr.write_all(text.as_bytes())?;
         // must be newline at end of record
         self.wtr.write_all(b"\n")?;
         Ok(())
     }
+
+    /// Write a raw record exactly as it was read. Nothing is added, as
+    /// a raw record holds the blank line which ends it. See [`RawRecord`].
+    pub fn write_raw(&mut self, record: &RawRecord) -> Result<()> {
+        self.wtr.write_all(record.to_string().as_bytes())?;
+        Ok(())
+    }
+
     /// Flush the contents of the current buffer and return the underlying writer.
     pub fn into_inner(self) -> StdResult<W, IntoInnerError<BufWriter<W>>> {
         self.wtr.into_inner()
@@ -120,4 +180,124 @@ mod tests {
 
         assert_eq!(wtr_as_string(wtr), "%A Brown, M\n%T refer crate\n\n");
     }
+
+    #[test]
+    fn typed_record() {
+        let record = Record {
+            title: Some("refer crate".into()),
+            other: Some("doi:10.1/abc".into()),
+            ..Default::default()
+        };
+        let mut wtr = Writer::new(vec![]);
+        wtr.write(&record).unwrap();
+        wtr.write(&record).unwrap();
+
+        assert_eq!(
+            wtr_as_string(wtr),
+            "%O doi:10.1/abc\n%T refer crate\n\n%O doi:10.1/abc\n%T refer crate\n\n"
+        );
+    }
+
+    #[test]
+    fn unreadable_record() {
+        let mut record = Record {
+            author: vec![crate::Author::default()],
+            title: Some("A title".into()),
+            ..Default::default()
+        };
+        let mut wtr = Writer::new(vec![]);
+        assert!(wtr.write(&record).is_err());
+
+        record.author[0].last = "Google".into();
+        wtr.write(&record).unwrap();
+        assert_eq!(wtr_as_string(wtr), "%A Google\n%T A title\n\n");
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let mut wtr = Writer::new(vec![]);
+        let err = wtr.write_record([b"%T \xff".as_slice()]).unwrap_err();
+        assert!(matches!(err.kind(), crate::ErrorKind::Utf8(_)));
+    }
+
+    #[test]
+    fn raw_records() {
+        let data = "%A Brown,  M\n# a comment\n%T refer crate\n\n\n%T another\n";
+        let mut rdr = crate::Reader::new(data.as_bytes());
+        let mut wtr = Writer::new(vec![]);
+        for raw in rdr.raw_records() {
+            wtr.write_raw(&raw.unwrap()).unwrap();
+        }
+
+        assert_eq!(wtr_as_string(wtr), data);
+    }
+
+    #[test]
+    fn wrap_long_fields() {
+        let title = "a very long title which will not fit on a single line of a refer \
+                     database, so is wrapped on to a second line";
+        let mut wtr = Writer::new(vec![]);
+        wtr.write_record(vec![format!("%T {}", title), "%K one\ntwo".into()])
+            .unwrap();
+        let written = wtr_as_string(wtr);
+        assert_eq!(
+            written,
+            "%K one two\n%T a very long title which will not fit on a single line of a refer \
+             database, so\nis wrapped on to a second line\n\n"
+        );
+
+        let mut rdr = crate::Reader::new(written.as_bytes());
+        let record = rdr.records().next().unwrap().unwrap();
+        assert_eq!(record.title.as_deref(), Some(title));
+    }
+
+    #[test]
+    fn keep_spaces_in_values() {
+        // only the spaces where a line wraps are changed
+        for title in [
+            "A  spaced   title",
+            "a long  title with  double spaces which will not fit on a single line of  a \
+             refer database",
+        ] {
+            let record = crate::Record {
+                title: Some(title.into()),
+                ..Default::default()
+            };
+            let mut wtr = Writer::new(vec![]);
+            wtr.write(&record).unwrap();
+            let written = wtr_as_string(wtr);
+            assert!(written.starts_with(&format!("%T {}", &title[..17])));
+
+            let mut rdr = crate::Reader::new(written.as_bytes());
+            let read = rdr.records().next().unwrap().unwrap();
+            assert_eq!(read, record);
+        }
+    }
+
+    #[test]
+    fn wrap_before_comment_or_tag() {
+        // a word starting with `#` or `%` would be read back as a comment
+        // or a field if it started a continuation line
+        for mark in ['#', '%'] {
+            let title = format!(
+                "a very long title which will not fit on a single line of the refer \
+                 database {}42 tail words here",
+                mark
+            );
+            let mut wtr = Writer::new(vec![]);
+            wtr.write_record(vec![format!("%T {}", title)]).unwrap();
+            let written = wtr_as_string(wtr);
+            assert!(written.lines().skip(1).all(|l| !l.starts_with(['#', '%'])));
+
+            let mut rdr = crate::Reader::new(written.as_bytes());
+            let record = rdr.records().next().unwrap().unwrap();
+            assert_eq!(record.title.as_deref(), Some(title.as_str()));
+            let again = crate::Reader::new(record.to_string().as_bytes())
+                .records()
+                .next()
+                .unwrap()
+                .unwrap();
+            assert_eq!(again, record);
+        }
+    }
 }
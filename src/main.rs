@@ -1,9 +1,15 @@
-use refer_cli::cli;
+use refer_cli::{cli, ReferErrorKind};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     match cli() {
         Ok(_) => (),
-        Err(e) => eprintln!("Error: {}.", e),
+        // show where in the file a parsing error is
+        Err(e) => match e.kind() {
+            ReferErrorKind::ReferParse(err) if err.position().is_some() => {
+                eprint!("{}", err.render())
+            }
+            _ => eprintln!("Error: {}.", e),
+        },
     }
 
     Ok(())